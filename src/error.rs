@@ -0,0 +1,211 @@
+//! Errors returned by the counter program.
+use num_traits::FromPrimitive;
+use solana_program::{
+    decode_error::DecodeError,
+    msg,
+    program_error::{PrintProgramError, ProgramError},
+};
+use std::fmt;
+ 
+// Errors returned by the counter program, surfaced as ProgramError::Custom with
+// the code noted beside each variant. Codes are stable: variants are only ever
+// appended, so clients may match on them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CounterError {
+    Overflow,                  // code 0
+    Unauthorized,              // code 1
+    MaxValueExceeded,          // code 2
+    MaxValueBelowCount,        // code 3
+    AlreadyMigrated,           // code 4
+    InvalidStep,               // code 5
+    CooldownActive,            // code 6
+    InitialExceedsMax,         // code 7
+    GloballyPaused,            // code 8
+    AccountNotWritable,        // code 9
+    Underflow,                 // code 10
+    InvalidDecimals,           // code 11
+    StepTooLarge,              // code 12
+    RawDataTooLarge,           // code 13
+    ReserveBreached,           // code 14
+    ValueTooLarge,             // code 15
+    BpsTooLarge,               // code 16
+    Frozen,                    // code 17
+    NotInitialized,            // code 18
+    NotElapsed,                // code 19
+    InitialBelowMin,           // code 20
+    BelowMinValue,             // code 21
+    TimestampRegression,       // code 22
+    ApprovalMissing,           // code 23
+    ComputeBudgetExhausted,    // code 24
+    TooManyCounters,           // code 25
+    InvalidInstructionsSysvar, // code 26
+    HistoryFull,               // code 27
+    NotTokenHolder,            // code 28
+    InsufficientAcrossBuckets, // code 29
+    DuplicateAccountInBatch,   // code 30
+    DailyLimitExceeded,        // code 31
+    TimeLocked,                // code 32
+    IncrementStale,            // code 33
+    DivisionByZero,            // code 34
+    MetadataTooLarge,          // code 35
+    BatchTooLarge,             // code 36
+    ReturnDataTooLarge,        // code 37
+    SlotOutOfRange,            // code 38
+    NotInAllowlist,            // code 39
+    ProofTooLong,              // code 40
+    DelegateAlreadyExists,     // code 41
+    InvalidDelegate,           // code 42
+    TooManyDelegates,          // code 43
+    TimestampInFuture,         // code 44
+    InvalidTreasury,           // code 45
+}
+ 
+impl fmt::Display for CounterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Overflow => write!(f, "Counter value overflowed"),
+            Self::Unauthorized => write!(f, "Signer is not authorized for this counter"),
+            Self::MaxValueExceeded => write!(f, "Counter would exceed its max_value"),
+            Self::MaxValueBelowCount => write!(f, "max_value cannot be below the current count"),
+            Self::AlreadyMigrated => write!(f, "Counter already uses the current layout"),
+            Self::InvalidStep => write!(f, "Step must be greater than zero"),
+            Self::CooldownActive => write!(f, "Counter cooldown has not elapsed"),
+            Self::InitialExceedsMax => write!(f, "Initial value exceeds max_value"),
+            Self::GloballyPaused => write!(f, "Program is globally paused"),
+            Self::AccountNotWritable => write!(f, "Account must be writable"),
+            Self::Underflow => write!(f, "Counter value underflowed"),
+            Self::InvalidDecimals => write!(f, "Decimals must be at most 19"),
+            Self::StepTooLarge => write!(f, "Increment amount exceeds the per-call maximum"),
+            Self::RawDataTooLarge => write!(f, "Raw account data exceeds the size limit"),
+            Self::ReserveBreached => write!(
+                f,
+                "Account balance would fall below rent plus the minimum reserve"
+            ),
+            Self::ValueTooLarge => write!(f, "Requested account size is too large"),
+            Self::BpsTooLarge => write!(f, "Basis points exceed the supported maximum"),
+            Self::Frozen => write!(f, "Counter is frozen"),
+            Self::NotInitialized => write!(f, "Counter account has not been initialized"),
+            Self::NotElapsed => write!(f, "Reset period has not elapsed"),
+            Self::InitialBelowMin => write!(f, "Initial value is below min_value"),
+            Self::BelowMinValue => write!(f, "Counter would fall below min_value"),
+            Self::TimestampRegression => write!(f, "Timestamp is not after the last update"),
+            Self::ApprovalMissing => write!(f, "Missing or mismatched ed25519 approval"),
+            Self::ComputeBudgetExhausted => write!(f, "Not enough compute left to finish the loop"),
+            Self::TooManyCounters => write!(f, "Too many counters for one bulk query"),
+            Self::InvalidInstructionsSysvar => {
+                write!(f, "Expected the instructions sysvar as the last account")
+            }
+            Self::HistoryFull => write!(f, "Counter history is full"),
+            Self::NotTokenHolder => write!(f, "Signer does not hold enough of the gate token"),
+            Self::InsufficientAcrossBuckets => {
+                write!(f, "Decrement exceeds what both counters hold")
+            }
+            Self::DuplicateAccountInBatch => write!(f, "The same counter appears twice in a batch"),
+            Self::DailyLimitExceeded => write!(f, "Decrement would exceed the daily limit"),
+            Self::TimeLocked => write!(f, "Counter is time-locked"),
+            Self::IncrementStale => write!(f, "Too many slots since the last fresh increment"),
+            Self::DivisionByZero => write!(f, "Counter division by zero"),
+            Self::MetadataTooLarge => write!(f, "Counter metadata exceeds its size limit"),
+            Self::BatchTooLarge => write!(f, "Too many counters for one batch"),
+            Self::ReturnDataTooLarge => write!(f, "Return data exceeds the runtime limit"),
+            Self::SlotOutOfRange => write!(f, "Slot is outside the multi-counter"),
+            Self::NotInAllowlist => write!(f, "Signer is not in the counter's allowlist"),
+            Self::ProofTooLong => write!(f, "Merkle proof is too long"),
+            Self::DelegateAlreadyExists => write!(f, "Delegate is already in the list"),
+            Self::InvalidDelegate => write!(f, "Delegate is the zero pubkey or not in the list"),
+            Self::TooManyDelegates => write!(f, "Delegate list is full"),
+            Self::TimestampInFuture => write!(f, "Client timestamp is ahead of the cluster clock"),
+            Self::InvalidTreasury => write!(f, "Treasury cannot receive the creation fee"),
+        }
+    }
+}
+ 
+impl std::error::Error for CounterError {}
+ 
+impl From<CounterError> for ProgramError {
+    fn from(error: CounterError) -> Self {
+        ProgramError::Custom(error as u32)
+    }
+}
+ 
+impl TryFrom<u32> for CounterError {
+    type Error = ProgramError;
+ 
+    fn try_from(code: u32) -> Result<Self, Self::Error> {
+        match code {
+            0 => Ok(Self::Overflow),
+            1 => Ok(Self::Unauthorized),
+            2 => Ok(Self::MaxValueExceeded),
+            3 => Ok(Self::MaxValueBelowCount),
+            4 => Ok(Self::AlreadyMigrated),
+            5 => Ok(Self::InvalidStep),
+            6 => Ok(Self::CooldownActive),
+            7 => Ok(Self::InitialExceedsMax),
+            8 => Ok(Self::GloballyPaused),
+            9 => Ok(Self::AccountNotWritable),
+            10 => Ok(Self::Underflow),
+            11 => Ok(Self::InvalidDecimals),
+            12 => Ok(Self::StepTooLarge),
+            13 => Ok(Self::RawDataTooLarge),
+            14 => Ok(Self::ReserveBreached),
+            15 => Ok(Self::ValueTooLarge),
+            16 => Ok(Self::BpsTooLarge),
+            17 => Ok(Self::Frozen),
+            18 => Ok(Self::NotInitialized),
+            19 => Ok(Self::NotElapsed),
+            20 => Ok(Self::InitialBelowMin),
+            21 => Ok(Self::BelowMinValue),
+            22 => Ok(Self::TimestampRegression),
+            23 => Ok(Self::ApprovalMissing),
+            24 => Ok(Self::ComputeBudgetExhausted),
+            25 => Ok(Self::TooManyCounters),
+            26 => Ok(Self::InvalidInstructionsSysvar),
+            27 => Ok(Self::HistoryFull),
+            28 => Ok(Self::NotTokenHolder),
+            29 => Ok(Self::InsufficientAcrossBuckets),
+            30 => Ok(Self::DuplicateAccountInBatch),
+            31 => Ok(Self::DailyLimitExceeded),
+            32 => Ok(Self::TimeLocked),
+            33 => Ok(Self::IncrementStale),
+            34 => Ok(Self::DivisionByZero),
+            35 => Ok(Self::MetadataTooLarge),
+            36 => Ok(Self::BatchTooLarge),
+            37 => Ok(Self::ReturnDataTooLarge),
+            38 => Ok(Self::SlotOutOfRange),
+            39 => Ok(Self::NotInAllowlist),
+            40 => Ok(Self::ProofTooLong),
+            41 => Ok(Self::DelegateAlreadyExists),
+            42 => Ok(Self::InvalidDelegate),
+            43 => Ok(Self::TooManyDelegates),
+            44 => Ok(Self::TimestampInFuture),
+            45 => Ok(Self::InvalidTreasury),
+            _ => Err(ProgramError::InvalidArgument),
+        }
+    }
+}
+ 
+impl FromPrimitive for CounterError {
+    fn from_i64(code: i64) -> Option<Self> {
+        u32::try_from(code).ok()?.try_into().ok()
+    }
+ 
+    fn from_u64(code: u64) -> Option<Self> {
+        u32::try_from(code).ok()?.try_into().ok()
+    }
+}
+ 
+impl<T> DecodeError<T> for CounterError {
+    fn type_of() -> &'static str {
+        "CounterError"
+    }
+}
+ 
+// Log the variant name alongside its description
+impl PrintProgramError for CounterError {
+    fn print<E>(&self)
+    where
+        E: 'static + std::error::Error + DecodeError<E> + PrintProgramError + FromPrimitive,
+    {
+        msg!("Error: {:?}: {}", self, self);
+    }
+}
@@ -0,0 +1,805 @@
+//! Instruction opcodes, wire format and client-side builders.
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+ 
+// Opcode byte leading each instruction. These are the wire format, so they
+// are fixed here rather than derived from declaration order.
+pub const OP_INITIALIZE: u8 = 0;
+pub const OP_INCREMENT: u8 = 1;
+pub const OP_DECREMENT_SATURATING: u8 = 2;
+pub const OP_SET_DELEGATE: u8 = 3;
+pub const OP_SET_REQUIRE_SIGNER: u8 = 4;
+pub const OP_SET_MAX_VALUE: u8 = 5;
+pub const OP_NOOP: u8 = 6;
+pub const OP_MIGRATE: u8 = 7;
+pub const OP_CONFIGURE: u8 = 8;
+pub const OP_INITIALIZE_FULL: u8 = 9;
+pub const OP_SET_GLOBAL_PAUSE: u8 = 10;
+pub const OP_DECREMENT_WITH_REASON: u8 = 11;
+pub const OP_DESCRIBE: u8 = 12;
+pub const OP_MUL_ADD: u8 = 13;
+pub const OP_CLOSE_AND_TRANSFER: u8 = 14;
+pub const OP_INCREMENT_BY: u8 = 15;
+pub const OP_SET_MAX_STEP_PER_CALL: u8 = 16;
+pub const OP_INITIALIZE_RAW: u8 = 17;
+pub const OP_SET_MIN_RESERVE: u8 = 18;
+pub const OP_WITHDRAW: u8 = 19;
+pub const OP_QUERY_ACCOUNT: u8 = 20;
+pub const OP_SET_STEP_AND_TICK: u8 = 21;
+pub const OP_TOUCH: u8 = 22;
+pub const OP_INCREMENT_PERCENT: u8 = 23;
+pub const OP_SET_AUTO_FREEZE: u8 = 24;
+pub const OP_INITIALIZE_PDA: u8 = 25;
+pub const OP_ADD_SAMPLE: u8 = 26;
+pub const OP_RESET_IF_ELAPSED: u8 = 27;
+pub const OP_RESET: u8 = 28;
+pub const OP_CLOSE: u8 = 29;
+pub const OP_DECREMENT_WITH_PROGRESS: u8 = 30;
+pub const OP_METRICS: u8 = 31;
+pub const OP_SWAP: u8 = 32;
+pub const OP_SET_CATEGORY: u8 = 33;
+pub const OP_INCREMENT_AT: u8 = 34;
+pub const OP_QUERY_RENT: u8 = 35;
+pub const OP_SET_SATURATING: u8 = 36;
+pub const OP_SET_ALLOW_ZERO: u8 = 37;
+pub const OP_INCREMENT_WITH_APPROVAL: u8 = 38;
+pub const OP_INCREMENT_TIMES: u8 = 39;
+pub const OP_PROPOSE_AUTHORITY: u8 = 40;
+pub const OP_ACCEPT_AUTHORITY: u8 = 41;
+pub const OP_BULK_QUERY: u8 = 42;
+pub const OP_CHECKPOINT: u8 = 43;
+pub const OP_SET_TOKEN_GATE: u8 = 44;
+pub const OP_INCREMENT_IF_HOLDER: u8 = 45;
+pub const OP_STAMP_SLOT: u8 = 46;
+pub const OP_SET_EMIT_EVENTS: u8 = 47;
+pub const OP_DECREMENT_WITH_BORROW: u8 = 48;
+pub const OP_COPY_CONFIG_FROM: u8 = 49;
+pub const OP_INITIALIZE_STR: u8 = 50;
+pub const OP_BATCH_INCREMENT: u8 = 51;
+pub const OP_INITIALIZE_SHARED: u8 = 52;
+pub const OP_QUERY_STALENESS: u8 = 53;
+pub const OP_INITIALIZE_CONFIG: u8 = 54;
+pub const OP_SET_DAILY_LIMIT: u8 = 55;
+pub const OP_DECREMENT_DAILY: u8 = 56;
+pub const OP_APPLY_DELTA: u8 = 57;
+pub const OP_CHECK_INCREMENT_BY: u8 = 58;
+pub const OP_LOCK_UNTIL: u8 = 59;
+pub const OP_SET_EXPIRY: u8 = 60;
+pub const OP_SLOTS_REMAINING: u8 = 61;
+pub const OP_SWEEP_CLOSED: u8 = 62;
+pub const OP_INCREMENT_WITH_TIP: u8 = 63;
+pub const OP_INCREMENT_FRESH: u8 = 64;
+pub const OP_SET_META: u8 = 65;
+pub const OP_REMOVE_META: u8 = 66;
+pub const OP_SET_COUNTER: u8 = 67;
+pub const OP_INITIALIZE_MINT_COUNTER: u8 = 68;
+pub const OP_REFRESH_BUMP: u8 = 69;
+pub const OP_DECREMENT_WITH_REMAINDER: u8 = 70;
+pub const OP_INITIALIZE_MULTI_COUNTER: u8 = 71;
+pub const OP_INCREMENT_SLOT: u8 = 72;
+pub const OP_SET_SLOT: u8 = 73;
+pub const OP_DRAIN_COUNTER: u8 = 74;
+pub const OP_SET_CREATION_FEE: u8 = 75;
+pub const OP_SET_MERKLE_ROOT: u8 = 76;
+pub const OP_INCREMENT_IF_IN_TREE: u8 = 77;
+pub const OP_SYNC_FROM_LAMPORTS: u8 = 78;
+pub const OP_ADD_DELEGATE: u8 = 79;
+pub const OP_REMOVE_DELEGATE: u8 = 80;
+pub const OP_INITIALIZE_AND_INCREMENT: u8 = 81;
+ 
+// Instructions that our program can execute, tagged with their opcode
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+#[borsh(use_discriminant = true)]
+#[repr(u8)]
+pub enum CounterInstruction {
+    InitializeCounter {
+        initial_value: u64,
+    } = OP_INITIALIZE,
+    IncrementCounter = OP_INCREMENT,
+    DecrementSaturating = OP_DECREMENT_SATURATING,
+    SetDelegate {
+        delegate_pda: Pubkey,
+    } = OP_SET_DELEGATE,
+    SetRequireSigner {
+        require_signer: bool,
+    } = OP_SET_REQUIRE_SIGNER,
+    SetMaxValue {
+        max_value: u64,
+    } = OP_SET_MAX_VALUE,
+    NoOp = OP_NOOP,
+    MigrateCounter = OP_MIGRATE,
+    Configure {
+        step: Option<u64>,
+        max_value: Option<u64>,
+        cooldown_secs: Option<u32>,
+    } = OP_CONFIGURE,
+    InitializeCounterFull {
+        initial_value: u64,
+        step: u64,
+        max_value: u64,
+        require_signer: bool,
+        decimals: u8,
+        min_value: u64,
+        category: u16,
+        emit_events: bool,
+    } = OP_INITIALIZE_FULL,
+    SetGlobalPause {
+        paused: bool,
+    } = OP_SET_GLOBAL_PAUSE,
+    DecrementWithReason {
+        reason: u8,
+    } = OP_DECREMENT_WITH_REASON,
+    DescribeCounter = OP_DESCRIBE,
+    MulAdd {
+        factor: u64,
+        addend: u64,
+    } = OP_MUL_ADD,
+    CloseAndTransfer = OP_CLOSE_AND_TRANSFER,
+    IncrementBy {
+        amount: u64,
+    } = OP_INCREMENT_BY,
+    SetMaxStepPerCall {
+        max_step_per_call: u64,
+    } = OP_SET_MAX_STEP_PER_CALL,
+    InitializeRaw {
+        data: Vec<u8>,
+    } = OP_INITIALIZE_RAW,
+    SetMinReserve {
+        min_reserve: u64,
+    } = OP_SET_MIN_RESERVE,
+    Withdraw {
+        lamports: u64,
+    } = OP_WITHDRAW,
+    QueryAccount = OP_QUERY_ACCOUNT,
+    SetStepAndTick {
+        step: u64,
+    } = OP_SET_STEP_AND_TICK,
+    Touch = OP_TOUCH,
+    IncrementPercent {
+        bps: u16,
+    } = OP_INCREMENT_PERCENT,
+    SetAutoFreeze {
+        auto_freeze_at_max: bool,
+    } = OP_SET_AUTO_FREEZE,
+    InitializePdaCounter {
+        initial_value: u64,
+    } = OP_INITIALIZE_PDA,
+    AddSample {
+        value: u64,
+    } = OP_ADD_SAMPLE,
+    ResetIfElapsed {
+        period_secs: u32,
+        require_elapsed: bool,
+    } = OP_RESET_IF_ELAPSED,
+    ResetCounter = OP_RESET,
+    CloseCounter = OP_CLOSE,
+    DecrementWithProgress = OP_DECREMENT_WITH_PROGRESS,
+    Metrics = OP_METRICS,
+    SwapCounters = OP_SWAP,
+    SetCategory {
+        category: u16,
+    } = OP_SET_CATEGORY,
+    IncrementAt {
+        client_ts: i64,
+    } = OP_INCREMENT_AT,
+    QueryRent {
+        space: u16,
+    } = OP_QUERY_RENT,
+    SetSaturating {
+        saturating: bool,
+    } = OP_SET_SATURATING,
+    SetAllowZero {
+        allow_zero: bool,
+    } = OP_SET_ALLOW_ZERO,
+    IncrementWithApproval = OP_INCREMENT_WITH_APPROVAL,
+    IncrementTimes {
+        n: u32,
+    } = OP_INCREMENT_TIMES,
+    ProposeAuthority {
+        new_authority: Pubkey,
+    } = OP_PROPOSE_AUTHORITY,
+    AcceptAuthority = OP_ACCEPT_AUTHORITY,
+    BulkQuery = OP_BULK_QUERY,
+    Checkpoint = OP_CHECKPOINT,
+    SetTokenGate {
+        gate_mint: Pubkey,
+        min_amount: u64,
+    } = OP_SET_TOKEN_GATE,
+    IncrementIfHolder = OP_INCREMENT_IF_HOLDER,
+    StampSlot = OP_STAMP_SLOT,
+    SetEmitEvents {
+        emit_events: bool,
+    } = OP_SET_EMIT_EVENTS,
+    DecrementWithBorrow {
+        amount: u64,
+    } = OP_DECREMENT_WITH_BORROW,
+    CopyConfigFrom = OP_COPY_CONFIG_FROM,
+    InitializeCounterStr {
+        value: String,
+    } = OP_INITIALIZE_STR,
+    BatchIncrement = OP_BATCH_INCREMENT,
+    InitializeCounterShared {
+        initial_value: u64,
+        first_share_bps: u16,
+    } = OP_INITIALIZE_SHARED,
+    QueryStaleness = OP_QUERY_STALENESS,
+    InitializeConfig {
+        admin: Pubkey,
+    } = OP_INITIALIZE_CONFIG,
+    SetDailyLimit {
+        daily_limit: u64,
+    } = OP_SET_DAILY_LIMIT,
+    DecrementDaily {
+        amount: u64,
+    } = OP_DECREMENT_DAILY,
+    ApplyDelta {
+        delta: i64,
+    } = OP_APPLY_DELTA,
+    CheckIncrementBy {
+        amount: u64,
+    } = OP_CHECK_INCREMENT_BY,
+    LockUntil {
+        unlock_ts: i64,
+    } = OP_LOCK_UNTIL,
+    SetExpiry {
+        expires_at_slot: u64,
+    } = OP_SET_EXPIRY,
+    SlotsRemaining = OP_SLOTS_REMAINING,
+    SweepClosed = OP_SWEEP_CLOSED,
+    IncrementWithTip {
+        lamports: u64,
+    } = OP_INCREMENT_WITH_TIP,
+    IncrementFresh {
+        max_age_slots: u64,
+    } = OP_INCREMENT_FRESH,
+    SetMeta {
+        key: String,
+        value: String,
+    } = OP_SET_META,
+    RemoveMeta {
+        key: String,
+    } = OP_REMOVE_META,
+    SetCounter {
+        value: u64,
+    } = OP_SET_COUNTER,
+    InitializeMintCounter {
+        initial_value: u64,
+    } = OP_INITIALIZE_MINT_COUNTER,
+    RefreshBump = OP_REFRESH_BUMP,
+    DecrementWithRemainder {
+        amount: u64,
+    } = OP_DECREMENT_WITH_REMAINDER,
+    InitializeMultiCounter = OP_INITIALIZE_MULTI_COUNTER,
+    IncrementSlot {
+        slot: u8,
+    } = OP_INCREMENT_SLOT,
+    SetSlot {
+        slot: u8,
+        value: u64,
+    } = OP_SET_SLOT,
+    DrainCounter = OP_DRAIN_COUNTER,
+    SetCreationFee {
+        creation_fee: u64,
+        treasury: Pubkey,
+    } = OP_SET_CREATION_FEE,
+    SetMerkleRoot {
+        merkle_root: [u8; 32],
+    } = OP_SET_MERKLE_ROOT,
+    IncrementIfInTree {
+        proof: Vec<[u8; 32]>,
+        leaf: [u8; 32],
+    } = OP_INCREMENT_IF_IN_TREE,
+    SyncFromLamports = OP_SYNC_FROM_LAMPORTS,
+    AddDelegate {
+        delegate: Pubkey,
+    } = OP_ADD_DELEGATE,
+    RemoveDelegate {
+        delegate: Pubkey,
+    } = OP_REMOVE_DELEGATE,
+    InitializeAndIncrement {
+        initial_value: u64,
+    } = OP_INITIALIZE_AND_INCREMENT,
+}
+ 
+// Most counters BatchIncrement and SweepClosed accept in one instruction
+pub const MAX_BATCH: usize = 32;
+ 
+// Most siblings an IncrementIfInTree proof may hold
+pub const MAX_PROOF_LEN: usize = 32;
+ 
+// Minimum number of bytes expected after the variant byte, per instruction
+// variant. Fixed-size payloads must match exactly; variable-size payloads
+// such as Configure's optional fields may be longer.
+pub const INSTRUCTION_LAYOUTS: &[(u8, usize)] = &[
+    (OP_INITIALIZE, 8),               // InitializeCounter: initial_value as u64
+    (OP_INCREMENT, 0),                // IncrementCounter
+    (OP_DECREMENT_SATURATING, 0),     // DecrementSaturating
+    (OP_SET_DELEGATE, 32),            // SetDelegate: delegate_pda as Pubkey
+    (OP_SET_REQUIRE_SIGNER, 1),       // SetRequireSigner: require_signer as bool
+    (OP_SET_MAX_VALUE, 8),            // SetMaxValue: max_value as u64
+    (OP_NOOP, 0),                     // NoOp
+    (OP_MIGRATE, 0),                  // MigrateCounter
+    (OP_CONFIGURE, 3),                // Configure: three Borsh options, each at least a 1-byte tag
+    (OP_INITIALIZE_FULL, 26), // InitializeCounterFull: initial_value, step, max_value, require_signer, decimals, then optional min_value, category and emit_events
+    (OP_SET_GLOBAL_PAUSE, 1), // SetGlobalPause: paused as bool
+    (OP_DECREMENT_WITH_REASON, 1), // DecrementWithReason: reason as u8
+    (OP_DESCRIBE, 0),         // DescribeCounter
+    (OP_MUL_ADD, 16),         // MulAdd: factor, addend as u64
+    (OP_CLOSE_AND_TRANSFER, 0), // CloseAndTransfer
+    (OP_INCREMENT_BY, 8),     // IncrementBy: amount as u64
+    (OP_SET_MAX_STEP_PER_CALL, 8), // SetMaxStepPerCall: max_step_per_call as u64
+    (OP_INITIALIZE_RAW, 4),   // InitializeRaw: data as a u32 length followed by the bytes
+    (OP_SET_MIN_RESERVE, 8),  // SetMinReserve: min_reserve as u64
+    (OP_WITHDRAW, 8),         // Withdraw: lamports as u64
+    (OP_QUERY_ACCOUNT, 0),    // QueryAccount
+    (OP_SET_STEP_AND_TICK, 8), // SetStepAndTick: step as u64
+    (OP_TOUCH, 0),            // Touch
+    (OP_INCREMENT_PERCENT, 2), // IncrementPercent: bps as u16
+    (OP_SET_AUTO_FREEZE, 1),  // SetAutoFreeze: auto_freeze_at_max as bool
+    (OP_INITIALIZE_PDA, 8),   // InitializePdaCounter: initial_value as u64
+    (OP_ADD_SAMPLE, 8),       // AddSample: value as u64
+    (OP_RESET_IF_ELAPSED, 5), // ResetIfElapsed: period_secs as u32, require_elapsed as bool
+    (OP_RESET, 0),            // ResetCounter
+    (OP_CLOSE, 0),            // CloseCounter
+    (OP_DECREMENT_WITH_PROGRESS, 0), // DecrementWithProgress
+    (OP_METRICS, 0),          // Metrics
+    (OP_SWAP, 0),             // SwapCounters
+    (OP_SET_CATEGORY, 2),     // SetCategory: category as u16
+    (OP_INCREMENT_AT, 8),     // IncrementAt: client_ts as i64
+    (OP_QUERY_RENT, 2),       // QueryRent: space as u16
+    (OP_SET_SATURATING, 1),   // SetSaturating: saturating as bool
+    (OP_SET_ALLOW_ZERO, 1),   // SetAllowZero: allow_zero as bool
+    (OP_INCREMENT_WITH_APPROVAL, 0), // IncrementWithApproval
+    (OP_INCREMENT_TIMES, 4),  // IncrementTimes: n as u32
+    (OP_PROPOSE_AUTHORITY, 32), // ProposeAuthority: new_authority as Pubkey
+    (OP_ACCEPT_AUTHORITY, 0), // AcceptAuthority
+    (OP_BULK_QUERY, 0),       // BulkQuery
+    (OP_CHECKPOINT, 0),       // Checkpoint
+    (OP_SET_TOKEN_GATE, 40),  // SetTokenGate: gate_mint as Pubkey, min_amount as u64
+    (OP_INCREMENT_IF_HOLDER, 0), // IncrementIfHolder
+    (OP_STAMP_SLOT, 0),       // StampSlot
+    (OP_SET_EMIT_EVENTS, 1),  // SetEmitEvents: emit_events as bool
+    (OP_DECREMENT_WITH_BORROW, 8), // DecrementWithBorrow: amount as u64
+    (OP_COPY_CONFIG_FROM, 0), // CopyConfigFrom
+    (OP_INITIALIZE_STR, 4), // InitializeCounterStr: value as a u32 length followed by ASCII digits
+    (OP_BATCH_INCREMENT, 0), // BatchIncrement
+    (OP_INITIALIZE_SHARED, 10), // InitializeCounterShared: initial_value as u64, first_share_bps as u16
+    (OP_QUERY_STALENESS, 0),    // QueryStaleness
+    (OP_INITIALIZE_CONFIG, 32), // InitializeConfig: admin as Pubkey
+    (OP_SET_DAILY_LIMIT, 8),    // SetDailyLimit: daily_limit as u64
+    (OP_DECREMENT_DAILY, 8),    // DecrementDaily: amount as u64
+    (OP_APPLY_DELTA, 8),        // ApplyDelta: delta as i64
+    (OP_CHECK_INCREMENT_BY, 8), // CheckIncrementBy: amount as u64
+    (OP_LOCK_UNTIL, 8),         // LockUntil: unlock_ts as i64
+    (OP_SET_EXPIRY, 8),         // SetExpiry: expires_at_slot as u64
+    (OP_SLOTS_REMAINING, 0),    // SlotsRemaining
+    (OP_SWEEP_CLOSED, 0),       // SweepClosed
+    (OP_INCREMENT_WITH_TIP, 8), // IncrementWithTip: lamports as u64
+    (OP_INCREMENT_FRESH, 8),    // IncrementFresh: max_age_slots as u64
+    (OP_SET_META, 8),           // SetMeta: key and value as u32-length-prefixed strings
+    (OP_REMOVE_META, 4),        // RemoveMeta: key as a u32-length-prefixed string
+    (OP_SET_COUNTER, 8),        // SetCounter: value as u64
+    (OP_INITIALIZE_MINT_COUNTER, 8), // InitializeMintCounter: initial_value as u64
+    (OP_REFRESH_BUMP, 0),       // RefreshBump
+    (OP_DECREMENT_WITH_REMAINDER, 8), // DecrementWithRemainder: amount as u64
+    (OP_INITIALIZE_MULTI_COUNTER, 0), // InitializeMultiCounter
+    (OP_INCREMENT_SLOT, 1),     // IncrementSlot: slot as u8
+    (OP_SET_SLOT, 9),           // SetSlot: slot as u8, value as u64
+    (OP_DRAIN_COUNTER, 0),      // DrainCounter
+    (OP_SET_CREATION_FEE, 40),  // SetCreationFee: creation_fee as u64, treasury as Pubkey
+    (OP_SET_MERKLE_ROOT, 32),   // SetMerkleRoot: merkle_root as [u8; 32]
+    (OP_INCREMENT_IF_IN_TREE, 36), // IncrementIfInTree: proof as a u32-length-prefixed list of [u8; 32], leaf as [u8; 32]
+    (OP_SYNC_FROM_LAMPORTS, 0),    // SyncFromLamports
+    (OP_ADD_DELEGATE, 32),         // AddDelegate: delegate as Pubkey
+    (OP_REMOVE_DELEGATE, 32),      // RemoveDelegate: delegate as Pubkey
+    (OP_INITIALIZE_AND_INCREMENT, 8), // InitializeAndIncrement: initial_value as u64
+];
+ 
+impl CounterInstruction {
+    // Encode the instruction as `unpack` reads it: the derive writes the
+    // explicit opcode as a single byte, then the Borsh payload. Writing into a
+    // Vec cannot fail.
+    pub fn pack(&self) -> Vec<u8> {
+        borsh::to_vec(self).unwrap_or_default()
+    }
+ 
+    // The opcode byte this instruction is encoded with
+    pub const fn opcode(&self) -> u8 {
+        match self {
+            Self::InitializeCounter { .. } => OP_INITIALIZE,
+            Self::IncrementCounter { .. } => OP_INCREMENT,
+            Self::DecrementSaturating { .. } => OP_DECREMENT_SATURATING,
+            Self::SetDelegate { .. } => OP_SET_DELEGATE,
+            Self::SetRequireSigner { .. } => OP_SET_REQUIRE_SIGNER,
+            Self::SetMaxValue { .. } => OP_SET_MAX_VALUE,
+            Self::NoOp { .. } => OP_NOOP,
+            Self::MigrateCounter { .. } => OP_MIGRATE,
+            Self::Configure { .. } => OP_CONFIGURE,
+            Self::InitializeCounterFull { .. } => OP_INITIALIZE_FULL,
+            Self::SetGlobalPause { .. } => OP_SET_GLOBAL_PAUSE,
+            Self::DecrementWithReason { .. } => OP_DECREMENT_WITH_REASON,
+            Self::DescribeCounter { .. } => OP_DESCRIBE,
+            Self::MulAdd { .. } => OP_MUL_ADD,
+            Self::CloseAndTransfer { .. } => OP_CLOSE_AND_TRANSFER,
+            Self::IncrementBy { .. } => OP_INCREMENT_BY,
+            Self::SetMaxStepPerCall { .. } => OP_SET_MAX_STEP_PER_CALL,
+            Self::InitializeRaw { .. } => OP_INITIALIZE_RAW,
+            Self::SetMinReserve { .. } => OP_SET_MIN_RESERVE,
+            Self::Withdraw { .. } => OP_WITHDRAW,
+            Self::QueryAccount { .. } => OP_QUERY_ACCOUNT,
+            Self::SetStepAndTick { .. } => OP_SET_STEP_AND_TICK,
+            Self::Touch { .. } => OP_TOUCH,
+            Self::IncrementPercent { .. } => OP_INCREMENT_PERCENT,
+            Self::SetAutoFreeze { .. } => OP_SET_AUTO_FREEZE,
+            Self::InitializePdaCounter { .. } => OP_INITIALIZE_PDA,
+            Self::AddSample { .. } => OP_ADD_SAMPLE,
+            Self::ResetIfElapsed { .. } => OP_RESET_IF_ELAPSED,
+            Self::ResetCounter { .. } => OP_RESET,
+            Self::CloseCounter { .. } => OP_CLOSE,
+            Self::DecrementWithProgress { .. } => OP_DECREMENT_WITH_PROGRESS,
+            Self::Metrics { .. } => OP_METRICS,
+            Self::SwapCounters { .. } => OP_SWAP,
+            Self::SetCategory { .. } => OP_SET_CATEGORY,
+            Self::IncrementAt { .. } => OP_INCREMENT_AT,
+            Self::QueryRent { .. } => OP_QUERY_RENT,
+            Self::SetSaturating { .. } => OP_SET_SATURATING,
+            Self::SetAllowZero { .. } => OP_SET_ALLOW_ZERO,
+            Self::IncrementWithApproval { .. } => OP_INCREMENT_WITH_APPROVAL,
+            Self::IncrementTimes { .. } => OP_INCREMENT_TIMES,
+            Self::ProposeAuthority { .. } => OP_PROPOSE_AUTHORITY,
+            Self::AcceptAuthority { .. } => OP_ACCEPT_AUTHORITY,
+            Self::BulkQuery { .. } => OP_BULK_QUERY,
+            Self::Checkpoint { .. } => OP_CHECKPOINT,
+            Self::SetTokenGate { .. } => OP_SET_TOKEN_GATE,
+            Self::IncrementIfHolder { .. } => OP_INCREMENT_IF_HOLDER,
+            Self::StampSlot { .. } => OP_STAMP_SLOT,
+            Self::SetEmitEvents { .. } => OP_SET_EMIT_EVENTS,
+            Self::DecrementWithBorrow { .. } => OP_DECREMENT_WITH_BORROW,
+            Self::CopyConfigFrom { .. } => OP_COPY_CONFIG_FROM,
+            Self::InitializeCounterStr { .. } => OP_INITIALIZE_STR,
+            Self::BatchIncrement { .. } => OP_BATCH_INCREMENT,
+            Self::InitializeCounterShared { .. } => OP_INITIALIZE_SHARED,
+            Self::QueryStaleness { .. } => OP_QUERY_STALENESS,
+            Self::InitializeConfig { .. } => OP_INITIALIZE_CONFIG,
+            Self::SetDailyLimit { .. } => OP_SET_DAILY_LIMIT,
+            Self::DecrementDaily { .. } => OP_DECREMENT_DAILY,
+            Self::ApplyDelta { .. } => OP_APPLY_DELTA,
+            Self::CheckIncrementBy { .. } => OP_CHECK_INCREMENT_BY,
+            Self::LockUntil { .. } => OP_LOCK_UNTIL,
+            Self::SetExpiry { .. } => OP_SET_EXPIRY,
+            Self::SlotsRemaining { .. } => OP_SLOTS_REMAINING,
+            Self::SweepClosed { .. } => OP_SWEEP_CLOSED,
+            Self::IncrementWithTip { .. } => OP_INCREMENT_WITH_TIP,
+            Self::IncrementFresh { .. } => OP_INCREMENT_FRESH,
+            Self::SetMeta { .. } => OP_SET_META,
+            Self::RemoveMeta { .. } => OP_REMOVE_META,
+            Self::SetCounter { .. } => OP_SET_COUNTER,
+            Self::InitializeMintCounter { .. } => OP_INITIALIZE_MINT_COUNTER,
+            Self::RefreshBump { .. } => OP_REFRESH_BUMP,
+            Self::DecrementWithRemainder { .. } => OP_DECREMENT_WITH_REMAINDER,
+            Self::InitializeMultiCounter { .. } => OP_INITIALIZE_MULTI_COUNTER,
+            Self::IncrementSlot { .. } => OP_INCREMENT_SLOT,
+            Self::SetSlot { .. } => OP_SET_SLOT,
+            Self::DrainCounter { .. } => OP_DRAIN_COUNTER,
+            Self::SetCreationFee { .. } => OP_SET_CREATION_FEE,
+            Self::SetMerkleRoot { .. } => OP_SET_MERKLE_ROOT,
+            Self::IncrementIfInTree { .. } => OP_INCREMENT_IF_IN_TREE,
+            Self::SyncFromLamports { .. } => OP_SYNC_FROM_LAMPORTS,
+            Self::AddDelegate { .. } => OP_ADD_DELEGATE,
+            Self::RemoveDelegate { .. } => OP_REMOVE_DELEGATE,
+            Self::InitializeAndIncrement { .. } => OP_INITIALIZE_AND_INCREMENT,
+        }
+    }
+ 
+    // Build a client instruction invoking `program_id` with this instruction's data
+    pub fn instruction(&self, program_id: &Pubkey, accounts: Vec<AccountMeta>) -> Instruction {
+        Instruction::new_with_bytes(*program_id, &self.pack(), accounts)
+    }
+ 
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        // Get the instruction variant from the first byte
+        let (&variant, rest) = input
+            .split_first()
+            .ok_or(ProgramError::InvalidInstructionData)?;
+ 
+        // Validate the payload length against the layout table
+        let min_len = INSTRUCTION_LAYOUTS
+            .iter()
+            .find(|(layout_variant, _)| *layout_variant == variant)
+            .map(|(_, len)| *len)
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        if rest.len() < min_len {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+ 
+        // Match instruction type and decode the remaining bytes based on the variant
+        match variant {
+            OP_INITIALIZE => Ok(Self::InitializeCounter {
+                initial_value: decode_payload(rest)?,
+            }),
+            OP_INCREMENT => decode_payload(rest).map(|()| Self::IncrementCounter),
+            OP_DECREMENT_SATURATING => decode_payload(rest).map(|()| Self::DecrementSaturating),
+            OP_SET_DELEGATE => Ok(Self::SetDelegate {
+                delegate_pda: decode_payload(rest)?,
+            }),
+            OP_SET_REQUIRE_SIGNER => Ok(Self::SetRequireSigner {
+                require_signer: decode_payload(rest)?,
+            }),
+            OP_SET_MAX_VALUE => Ok(Self::SetMaxValue {
+                max_value: decode_payload(rest)?,
+            }),
+            OP_NOOP => decode_payload(rest).map(|()| Self::NoOp),
+            OP_MIGRATE => decode_payload(rest).map(|()| Self::MigrateCounter),
+            OP_CONFIGURE => {
+                let (step, max_value, cooldown_secs) = decode_payload(rest)?;
+                Ok(Self::Configure {
+                    step,
+                    max_value,
+                    cooldown_secs,
+                })
+            }
+            OP_INITIALIZE_FULL => {
+                // Fields after decimals were added later; clients predating
+                // them stop early and get the defaults
+                let reader = &mut &rest[..];
+                let (initial_value, step, max_value, require_signer, decimals) =
+                    decode_field(reader)?;
+                let min_value = decode_trailing(reader, 0)?;
+                let category = decode_trailing(reader, 0)?;
+                let emit_events = decode_trailing(reader, true)?;
+                decode_payload(reader).map(|()| Self::InitializeCounterFull {
+                    initial_value,
+                    step,
+                    max_value,
+                    require_signer,
+                    decimals,
+                    min_value,
+                    category,
+                    emit_events,
+                })
+            }
+            OP_SET_GLOBAL_PAUSE => Ok(Self::SetGlobalPause {
+                paused: decode_payload(rest)?,
+            }),
+            OP_DECREMENT_WITH_REASON => Ok(Self::DecrementWithReason {
+                reason: decode_payload(rest)?,
+            }),
+            OP_DESCRIBE => decode_payload(rest).map(|()| Self::DescribeCounter),
+            OP_MUL_ADD => {
+                let (factor, addend) = decode_payload(rest)?;
+                Ok(Self::MulAdd { factor, addend })
+            }
+            OP_CLOSE_AND_TRANSFER => decode_payload(rest).map(|()| Self::CloseAndTransfer),
+            OP_INCREMENT_BY => Ok(Self::IncrementBy {
+                amount: decode_payload(rest)?,
+            }),
+            OP_SET_MAX_STEP_PER_CALL => Ok(Self::SetMaxStepPerCall {
+                max_step_per_call: decode_payload(rest)?,
+            }),
+            OP_INITIALIZE_RAW => Ok(Self::InitializeRaw {
+                data: decode_payload(rest)?,
+            }),
+            OP_SET_MIN_RESERVE => Ok(Self::SetMinReserve {
+                min_reserve: decode_payload(rest)?,
+            }),
+            OP_WITHDRAW => Ok(Self::Withdraw {
+                lamports: decode_payload(rest)?,
+            }),
+            OP_QUERY_ACCOUNT => decode_payload(rest).map(|()| Self::QueryAccount),
+            OP_SET_STEP_AND_TICK => Ok(Self::SetStepAndTick {
+                step: decode_payload(rest)?,
+            }),
+            OP_TOUCH => decode_payload(rest).map(|()| Self::Touch),
+            OP_INCREMENT_PERCENT => Ok(Self::IncrementPercent {
+                bps: decode_payload(rest)?,
+            }),
+            OP_SET_AUTO_FREEZE => Ok(Self::SetAutoFreeze {
+                auto_freeze_at_max: decode_payload(rest)?,
+            }),
+            OP_INITIALIZE_PDA => Ok(Self::InitializePdaCounter {
+                initial_value: decode_payload(rest)?,
+            }),
+            OP_ADD_SAMPLE => Ok(Self::AddSample {
+                value: decode_payload(rest)?,
+            }),
+            OP_RESET_IF_ELAPSED => {
+                let (period_secs, require_elapsed) = decode_payload(rest)?;
+                Ok(Self::ResetIfElapsed {
+                    period_secs,
+                    require_elapsed,
+                })
+            }
+            OP_RESET => decode_payload(rest).map(|()| Self::ResetCounter),
+            OP_CLOSE => decode_payload(rest).map(|()| Self::CloseCounter),
+            OP_DECREMENT_WITH_PROGRESS => {
+                decode_payload(rest).map(|()| Self::DecrementWithProgress)
+            }
+            OP_METRICS => decode_payload(rest).map(|()| Self::Metrics),
+            OP_SWAP => decode_payload(rest).map(|()| Self::SwapCounters),
+            OP_SET_CATEGORY => Ok(Self::SetCategory {
+                category: decode_payload(rest)?,
+            }),
+            OP_INCREMENT_AT => Ok(Self::IncrementAt {
+                client_ts: decode_payload(rest)?,
+            }),
+            OP_QUERY_RENT => Ok(Self::QueryRent {
+                space: decode_payload(rest)?,
+            }),
+            OP_SET_SATURATING => Ok(Self::SetSaturating {
+                saturating: decode_payload(rest)?,
+            }),
+            OP_SET_ALLOW_ZERO => Ok(Self::SetAllowZero {
+                allow_zero: decode_payload(rest)?,
+            }),
+            OP_INCREMENT_WITH_APPROVAL => {
+                decode_payload(rest).map(|()| Self::IncrementWithApproval)
+            }
+            OP_INCREMENT_TIMES => Ok(Self::IncrementTimes {
+                n: decode_payload(rest)?,
+            }),
+            OP_PROPOSE_AUTHORITY => Ok(Self::ProposeAuthority {
+                new_authority: decode_payload(rest)?,
+            }),
+            OP_ACCEPT_AUTHORITY => decode_payload(rest).map(|()| Self::AcceptAuthority),
+            OP_BULK_QUERY => decode_payload(rest).map(|()| Self::BulkQuery),
+            OP_CHECKPOINT => decode_payload(rest).map(|()| Self::Checkpoint),
+            OP_SET_TOKEN_GATE => {
+                let (gate_mint, min_amount) = decode_payload(rest)?;
+                Ok(Self::SetTokenGate {
+                    gate_mint,
+                    min_amount,
+                })
+            }
+            OP_INCREMENT_IF_HOLDER => decode_payload(rest).map(|()| Self::IncrementIfHolder),
+            OP_STAMP_SLOT => decode_payload(rest).map(|()| Self::StampSlot),
+            OP_SET_EMIT_EVENTS => Ok(Self::SetEmitEvents {
+                emit_events: decode_payload(rest)?,
+            }),
+            OP_DECREMENT_WITH_BORROW => Ok(Self::DecrementWithBorrow {
+                amount: decode_payload(rest)?,
+            }),
+            OP_COPY_CONFIG_FROM => decode_payload(rest).map(|()| Self::CopyConfigFrom),
+            OP_INITIALIZE_STR => Ok(Self::InitializeCounterStr {
+                value: decode_payload(rest)?,
+            }),
+            OP_BATCH_INCREMENT => decode_payload(rest).map(|()| Self::BatchIncrement),
+            OP_INITIALIZE_SHARED => {
+                let (initial_value, first_share_bps) = decode_payload(rest)?;
+                Ok(Self::InitializeCounterShared {
+                    initial_value,
+                    first_share_bps,
+                })
+            }
+            OP_QUERY_STALENESS => decode_payload(rest).map(|()| Self::QueryStaleness),
+            OP_INITIALIZE_CONFIG => Ok(Self::InitializeConfig {
+                admin: decode_payload(rest)?,
+            }),
+            OP_SET_DAILY_LIMIT => Ok(Self::SetDailyLimit {
+                daily_limit: decode_payload(rest)?,
+            }),
+            OP_DECREMENT_DAILY => Ok(Self::DecrementDaily {
+                amount: decode_payload(rest)?,
+            }),
+            OP_APPLY_DELTA => Ok(Self::ApplyDelta {
+                delta: decode_payload(rest)?,
+            }),
+            OP_CHECK_INCREMENT_BY => Ok(Self::CheckIncrementBy {
+                amount: decode_payload(rest)?,
+            }),
+            OP_LOCK_UNTIL => Ok(Self::LockUntil {
+                unlock_ts: decode_payload(rest)?,
+            }),
+            OP_SET_EXPIRY => Ok(Self::SetExpiry {
+                expires_at_slot: decode_payload(rest)?,
+            }),
+            OP_SLOTS_REMAINING => decode_payload(rest).map(|()| Self::SlotsRemaining),
+            OP_SWEEP_CLOSED => decode_payload(rest).map(|()| Self::SweepClosed),
+            OP_INCREMENT_WITH_TIP => Ok(Self::IncrementWithTip {
+                lamports: decode_payload(rest)?,
+            }),
+            OP_INCREMENT_FRESH => Ok(Self::IncrementFresh {
+                max_age_slots: decode_payload(rest)?,
+            }),
+            OP_SET_META => {
+                let (key, value) = decode_payload(rest)?;
+                Ok(Self::SetMeta { key, value })
+            }
+            OP_REMOVE_META => Ok(Self::RemoveMeta {
+                key: decode_payload(rest)?,
+            }),
+            OP_SET_COUNTER => Ok(Self::SetCounter {
+                value: decode_payload(rest)?,
+            }),
+            OP_INITIALIZE_MINT_COUNTER => Ok(Self::InitializeMintCounter {
+                initial_value: decode_payload(rest)?,
+            }),
+            OP_REFRESH_BUMP => decode_payload(rest).map(|()| Self::RefreshBump),
+            OP_DECREMENT_WITH_REMAINDER => Ok(Self::DecrementWithRemainder {
+                amount: decode_payload(rest)?,
+            }),
+            OP_INITIALIZE_MULTI_COUNTER => {
+                decode_payload(rest).map(|()| Self::InitializeMultiCounter)
+            }
+            OP_INCREMENT_SLOT => Ok(Self::IncrementSlot {
+                slot: decode_payload(rest)?,
+            }),
+            OP_SET_SLOT => {
+                let (slot, value) = decode_payload(rest)?;
+                Ok(Self::SetSlot { slot, value })
+            }
+            OP_DRAIN_COUNTER => decode_payload(rest).map(|()| Self::DrainCounter),
+            OP_SET_CREATION_FEE => {
+                let (creation_fee, treasury) = decode_payload(rest)?;
+                Ok(Self::SetCreationFee {
+                    creation_fee,
+                    treasury,
+                })
+            }
+            OP_SET_MERKLE_ROOT => Ok(Self::SetMerkleRoot {
+                merkle_root: decode_payload(rest)?,
+            }),
+            OP_INCREMENT_IF_IN_TREE => {
+                let (proof, leaf) = decode_payload(rest)?;
+                Ok(Self::IncrementIfInTree { proof, leaf })
+            }
+            OP_SYNC_FROM_LAMPORTS => decode_payload(rest).map(|()| Self::SyncFromLamports),
+            OP_ADD_DELEGATE => Ok(Self::AddDelegate {
+                delegate: decode_payload(rest)?,
+            }),
+            OP_REMOVE_DELEGATE => Ok(Self::RemoveDelegate {
+                delegate: decode_payload(rest)?,
+            }),
+            OP_INITIALIZE_AND_INCREMENT => Ok(Self::InitializeAndIncrement {
+                initial_value: decode_payload(rest)?,
+            }),
+            _ => Err(ProgramError::InvalidInstructionData),
+        }
+    }
+}
+ 
+impl CounterInstruction {
+    // Whether the instruction changes state and so is blocked by the global pause.
+    // SetGlobalPause and the other config updates stay available so operators
+    // can lift the pause.
+    pub fn is_pausable(&self) -> bool {
+        !matches!(
+            self,
+            Self::NoOp
+                | Self::SetGlobalPause { .. }
+                | Self::InitializeConfig { .. }
+                | Self::SetCreationFee { .. }
+                | Self::DescribeCounter
+                | Self::QueryAccount
+                | Self::Metrics
+                | Self::QueryRent { .. }
+                | Self::BulkQuery
+                | Self::QueryStaleness
+                | Self::CheckIncrementBy { .. }
+                | Self::SlotsRemaining
+        )
+    }
+}
+ 
+// Decode an instruction payload with Borsh, rejecting malformed or trailing bytes
+fn decode_payload<T: BorshDeserialize>(rest: &[u8]) -> Result<T, ProgramError> {
+    T::try_from_slice(rest).map_err(|_| ProgramError::InvalidInstructionData)
+}
+ 
+// Decode one field from the front of an instruction payload
+fn decode_field<T: BorshDeserialize>(reader: &mut &[u8]) -> Result<T, ProgramError> {
+    T::deserialize(reader).map_err(|_| ProgramError::InvalidInstructionData)
+}
+ 
+// Decode a field that older clients omit from the end of the payload, taking
+// `default` in its place
+fn decode_trailing<T: BorshDeserialize>(reader: &mut &[u8], default: T) -> Result<T, ProgramError> {
+    if reader.is_empty() {
+        Ok(default)
+    } else {
+        decode_field(reader)
+    }
+}
@@ -1,164 +1,302 @@
-use solana_program::{
-    account_info::{next_account_info, AccountInfo},
-    entrypoint,
-    entrypoint::ProgramResult,
-    msg,
-    program::invoke,
-    program_error::ProgramError,
-    pubkey::Pubkey,
-    system_instruction,
-    sysvar::{rent::Rent, Sysvar},
-};
-use borsh::{BorshDeserialize, BorshSerialize};
-// Program entrypoint
-entrypoint!(process_instruction);
- 
-// Function to route instructions to the correct handler
-pub fn process_instruction(
-    program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    instruction_data: &[u8],
-) -> ProgramResult {
-    // Unpack instruction data
-    let instruction = CounterInstruction::unpack(instruction_data)?;
- 
-    // Match instruction type
-    match instruction {
-        CounterInstruction::InitializeCounter { initial_value } => {
-            process_initialize_counter(program_id, accounts, initial_value)?
-        }
-        CounterInstruction::IncrementCounter => process_increment_counter(program_id, accounts)?,
-    };
-    Ok(())
-}
+//! A minimal counter program.
+//!
+//! Enable the `no-entrypoint` feature to embed this crate in another program
+//! without a conflicting `entrypoint` symbol. The processor stays callable
+//! directly:
+//!
+//! ```
+//! use counter::process_instruction;
+//! use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+//!
+//! let program_id = Pubkey::new_unique();
+//! assert_eq!(
+//!     process_instruction(&program_id, &[], &[]),
+//!     Err(ProgramError::InvalidInstructionData)
+//! );
+//! ```
+//!
+//! Account layouts live in [`state`], the wire format and instruction builders
+//! in [`instruction`] and the handlers in [`processor`], so a client crate can
+//! depend on the first two alone. All three are re-exported at the crate root.
+//! PDA seeds for both sides are in [`seeds`], and the checked count arithmetic
+//! in [`math`].
+// Handlers run on attacker-controlled input, so non-test code must report
+// malformed data as typed errors rather than panicking
+#![cfg_attr(
+    not(test),
+    deny(
+        clippy::indexing_slicing,
+        clippy::unwrap_used,
+        clippy::expect_used,
+        clippy::panic
+    )
+)]
+pub mod error;
+pub mod instruction;
+pub mod math;
+pub mod processor;
+pub mod seeds;
+pub mod state;
  
-// Instructions that our program can execute
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
-pub enum CounterInstruction {
-    InitializeCounter { initial_value: u64 }, // variant 0
-    IncrementCounter,                         // variant 1
-}
+pub use error::CounterError;
+pub use instruction::*;
+pub use processor::process_instruction;
+pub use state::*;
  
-impl CounterInstruction {
-    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
-        // Get the instruction variant from the first byte
-        let (&variant, rest) = input
-            .split_first()
-            .ok_or(ProgramError::InvalidInstructionData)?;
- 
-        // Match instruction type and parse the remaining bytes based on the variant
-        match variant {
-            0 => {
-                // For InitializeCounter, parse a u64 from the remaining bytes
-                let initial_value = u64::from_le_bytes(
-                    rest.try_into()
-                        .map_err(|_| ProgramError::InvalidInstructionData)?,
-                );
-                Ok(Self::InitializeCounter { initial_value })
-            }
-            1 => Ok(Self::IncrementCounter), // No additional data needed
-            _ => Err(ProgramError::InvalidInstructionData),
+// Helpers for off-chain consumers of the program
+#[cfg(feature = "client")]
+pub mod client {
+    use super::{CounterDescription, CounterEvent, CounterMetrics, RETURN_U64_LEN, STATE_HASH_LEN};
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use borsh::BorshDeserialize;
+    use solana_program::hash::Hash;
+ 
+    const PROGRAM_DATA_PREFIX: &str = "Program data: ";
+ 
+    // Decode every `CounterEvent` found in a transaction's log messages; lines
+    // that are not program data, or that hold some other program's payload, are
+    // skipped
+    pub fn parse_events(logs: &[String]) -> Vec<CounterEvent> {
+        logs.iter()
+            .filter_map(|line| line.strip_prefix(PROGRAM_DATA_PREFIX))
+            .filter_map(|data| STANDARD.decode(data).ok())
+            .filter_map(|bytes| CounterEvent::try_from_slice(&bytes).ok())
+            .collect()
+    }
+ 
+    // Read the LE u64 at the start of `bytes`
+    fn leading_u64(bytes: &[u8]) -> Option<u64> {
+        bytes
+            .get(..RETURN_U64_LEN)
+            .and_then(|value| value.try_into().ok())
+            .map(u64::from_le_bytes)
+    }
+ 
+    // Decode a return value made of u64s only, rejecting a partial trailing one
+    fn all_u64s(bytes: &[u8]) -> Option<Vec<u64>> {
+        if !bytes.len().is_multiple_of(RETURN_U64_LEN) {
+            return None;
         }
+        bytes.chunks(RETURN_U64_LEN).map(leading_u64).collect()
     }
-}
  
-// Initialize a new counter account
-fn process_initialize_counter(
-    program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    initial_value: u64,
-) -> ProgramResult {
-    let accounts_iter = &mut accounts.iter();
- 
-    let counter_account = next_account_info(accounts_iter)?;
-    let payer_account = next_account_info(accounts_iter)?;
-    let system_program = next_account_info(accounts_iter)?;
- 
-    // Size of our counter account
-    let account_space = 8; // Size in bytes to store a u64
- 
-    // Calculate minimum balance for rent exemption
-    let rent = Rent::get()?;
-    let required_lamports = rent.minimum_balance(account_space);
- 
-    // Create the counter account
-    invoke(
-        &system_instruction::create_account(
-            payer_account.key,    // Account paying for the new account
-            counter_account.key,  // Account to be created
-            required_lamports,    // Amount of lamports to transfer to the new account
-            account_space as u64, // Size in bytes to allocate for the data field
-            program_id,           // Set program owner to our program
-        ),
-        &[
-            payer_account.clone(),
-            counter_account.clone(),
-            system_program.clone(),
-        ],
-    )?;
- 
-    // Create a new CounterAccount struct with the initial value
-    let counter_data = CounterAccount {
-        count: initial_value,
-    };
+    // Decode the post-state hash ending a mutating handler's return data
+    pub fn decode_state_hash(bytes: &[u8]) -> Option<Hash> {
+        let start = bytes.len().checked_sub(STATE_HASH_LEN)?;
+        bytes
+            .get(start..)?
+            .try_into()
+            .ok()
+            .map(Hash::new_from_array)
+    }
  
-    // Get a mutable reference to the counter account's data
-    let mut account_data = &mut counter_account.data.borrow_mut()[..];
+    // Decode the headroom below max_value reported by IncrementCounter and
+    // IncrementBy
+    pub fn decode_increment_result(bytes: &[u8]) -> Option<u64> {
+        if bytes.len() < RETURN_U64_LEN + STATE_HASH_LEN {
+            return None;
+        }
+        leading_u64(bytes)
+    }
  
-    // Serialize the CounterAccount struct into the account's data
-    counter_data.serialize(&mut account_data)?;
+    // Decode whether a saturating counter's IncrementCounter was clamped;
+    // `None` when the counter is not in saturating mode
+    pub fn decode_saturation_flag(bytes: &[u8]) -> Option<bool> {
+        if bytes.len() != RETURN_U64_LEN + 1 + STATE_HASH_LEN {
+            return None;
+        }
+        bytes.get(RETURN_U64_LEN).map(|flag| *flag != 0)
+    }
  
-    msg!("Counter initialized with value: {}", initial_value);
+    // Decode the count SetCounter replaced
+    pub fn decode_previous_count(bytes: &[u8]) -> Option<u64> {
+        decode_increment_result(bytes)
+    }
  
-    Ok(())
-}
+    // Decode the count DrainCounter cleared
+    pub fn decode_drained_count(bytes: &[u8]) -> Option<u64> {
+        decode_increment_result(bytes)
+    }
  
-// Update an existing counter's value
-fn process_increment_counter(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
-    let accounts_iter = &mut accounts.iter();
-    let counter_account = next_account_info(accounts_iter)?;
+    // Decode the amount DecrementWithRemainder could not subtract
+    pub fn decode_decrement_remainder(bytes: &[u8]) -> Option<u64> {
+        decode_increment_result(bytes)
+    }
  
-    // Verify account ownership
-    if counter_account.owner != program_id {
-        return Err(ProgramError::IncorrectProgramId);
+    // Decode the progress down from max_value reported by DecrementWithProgress
+    pub fn decode_decrement_progress(bytes: &[u8]) -> Option<u64> {
+        decode_increment_result(bytes)
     }
  
-    // Mutable borrow the account data
-    let mut data = counter_account.data.borrow_mut();
+    // Decode the (count, lamports) pair returned by QueryAccount
+    pub fn decode_query_account(bytes: &[u8]) -> Option<(u64, u64)> {
+        match all_u64s(bytes)?.as_slice() {
+            [count, lamports] => Some((*count, *lamports)),
+            _ => None,
+        }
+    }
  
-    // Deserialize the account data into our CounterAccount struct
-    let mut counter_data: CounterAccount = CounterAccount::try_from_slice(&data)?;
+    // Decode the rent-exempt minimum returned by QueryRent
+    pub fn decode_rent(bytes: &[u8]) -> Option<u64> {
+        match all_u64s(bytes)?.as_slice() {
+            [minimum_balance] => Some(*minimum_balance),
+            _ => None,
+        }
+    }
  
-    // Increment the counter value
-    counter_data.count = counter_data
-        .count
-        .checked_add(1)
-        .ok_or(ProgramError::InvalidAccountData)?;
+    // Decode whether CheckIncrementBy found the increment would overflow
+    pub fn decode_would_overflow(bytes: &[u8]) -> Option<bool> {
+        match bytes {
+            [flag] => Some(*flag != 0),
+            _ => None,
+        }
+    }
  
-    // Serialize the updated counter data back into the account
-    counter_data.serialize(&mut &mut data[..])?;
+    // Decode the slots left before expiry returned by SlotsRemaining
+    pub fn decode_slots_remaining(bytes: &[u8]) -> Option<u64> {
+        match all_u64s(bytes)?.as_slice() {
+            [remaining] => Some(*remaining),
+            _ => None,
+        }
+    }
  
-    msg!("Counter incremented to: {}", counter_data.count);
-    Ok(())
-}
+    // Decode the seconds since the last update returned by QueryStaleness
+    pub fn decode_staleness(bytes: &[u8]) -> Option<i64> {
+        bytes.try_into().ok().map(i64::from_le_bytes)
+    }
  
-// Struct representing our counter account's data
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
-pub struct CounterAccount {
-    count: u64,
+    // Decode the counts returned by BulkQuery, in account order
+    pub fn decode_bulk_query(bytes: &[u8]) -> Option<Vec<u64>> {
+        all_u64s(bytes)
+    }
+ 
+    // Decode the snapshot returned by Metrics
+    pub fn decode_metrics(bytes: &[u8]) -> Option<CounterMetrics> {
+        CounterMetrics::try_from_slice(bytes).ok()
+    }
+ 
+    // Decode the account metadata returned by DescribeCounter
+    pub fn decode_description(bytes: &[u8]) -> Option<CounterDescription> {
+        CounterDescription::try_from_slice(bytes).ok()
+    }
 }
  
 #[cfg(test)]
 mod test {
     use super::*;
+    use borsh::{BorshDeserialize, BorshSerialize};
+    use solana_program::{
+        account_info::{next_account_info, AccountInfo},
+        bpf_loader_upgradeable,
+        decode_error::DecodeError,
+        entrypoint::ProgramResult,
+        hash::hash,
+        program::{invoke_signed, MAX_RETURN_DATA},
+        program_error::ProgramError,
+        pubkey::Pubkey,
+        system_instruction::MAX_PERMITTED_DATA_LENGTH,
+        sysvar::{clock::Clock, rent::Rent},
+    };
     use solana_program_test::*;
     use solana_sdk::{
-        instruction::{AccountMeta, Instruction},
+        account::Account,
+        instruction::{AccountMeta, Instruction, InstructionError},
         signature::{Keypair, Signer},
         system_program,
-        transaction::Transaction,
+        transaction::{Transaction, TransactionError},
     };
  
+    fn program_test(program_id: Pubkey) -> ProgramTest {
+        ProgramTest::new(
+            "counter_program",
+            program_id,
+            processor!(process_instruction),
+        )
+    }
+ 
+    // Send the instructions in a transaction paid for by the context payer,
+    // fetching a fresh blockhash so identical transactions are not deduplicated
+    async fn process(
+        context: &mut ProgramTestContext,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> BanksTransactionResultWithMetadata {
+        let blockhash = context.get_new_latest_blockhash().await.unwrap();
+        let mut all_signers = vec![&context.payer];
+        all_signers.extend_from_slice(signers);
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&context.payer.pubkey()),
+            &all_signers,
+            blockhash,
+        );
+        context
+            .banks_client
+            .process_transaction_with_metadata(transaction)
+            .await
+            .unwrap()
+    }
+ 
+    // Create a keypair counter account holding `initial_value`
+    async fn initialize_counter(
+        context: &mut ProgramTestContext,
+        program_id: Pubkey,
+        initial_value: u64,
+    ) -> Keypair {
+        let counter_keypair = Keypair::new();
+        let mut data = vec![0];
+        data.extend_from_slice(&initial_value.to_le_bytes());
+        let instruction = Instruction::new_with_bytes(
+            program_id,
+            &data,
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), true),
+                AccountMeta::new(context.payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        process(context, &[instruction], &[&counter_keypair])
+            .await
+            .result
+            .unwrap();
+        counter_keypair
+    }
+ 
+    fn increment_instruction(program_id: Pubkey, counter: Pubkey) -> Instruction {
+        Instruction::new_with_bytes(
+            program_id,
+            &[1], // 1 = increment instruction
+            vec![AccountMeta::new(counter, false)],
+        )
+    }
+ 
+    // Build an authority-gated instruction signed by `authority`
+    fn authority_instruction(
+        program_id: Pubkey,
+        counter: Pubkey,
+        authority: Pubkey,
+        data: &[u8],
+    ) -> Instruction {
+        Instruction::new_with_bytes(
+            program_id,
+            data,
+            vec![
+                AccountMeta::new(counter, false),
+                AccountMeta::new_readonly(authority, true),
+            ],
+        )
+    }
+ 
+    async fn get_counter(context: &mut ProgramTestContext, address: Pubkey) -> CounterAccount {
+        let account = context
+            .banks_client
+            .get_account(address)
+            .await
+            .unwrap()
+            .expect("counter account not found");
+        CounterAccount::load(&account.data).unwrap()
+    }
+ 
     #[tokio::test]
     async fn test_counter_program() {
         let program_id = Pubkey::new_unique();
@@ -242,4 +380,4498 @@ mod test {
             println!("✅ Counter incremented successfully to: {}", counter.count);
         }
     }
-}
\ No newline at end of file
+ 
+    #[tokio::test]
+    async fn test_decrement_saturating_floors_at_zero() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter_keypair = initialize_counter(&mut context, program_id, 0).await;
+ 
+        let decrement_instruction = Instruction::new_with_bytes(
+            program_id,
+            &[2], // 2 = decrement saturating instruction
+            vec![AccountMeta::new(counter_keypair.pubkey(), false)],
+        );
+        let result = process(&mut context, &[decrement_instruction], &[]).await;
+        assert!(result.result.is_ok());
+ 
+        let counter = get_counter(&mut context, counter_keypair.pubkey()).await;
+        assert_eq!(counter.count, 0);
+    }
+ 
+    #[test]
+    fn test_unpack_rejects_short_payloads() {
+        for &(variant, len) in INSTRUCTION_LAYOUTS.iter().filter(|(_, len)| *len > 0) {
+            let mut data = vec![variant];
+            data.resize(len, 0); // one byte short of the expected payload
+            assert_eq!(
+                CounterInstruction::unpack(&data).unwrap_err(),
+                ProgramError::InvalidInstructionData,
+                "variant {variant} accepted a short payload"
+            );
+        }
+    }
+ 
+    #[tokio::test]
+    async fn test_overflow_error_is_logged() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter_keypair = initialize_counter(&mut context, program_id, u64::MAX).await;
+ 
+        let increment_instruction = Instruction::new_with_bytes(
+            program_id,
+            &[1], // 1 = increment instruction
+            vec![AccountMeta::new(counter_keypair.pubkey(), false)],
+        );
+        let result = process(&mut context, &[increment_instruction], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Overflow as u32)
+            )
+        );
+        let logs = result.metadata.unwrap().log_messages;
+        assert!(logs
+            .iter()
+            .any(|log| log == "Program log: Error: Overflow: Counter value overflowed"));
+    }
+ 
+    // Stand-in for a vault-style program that increments a counter as its PDA
+    fn delegate_caller_process(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        _instruction_data: &[u8],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let counter_account = next_account_info(accounts_iter)?;
+        let delegate_account = next_account_info(accounts_iter)?;
+        let counter_program = next_account_info(accounts_iter)?;
+        let (_, bump) = Pubkey::find_program_address(&[b"delegate"], program_id);
+        invoke_signed(
+            &Instruction::new_with_bytes(
+                *counter_program.key,
+                &[1], // 1 = increment instruction
+                vec![
+                    AccountMeta::new(*counter_account.key, false),
+                    AccountMeta::new_readonly(*delegate_account.key, true),
+                ],
+            ),
+            &[
+                counter_account.clone(),
+                delegate_account.clone(),
+                counter_program.clone(),
+            ],
+            &[&[b"delegate", &[bump]]],
+        )
+    }
+ 
+    #[tokio::test]
+    async fn test_delegate_pda_increments_via_cpi() {
+        let program_id = Pubkey::new_unique();
+        let caller_program_id = Pubkey::new_unique();
+        let mut program_test = program_test(program_id);
+        program_test.add_program(
+            "delegate_caller",
+            caller_program_id,
+            processor!(delegate_caller_process),
+        );
+        let mut context = program_test.start_with_context().await;
+        let counter_keypair = initialize_counter(&mut context, program_id, 0).await;
+        let authority = context.payer.pubkey();
+        let (delegate_pda, _) = Pubkey::find_program_address(&[b"delegate"], &caller_program_id);
+ 
+        // Configure the delegate and require a signer for increments
+        let mut set_delegate_data = vec![3]; // 3 = set delegate instruction
+        set_delegate_data.extend_from_slice(delegate_pda.as_ref());
+        let authority_metas = vec![
+            AccountMeta::new(counter_keypair.pubkey(), false),
+            AccountMeta::new_readonly(authority, true),
+        ];
+        let set_delegate_instruction =
+            Instruction::new_with_bytes(program_id, &set_delegate_data, authority_metas.clone());
+        let require_signer_instruction =
+            Instruction::new_with_bytes(program_id, &[4, 1], authority_metas);
+        let result = process(
+            &mut context,
+            &[set_delegate_instruction, require_signer_instruction],
+            &[],
+        )
+        .await;
+        assert!(result.result.is_ok());
+ 
+        // An unsigned increment is now rejected
+        let increment_instruction = Instruction::new_with_bytes(
+            program_id,
+            &[1],
+            vec![AccountMeta::new(counter_keypair.pubkey(), false)],
+        );
+        let result = process(&mut context, &[increment_instruction], &[]).await;
+        assert!(result.result.is_err());
+ 
+        // The caller program increments by signing as the delegate PDA
+        let caller_instruction = Instruction::new_with_bytes(
+            caller_program_id,
+            &[],
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), false),
+                AccountMeta::new_readonly(delegate_pda, false),
+                AccountMeta::new_readonly(program_id, false),
+            ],
+        );
+        let result = process(&mut context, &[caller_instruction], &[]).await;
+        assert!(result.result.is_ok());
+ 
+        let counter = get_counter(&mut context, counter_keypair.pubkey()).await;
+        assert_eq!(counter.count, 1);
+    }
+ 
+    #[tokio::test]
+    async fn test_increment_returns_headroom() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter_keypair = initialize_counter(&mut context, program_id, 0).await;
+        let counter = counter_keypair.pubkey();
+ 
+        let mut set_max_data = vec![5]; // 5 = set max value instruction
+        set_max_data.extend_from_slice(&100u64.to_le_bytes());
+        let set_max_instruction =
+            authority_instruction(program_id, counter, context.payer.pubkey(), &set_max_data);
+        let result = process(&mut context, &[set_max_instruction], &[]).await;
+        assert!(result.result.is_ok());
+ 
+        let increments = vec![increment_instruction(program_id, counter); 5];
+        let result = process(&mut context, &increments, &[]).await;
+        assert!(result.result.is_ok());
+ 
+        let return_data = result.metadata.unwrap().return_data.unwrap();
+        assert_eq!(return_data.program_id, program_id);
+        assert_eq!(return_data.data[..8], 95u64.to_le_bytes());
+        assert_eq!(get_counter(&mut context, counter).await.count, 5);
+    }
+ 
+    #[tokio::test]
+    async fn test_noop_logs_and_succeeds() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+ 
+        let noop_instruction = Instruction::new_with_bytes(program_id, &[6], vec![]);
+        let result = process(&mut context, &[noop_instruction], &[]).await;
+        assert!(result.result.is_ok());
+        let logs = result.metadata.unwrap().log_messages;
+        assert!(logs.iter().any(|log| log == "Program log: counter:noop"));
+    }
+ 
+    #[tokio::test]
+    async fn test_migrate_grows_legacy_account_rent_exempt() {
+        let program_id = Pubkey::new_unique();
+        let legacy_keypair = Keypair::new();
+        let mut program_test = program_test(program_id);
+        program_test.add_account(
+            legacy_keypair.pubkey(),
+            Account {
+                lamports: Rent::default().minimum_balance(CounterAccount::LEGACY_LEN),
+                data: 42u64.to_le_bytes().to_vec(),
+                owner: program_id,
+                ..Account::default()
+            },
+        );
+        let mut context = program_test.start_with_context().await;
+ 
+        let migrate_instruction = Instruction::new_with_bytes(
+            program_id,
+            &[7], // 7 = migrate instruction
+            vec![
+                AccountMeta::new(legacy_keypair.pubkey(), true),
+                AccountMeta::new(context.payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let result = process(&mut context, &[migrate_instruction], &[&legacy_keypair]).await;
+        assert!(result.result.is_ok());
+ 
+        let account = context
+            .banks_client
+            .get_account(legacy_keypair.pubkey())
+            .await
+            .unwrap()
+            .unwrap();
+        let rent = context.banks_client.get_rent().await.unwrap();
+        assert_eq!(account.data.len(), CounterAccount::LEN);
+        assert!(rent.is_exempt(account.lamports, account.data.len()));
+ 
+        let counter = CounterAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(counter.count, 42);
+        assert_eq!(counter.authority, context.payer.pubkey());
+    }
+ 
+    #[tokio::test]
+    async fn test_configure_applies_only_some_fields() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter_keypair = initialize_counter(&mut context, program_id, 0).await;
+        let counter = counter_keypair.pubkey();
+ 
+        let mut configure_data = vec![8]; // 8 = configure instruction
+        (Some(5u64), Some(50u64), None::<u32>)
+            .serialize(&mut configure_data)
+            .unwrap();
+        let configure_instruction =
+            authority_instruction(program_id, counter, context.payer.pubkey(), &configure_data);
+        let result = process(&mut context, &[configure_instruction], &[]).await;
+        assert!(result.result.is_ok());
+ 
+        let counter_data = get_counter(&mut context, counter).await;
+        assert_eq!(counter_data.step, 5);
+        assert_eq!(counter_data.max_value, 50);
+        assert_eq!(counter_data.cooldown_secs, 0);
+ 
+        // Increments now advance by the configured step
+        let result = process(
+            &mut context,
+            &[increment_instruction(program_id, counter)],
+            &[],
+        )
+        .await;
+        assert!(result.result.is_ok());
+        assert_eq!(get_counter(&mut context, counter).await.count, 5);
+    }
+ 
+    #[test]
+    fn test_count_lives_at_fixed_offset() {
+        let counter_data = CounterAccount {
+            discriminator: CounterAccount::DISCRIMINATOR,
+            count: 0x0102_0304_0506_0708,
+            authority: Pubkey::new_unique(),
+            require_signer: true,
+            delegate_pda: Pubkey::new_unique(),
+            max_value: u64::MAX - 1,
+            step: 3,
+            cooldown_secs: 60,
+            last_updated: 1_700_000_000,
+            last_reason: 7,
+            decimals: 2,
+            max_step_per_call: 10,
+            nonce: 11,
+            min_reserve: 5_000,
+            auto_freeze_at_max: true,
+            is_frozen: false,
+            bump: 254,
+            sum: 600,
+            samples: 3,
+            lifetime_total: 9,
+            min_value: 1,
+            increment_count: 4,
+            category: 42,
+            saturating: true,
+            allow_zero: false,
+            pending_authority: Pubkey::new_from_array([5; 32]),
+            gate_mint: Pubkey::new_from_array([6; 32]),
+            gate_min_amount: 43,
+            emit_events: false,
+            daily_limit: 31,
+            daily_spent: 32,
+            day_start: 33,
+            locked_until: 34,
+            expires_at_slot: 35,
+            total_tips: 36,
+            last_increment_slot: 38,
+            merkle_root: [9; 32],
+            delegates: [Pubkey::new_from_array([10; 32]); CounterAccount::MAX_DELEGATES],
+        };
+        let bytes = borsh::to_vec(&counter_data).unwrap();
+        assert_eq!(bytes.len(), CounterAccount::LEN);
+ 
+        let offset = CounterAccount::COUNT_OFFSET;
+        let count_bytes: [u8; 8] = bytes[offset..offset + 8].try_into().unwrap();
+        assert_eq!(u64::from_le_bytes(count_bytes), counter_data.count);
+    }
+ 
+    #[tokio::test]
+    async fn test_initialize_counter_full() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter_keypair = Keypair::new();
+ 
+        let mut init_data = vec![9]; // 9 = initialize full instruction
+        (7u64, 3u64, 100u64, true, 0u8)
+            .serialize(&mut init_data)
+            .unwrap();
+        let init_instruction = Instruction::new_with_bytes(
+            program_id,
+            &init_data,
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), true),
+                AccountMeta::new(context.payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let result = process(&mut context, &[init_instruction], &[&counter_keypair]).await;
+        assert!(result.result.is_ok());
+ 
+        let counter = get_counter(&mut context, counter_keypair.pubkey()).await;
+        assert_eq!(counter.discriminator, CounterAccount::DISCRIMINATOR);
+        assert_eq!(counter.count, 7);
+        assert_eq!(counter.step, 3);
+        assert_eq!(counter.max_value, 100);
+        assert!(counter.require_signer);
+        assert_eq!(counter.authority, context.payer.pubkey());
+        assert_eq!(counter.delegate_pda, Pubkey::default());
+        assert_eq!(counter.cooldown_secs, 0);
+    }
+ 
+    #[test]
+    fn test_unpack_legacy_account_with_defaults() {
+        // `load` adds only a log on top of `unpack`, and logging needs a runtime
+        let counter_data = CounterAccount::unpack(&42u64.to_le_bytes()).unwrap();
+        assert_eq!(counter_data.discriminator, CounterAccount::DISCRIMINATOR);
+        assert_eq!(counter_data.count, 42);
+        assert_eq!(counter_data.authority, Pubkey::default());
+        assert!(!counter_data.require_signer);
+        assert_eq!(counter_data.max_value, u64::MAX);
+        assert_eq!(counter_data.step, 1);
+        assert_eq!(counter_data.cooldown_secs, 0);
+        assert_eq!(counter_data.last_updated, 0);
+    }
+ 
+    // Register a ProgramData account naming `upgrade_authority` for the program
+    fn add_program_data(
+        program_test: &mut ProgramTest,
+        program_id: Pubkey,
+        upgrade_authority: Pubkey,
+    ) {
+        let (program_data_address, _) =
+            Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+        let mut data = 3u32.to_le_bytes().to_vec(); // ProgramData variant
+        data.extend_from_slice(&0u64.to_le_bytes()); // deployment slot
+        data.push(1); // Some(upgrade_authority)
+        data.extend_from_slice(upgrade_authority.as_ref());
+        program_test.add_account(
+            program_data_address,
+            Account {
+                lamports: Rent::default().minimum_balance(data.len()),
+                data,
+                owner: bpf_loader_upgradeable::id(),
+                ..Account::default()
+            },
+        );
+    }
+ 
+    fn set_global_pause_instruction(
+        program_id: Pubkey,
+        upgrade_authority: Pubkey,
+        paused: bool,
+    ) -> Instruction {
+        let (program_data_address, _) =
+            Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+        Instruction::new_with_bytes(
+            program_id,
+            &[10, paused as u8], // 10 = set global pause instruction
+            vec![
+                AccountMeta::new(GlobalConfig::find_address(&program_id).0, false),
+                AccountMeta::new(upgrade_authority, true),
+                AccountMeta::new_readonly(program_data_address, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        )
+    }
+ 
+    #[tokio::test]
+    async fn test_global_pause_blocks_increments() {
+        let program_id = Pubkey::new_unique();
+        let upgrade_authority = Keypair::new();
+        let mut program_test = program_test(program_id);
+        add_program_data(&mut program_test, program_id, upgrade_authority.pubkey());
+        program_test.add_account(
+            upgrade_authority.pubkey(),
+            Account {
+                lamports: 1_000_000_000,
+                ..Account::default()
+            },
+        );
+        let mut context = program_test.start_with_context().await;
+        let counter_keypair = initialize_counter(&mut context, program_id, 0).await;
+        let (config_address, _) = GlobalConfig::find_address(&program_id);
+ 
+        // Increments passing the config account fail while paused
+        let pause = set_global_pause_instruction(program_id, upgrade_authority.pubkey(), true);
+        let result = process(&mut context, &[pause], &[&upgrade_authority]).await;
+        assert!(result.result.is_ok());
+        let mut increment = increment_instruction(program_id, counter_keypair.pubkey());
+        increment
+            .accounts
+            .push(AccountMeta::new_readonly(config_address, false));
+        let result = process(&mut context, &[increment.clone()], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::GloballyPaused as u32)
+            )
+        );
+ 
+        // Lifting the pause lets the same increment through
+        let unpause = set_global_pause_instruction(program_id, upgrade_authority.pubkey(), false);
+        let result = process(&mut context, &[unpause], &[&upgrade_authority]).await;
+        assert!(result.result.is_ok());
+        let result = process(&mut context, &[increment], &[]).await;
+        assert!(result.result.is_ok());
+        assert_eq!(
+            get_counter(&mut context, counter_keypair.pubkey())
+                .await
+                .count,
+            1
+        );
+    }
+ 
+    #[tokio::test]
+    async fn test_global_pause_rejects_non_upgrade_authority() {
+        let program_id = Pubkey::new_unique();
+        let mut program_test = program_test(program_id);
+        add_program_data(&mut program_test, program_id, Pubkey::new_unique());
+        let mut context = program_test.start_with_context().await;
+ 
+        let pause = set_global_pause_instruction(program_id, context.payer.pubkey(), true);
+        let result = process(&mut context, &[pause], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Unauthorized as u32)
+            )
+        );
+    }
+ 
+    #[tokio::test]
+    async fn test_increment_returns_state_hash() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter_keypair = initialize_counter(&mut context, program_id, 9).await;
+        let counter = counter_keypair.pubkey();
+        let mut expected = get_counter(&mut context, counter).await;
+ 
+        let result = process(
+            &mut context,
+            &[increment_instruction(program_id, counter)],
+            &[],
+        )
+        .await;
+        assert!(result.result.is_ok());
+ 
+        // Build the expected post-state locally and hash its bytes
+        let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+        expected.count = 10;
+        expected.last_updated = clock.unix_timestamp;
+        expected.nonce = 1;
+        expected.lifetime_total = 1;
+        expected.increment_count = 1;
+        let expected_hash = hash(&borsh::to_vec(&expected).unwrap());
+ 
+        let return_data = result.metadata.unwrap().return_data.unwrap();
+        assert_eq!(return_data.data.len(), 8 + 32);
+        assert_eq!(return_data.data[8..], expected_hash.to_bytes());
+    }
+ 
+    #[tokio::test]
+    async fn test_mutating_instructions_require_writable_counter() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter_keypair = initialize_counter(&mut context, program_id, 1).await;
+        let counter = counter_keypair.pubkey();
+        let authority = context.payer.pubkey();
+ 
+        let mut set_delegate_data = vec![3];
+        set_delegate_data.extend_from_slice(Pubkey::new_unique().as_ref());
+        let mut set_max_data = vec![5];
+        set_max_data.extend_from_slice(&10u64.to_le_bytes());
+        let mut configure_data = vec![8];
+        (Some(2u64), None::<u64>, None::<u32>)
+            .serialize(&mut configure_data)
+            .unwrap();
+        let instructions = [
+            increment_instruction(program_id, counter),
+            Instruction::new_with_bytes(program_id, &[2], vec![AccountMeta::new(counter, false)]),
+            authority_instruction(program_id, counter, authority, &set_delegate_data),
+            authority_instruction(program_id, counter, authority, &[4, 1]),
+            authority_instruction(program_id, counter, authority, &set_max_data),
+            authority_instruction(program_id, counter, authority, &configure_data),
+        ];
+ 
+        for mut instruction in instructions {
+            instruction.accounts[0].is_writable = false;
+            let variant = instruction.data[0];
+            let result = process(&mut context, &[instruction], &[]).await;
+            assert_eq!(
+                result.result.unwrap_err(),
+                TransactionError::InstructionError(
+                    0,
+                    InstructionError::Custom(CounterError::AccountNotWritable as u32)
+                ),
+                "variant {variant} accepted a read-only counter"
+            );
+        }
+    }
+ 
+    #[tokio::test]
+    async fn test_decrement_with_reason_records_reason() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter_keypair = initialize_counter(&mut context, program_id, 3).await;
+        let counter = counter_keypair.pubkey();
+ 
+        let decrement_instruction = Instruction::new_with_bytes(
+            program_id,
+            &[11, 7], // 11 = decrement with reason instruction, reason 7
+            vec![AccountMeta::new(counter, false)],
+        );
+        let result = process(&mut context, &[decrement_instruction], &[]).await;
+        assert!(result.result.is_ok());
+        let logs = result.metadata.unwrap().log_messages;
+        assert!(logs
+            .iter()
+            .any(|log| log == "Program log: counter:decrement reason=7"));
+ 
+        let counter_data = get_counter(&mut context, counter).await;
+        assert_eq!(counter_data.count, 2);
+        assert_eq!(counter_data.last_reason, 7);
+    }
+ 
+    #[tokio::test]
+    async fn test_decimals_scale_ui_amount() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter_keypair = Keypair::new();
+ 
+        let mut init_data = vec![9]; // 9 = initialize full instruction
+        (1500u64, 1u64, u64::MAX, false, 2u8)
+            .serialize(&mut init_data)
+            .unwrap();
+        let init_instruction = Instruction::new_with_bytes(
+            program_id,
+            &init_data,
+            vec![
+                AccountMeta::new(counter_keypair.pubkey(), true),
+                AccountMeta::new(context.payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let result = process(&mut context, &[init_instruction], &[&counter_keypair]).await;
+        assert!(result.result.is_ok());
+        let logs = result.metadata.unwrap().log_messages;
+        assert!(logs
+            .iter()
+            .any(|log| log.contains("Counter initialized with value: 15.00,")));
+ 
+        let counter = get_counter(&mut context, counter_keypair.pubkey()).await;
+        assert_eq!(counter.decimals, 2);
+        assert_eq!(counter.count, 1500);
+        assert_eq!(counter.ui_amount(), 15.0);
+    }
+ 
+    #[tokio::test]
+    async fn test_describe_fresh_counter() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter_keypair = initialize_counter(&mut context, program_id, 0).await;
+ 
+        let describe_instruction = Instruction::new_with_bytes(
+            program_id,
+            &[12], // 12 = describe instruction
+            vec![AccountMeta::new_readonly(counter_keypair.pubkey(), false)],
+        );
+        let result = process(&mut context, &[describe_instruction], &[]).await;
+        assert!(result.result.is_ok());
+ 
+        let return_data = result.metadata.unwrap().return_data.unwrap();
+        let description = CounterDescription::try_from_slice(&return_data.data).unwrap();
+        assert_eq!(
+            description,
+            CounterDescription {
+                version: CounterAccount::VERSION,
+                owner_matches: true,
+                data_len: CounterAccount::LEN as u64,
+                rent_exempt: true,
+                nonce: Some(0),
+                category: Some(0),
+            }
+        );
+    }
+ 
+    fn mul_add_instruction(
+        program_id: Pubkey,
+        counter: Pubkey,
+        factor: u64,
+        addend: u64,
+    ) -> Instruction {
+        let mut data = vec![13]; // 13 = multiply-accumulate instruction
+        data.extend_from_slice(&factor.to_le_bytes());
+        data.extend_from_slice(&addend.to_le_bytes());
+        Instruction::new_with_bytes(program_id, &data, vec![AccountMeta::new(counter, false)])
+    }
+ 
+    #[tokio::test]
+    async fn test_mul_add() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter_keypair = initialize_counter(&mut context, program_id, 2).await;
+        let counter = counter_keypair.pubkey();
+ 
+        let result = process(
+            &mut context,
+            &[mul_add_instruction(program_id, counter, 3, 4)],
+            &[],
+        )
+        .await;
+        assert!(result.result.is_ok());
+        assert_eq!(get_counter(&mut context, counter).await.count, 10);
+ 
+        // Overflow in the multiplication fails without changing the count
+        let instruction = mul_add_instruction(program_id, counter, u64::MAX, 0);
+        let result = process(&mut context, &[instruction], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Overflow as u32)
+            )
+        );
+        assert_eq!(get_counter(&mut context, counter).await.count, 10);
+    }
+ 
+    #[test]
+    fn test_unpack_truncated_account_data_errors() {
+        let counter_data = CounterAccount::new(5, Pubkey::new_unique(), 0);
+        let bytes = borsh::to_vec(&counter_data).unwrap();
+        for len in (0..bytes.len()).filter(|len| *len != CounterAccount::LEGACY_LEN) {
+            assert_eq!(
+                CounterAccount::unpack(&bytes[..len]).unwrap_err(),
+                ProgramError::InvalidAccountData,
+                "{len}-byte account data was accepted"
+            );
+        }
+        let mut oversized = bytes.clone();
+        oversized.push(0);
+        assert!(CounterAccount::unpack(&oversized).is_err());
+    }
+ 
+    #[test]
+    fn test_unpack_truncated_instruction_data_errors() {
+        let mut configure_data = vec![8];
+        (Some(1u64), Some(2u64), Some(3u32))
+            .serialize(&mut configure_data)
+            .unwrap();
+        let mut init_full_data = vec![9];
+        (1u64, 1u64, 10u64, false, 0u8)
+            .serialize(&mut init_full_data)
+            .unwrap();
+        for data in [configure_data, init_full_data] {
+            assert!(CounterInstruction::unpack(&data).is_ok());
+            for len in 0..data.len() {
+                assert_eq!(
+                    CounterInstruction::unpack(&data[..len]).unwrap_err(),
+                    ProgramError::InvalidInstructionData
+                );
+            }
+        }
+        assert!(CounterInstruction::unpack(&[4, 2]).is_err()); // non-boolean byte
+        assert!(CounterInstruction::unpack(&[u8::MAX]).is_err()); // unknown variant
+    }
+ 
+    #[tokio::test]
+    async fn test_truncated_counter_account_errors() {
+        let program_id = Pubkey::new_unique();
+        let truncated = Pubkey::new_unique();
+        let mut program_test = program_test(program_id);
+        let mut data = borsh::to_vec(&CounterAccount::new(1, Pubkey::default(), 0)).unwrap();
+        data.truncate(20);
+        program_test.add_account(
+            truncated,
+            Account {
+                lamports: Rent::default().minimum_balance(data.len()),
+                data,
+                owner: program_id,
+                ..Account::default()
+            },
+        );
+        let mut context = program_test.start_with_context().await;
+ 
+        let result = process(
+            &mut context,
+            &[increment_instruction(program_id, truncated)],
+            &[],
+        )
+        .await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::InvalidAccountData)
+        );
+    }
+ 
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_parse_events_from_logs() {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+ 
+        let program_id = Pubkey::new_from_array([7; 32]);
+        let counter = Pubkey::new_from_array([1; 32]);
+        let authority = Pubkey::new_from_array([2; 32]);
+        let events = vec![
+            CounterEvent::Initialized {
+                counter,
+                authority,
+                count: 42,
+            },
+            CounterEvent::CountChanged { counter, count: 43 },
+        ];
+        let data_line = |event: &CounterEvent| {
+            format!(
+                "Program data: {}",
+                STANDARD.encode(borsh::to_vec(event).unwrap())
+            )
+        };
+        let logs = vec![
+            format!("Program {program_id} invoke [1]"),
+            "Program log: Counter initialized with value: 42".to_string(),
+            data_line(&events[0]),
+            format!("Program {program_id} success"),
+            format!("Program {program_id} invoke [1]"),
+            data_line(&events[1]),
+            "Program data: bm90IGFuIGV2ZW50".to_string(),
+            "Program return: 11111111111111111111111111111111 AQ==".to_string(),
+            format!("Program {program_id} success"),
+        ];
+ 
+        assert_eq!(crate::client::parse_events(&logs), events);
+    }
+ 
+    #[tokio::test]
+    async fn test_close_and_transfer() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let source = initialize_counter(&mut context, program_id, 15).await;
+        let destination = initialize_counter(&mut context, program_id, 5).await;
+        let instruction = Instruction::new_with_bytes(
+            program_id,
+            &[14],
+            vec![
+                AccountMeta::new(source.pubkey(), false),
+                AccountMeta::new(destination.pubkey(), false),
+                AccountMeta::new(context.payer.pubkey(), true),
+            ],
+        );
+ 
+        let result = process(&mut context, &[instruction], &[]).await;
+        assert!(result.result.is_ok());
+        assert_eq!(
+            get_counter(&mut context, destination.pubkey()).await.count,
+            20
+        );
+        let closed = context
+            .banks_client
+            .get_account(source.pubkey())
+            .await
+            .unwrap();
+        assert!(closed.is_none());
+    }
+ 
+    #[tokio::test]
+    async fn test_increment_by_respects_max_step_per_call() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 0).await;
+        let authority = context.payer.pubkey();
+        let mut data = vec![16];
+        10u64.serialize(&mut data).unwrap();
+        let set_max_step = authority_instruction(program_id, counter.pubkey(), authority, &data);
+        assert!(process(&mut context, &[set_max_step], &[])
+            .await
+            .result
+            .is_ok());
+ 
+        let increment_by = |amount: u64| {
+            let mut data = vec![15];
+            amount.serialize(&mut data).unwrap();
+            Instruction::new_with_bytes(
+                program_id,
+                &data,
+                vec![AccountMeta::new(counter.pubkey(), false)],
+            )
+        };
+        let result = process(&mut context, &[increment_by(10)], &[]).await;
+        assert!(result.result.is_ok());
+        assert_eq!(get_counter(&mut context, counter.pubkey()).await.count, 10);
+ 
+        let result = process(&mut context, &[increment_by(11)], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::Custom(12))
+        );
+        assert_eq!(get_counter(&mut context, counter.pubkey()).await.count, 10);
+    }
+ 
+    #[test]
+    fn test_default_counter_account() {
+        let counter_data = CounterAccount::default();
+        assert_eq!(counter_data.discriminator, CounterAccount::DISCRIMINATOR);
+        assert_eq!(counter_data.count, 0);
+        assert_eq!(counter_data.authority, Pubkey::default());
+        assert!(!counter_data.require_signer);
+        assert_eq!(counter_data.delegate_pda, Pubkey::default());
+        assert_eq!(counter_data.max_value, u64::MAX);
+        assert_eq!(counter_data.step, 1);
+        assert_eq!(counter_data.cooldown_secs, 0);
+        assert_eq!(counter_data.max_step_per_call, u64::MAX);
+ 
+        let bytes = borsh::to_vec(&counter_data).unwrap();
+        assert_eq!(bytes.len(), CounterAccount::LEN);
+        assert_eq!(CounterAccount::unpack(&bytes).unwrap(), counter_data);
+    }
+ 
+    #[test]
+    fn test_store_into_undersized_account_writes_nothing() {
+        let counter_data = CounterAccount::new(9, Pubkey::new_unique(), 0);
+        let mut data = vec![0xAA; CounterAccount::LEN - 1];
+        assert_eq!(
+            counter_data.store(&mut data).unwrap_err(),
+            ProgramError::AccountDataTooSmall
+        );
+        assert!(data.iter().all(|byte| *byte == 0xAA));
+ 
+        let mut data = vec![0xAA; CounterAccount::LEN];
+        counter_data.store(&mut data).unwrap();
+        assert_eq!(CounterAccount::unpack(&data).unwrap(), counter_data);
+    }
+ 
+    #[tokio::test]
+    async fn test_nonce_advances_on_every_mutation() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 0).await;
+        assert_eq!(get_counter(&mut context, counter.pubkey()).await.nonce, 0);
+ 
+        let mut data = vec![5];
+        100u64.serialize(&mut data).unwrap();
+        let set_max_value =
+            authority_instruction(program_id, counter.pubkey(), context.payer.pubkey(), &data);
+        let increment = increment_instruction(program_id, counter.pubkey());
+        let result = process(&mut context, &[increment, set_max_value], &[]).await;
+        assert!(result.result.is_ok());
+ 
+        let counter_data = get_counter(&mut context, counter.pubkey()).await;
+        assert_eq!(counter_data.count, 1);
+        assert_eq!(counter_data.nonce, 2);
+    }
+ 
+    #[test]
+    fn test_nonce_wraps_on_overflow() {
+        let mut counter_data = CounterAccount {
+            nonce: u64::MAX,
+            ..CounterAccount::default()
+        };
+        counter_data.bump_nonce();
+        assert_eq!(counter_data.nonce, 0);
+    }
+ 
+    fn initialize_raw_instruction(
+        program_id: Pubkey,
+        counter: Pubkey,
+        payer: Pubkey,
+        raw: Vec<u8>,
+    ) -> Instruction {
+        let mut data = vec![17];
+        raw.serialize(&mut data).unwrap();
+        Instruction::new_with_bytes(
+            program_id,
+            &data,
+            vec![
+                AccountMeta::new(counter, true),
+                AccountMeta::new(payer, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        )
+    }
+ 
+    #[tokio::test]
+    async fn test_initialize_raw() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let payer = context.payer.pubkey();
+        let configured = || CounterAccount {
+            count: 77,
+            authority: payer,
+            step: 5,
+            max_value: 1_000,
+            decimals: 2,
+            ..CounterAccount::default()
+        };
+        let expected = configured();
+ 
+        let counter = Keypair::new();
+        let raw = borsh::to_vec(&expected).unwrap();
+        let instruction = initialize_raw_instruction(program_id, counter.pubkey(), payer, raw);
+        let result = process(&mut context, &[instruction], &[&counter]).await;
+        assert!(result.result.is_ok());
+        assert_eq!(get_counter(&mut context, counter.pubkey()).await, expected);
+ 
+        // Bytes that do not decode as a counter are refused
+        let garbage = Keypair::new();
+        let instruction = initialize_raw_instruction(
+            program_id,
+            garbage.pubkey(),
+            payer,
+            vec![0xAB; CounterAccount::LEN],
+        );
+        let result = process(&mut context, &[instruction], &[&garbage]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::InvalidInstructionData)
+        );
+ 
+        // Valid counter bytes followed by padding are more than the layout holds
+        let padded = Keypair::new();
+        let mut raw = borsh::to_vec(&expected).unwrap();
+        raw.push(0);
+        let instruction = initialize_raw_instruction(program_id, padded.pubkey(), payer, raw);
+        let result = process(&mut context, &[instruction], &[&padded]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::RawDataTooLarge as u32)
+            )
+        );
+ 
+        // Raw bytes get the same configuration checks as InitializeCounterFull
+        for (invalid, error) in [
+            (
+                CounterAccount {
+                    step: 0,
+                    ..configured()
+                },
+                CounterError::InvalidStep,
+            ),
+            (
+                CounterAccount {
+                    count: 1_001,
+                    ..configured()
+                },
+                CounterError::InitialExceedsMax,
+            ),
+            (
+                CounterAccount {
+                    min_value: 78,
+                    ..configured()
+                },
+                CounterError::InitialBelowMin,
+            ),
+            (
+                CounterAccount {
+                    decimals: CounterAccount::MAX_DECIMALS + 1,
+                    ..configured()
+                },
+                CounterError::InvalidDecimals,
+            ),
+        ] {
+            let rejected = Keypair::new();
+            let raw = borsh::to_vec(&invalid).unwrap();
+            let instruction = initialize_raw_instruction(program_id, rejected.pubkey(), payer, raw);
+            let result = process(&mut context, &[instruction], &[&rejected]).await;
+            assert_eq!(
+                result.result.unwrap_err(),
+                TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+            );
+        }
+    }
+ 
+    #[tokio::test]
+    async fn test_executable_counter_account_is_rejected() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+ 
+        // The program itself is executable; the runtime may also demote it to
+        // read-only, so the executable check must come first
+        let result = process(
+            &mut context,
+            &[increment_instruction(program_id, program_id)],
+            &[],
+        )
+        .await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::InvalidAccountData)
+        );
+ 
+        let mut data = vec![0];
+        7u64.serialize(&mut data).unwrap();
+        let initialize = Instruction::new_with_bytes(
+            program_id,
+            &data,
+            vec![
+                AccountMeta::new(program_id, false),
+                AccountMeta::new(context.payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let result = process(&mut context, &[initialize], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::InvalidAccountData)
+        );
+    }
+ 
+    #[tokio::test]
+    async fn test_withdraw_keeps_min_reserve() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 0).await;
+        let authority = context.payer.pubkey();
+ 
+        // Fund the counter above rent and reserve part of the surplus
+        let fund =
+            solana_sdk::system_instruction::transfer(&authority, &counter.pubkey(), 1_000_000);
+        let mut data = vec![18];
+        400_000u64.serialize(&mut data).unwrap();
+        let set_reserve = authority_instruction(program_id, counter.pubkey(), authority, &data);
+        let result = process(&mut context, &[fund, set_reserve], &[]).await;
+        assert!(result.result.is_ok());
+ 
+        let withdraw = |lamports: u64| {
+            let mut data = vec![19];
+            lamports.serialize(&mut data).unwrap();
+            Instruction::new_with_bytes(
+                program_id,
+                &data,
+                vec![
+                    AccountMeta::new(counter.pubkey(), false),
+                    AccountMeta::new(authority, true),
+                ],
+            )
+        };
+        let result = process(&mut context, &[withdraw(600_001)], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::Custom(14))
+        );
+ 
+        let before = context
+            .banks_client
+            .get_balance(counter.pubkey())
+            .await
+            .unwrap();
+        let result = process(&mut context, &[withdraw(600_000)], &[]).await;
+        assert!(result.result.is_ok());
+        let after = context
+            .banks_client
+            .get_balance(counter.pubkey())
+            .await
+            .unwrap();
+        assert_eq!(before - after, 600_000);
+    }
+ 
+    // One instance of every instruction variant, in variant order
+    fn sample_instructions() -> Vec<CounterInstruction> {
+        vec![
+            CounterInstruction::InitializeCounter { initial_value: 1 },
+            CounterInstruction::IncrementCounter,
+            CounterInstruction::DecrementSaturating,
+            CounterInstruction::SetDelegate {
+                delegate_pda: Pubkey::new_unique(),
+            },
+            CounterInstruction::SetRequireSigner {
+                require_signer: true,
+            },
+            CounterInstruction::SetMaxValue { max_value: 3 },
+            CounterInstruction::NoOp,
+            CounterInstruction::MigrateCounter,
+            CounterInstruction::Configure {
+                step: Some(2),
+                max_value: None,
+                cooldown_secs: Some(5),
+            },
+            CounterInstruction::InitializeCounterFull {
+                initial_value: 1,
+                step: 2,
+                max_value: 3,
+                require_signer: false,
+                decimals: 4,
+                min_value: 1,
+                category: 2,
+                emit_events: false,
+            },
+            CounterInstruction::SetGlobalPause { paused: true },
+            CounterInstruction::DecrementWithReason { reason: 6 },
+            CounterInstruction::DescribeCounter,
+            CounterInstruction::MulAdd {
+                factor: 2,
+                addend: 1,
+            },
+            CounterInstruction::CloseAndTransfer,
+            CounterInstruction::IncrementBy { amount: 7 },
+            CounterInstruction::SetMaxStepPerCall {
+                max_step_per_call: 8,
+            },
+            CounterInstruction::InitializeRaw { data: vec![1, 2] },
+            CounterInstruction::SetMinReserve { min_reserve: 9 },
+            CounterInstruction::Withdraw { lamports: 10 },
+            CounterInstruction::QueryAccount,
+            CounterInstruction::SetStepAndTick { step: 11 },
+            CounterInstruction::Touch,
+            CounterInstruction::IncrementPercent { bps: 12 },
+            CounterInstruction::SetAutoFreeze {
+                auto_freeze_at_max: true,
+            },
+            CounterInstruction::InitializePdaCounter { initial_value: 13 },
+            CounterInstruction::AddSample { value: 14 },
+            CounterInstruction::ResetIfElapsed {
+                period_secs: 15,
+                require_elapsed: true,
+            },
+            CounterInstruction::ResetCounter,
+            CounterInstruction::CloseCounter,
+            CounterInstruction::DecrementWithProgress,
+            CounterInstruction::Metrics,
+            CounterInstruction::SwapCounters,
+            CounterInstruction::SetCategory { category: 16 },
+            CounterInstruction::IncrementAt { client_ts: 17 },
+            CounterInstruction::QueryRent { space: 18 },
+            CounterInstruction::SetSaturating { saturating: true },
+            CounterInstruction::SetAllowZero { allow_zero: false },
+            CounterInstruction::IncrementWithApproval,
+            CounterInstruction::IncrementTimes { n: 19 },
+            CounterInstruction::ProposeAuthority {
+                new_authority: Pubkey::new_from_array([20; 32]),
+            },
+            CounterInstruction::AcceptAuthority,
+            CounterInstruction::BulkQuery,
+            CounterInstruction::Checkpoint,
+            CounterInstruction::SetTokenGate {
+                gate_mint: Pubkey::new_from_array([22; 32]),
+                min_amount: 23,
+            },
+            CounterInstruction::IncrementIfHolder,
+            CounterInstruction::StampSlot,
+            CounterInstruction::SetEmitEvents { emit_events: true },
+            CounterInstruction::DecrementWithBorrow { amount: 24 },
+            CounterInstruction::CopyConfigFrom,
+            CounterInstruction::InitializeCounterStr {
+                value: "25".to_string(),
+            },
+            CounterInstruction::BatchIncrement,
+            CounterInstruction::InitializeCounterShared {
+                initial_value: 26,
+                first_share_bps: 5_000,
+            },
+            CounterInstruction::QueryStaleness,
+            CounterInstruction::InitializeConfig {
+                admin: Pubkey::new_from_array([27; 32]),
+            },
+            CounterInstruction::SetDailyLimit { daily_limit: 28 },
+            CounterInstruction::DecrementDaily { amount: 29 },
+            CounterInstruction::ApplyDelta { delta: -30 },
+            CounterInstruction::CheckIncrementBy { amount: 30 },
+            CounterInstruction::LockUntil { unlock_ts: 31 },
+            CounterInstruction::SetExpiry {
+                expires_at_slot: 32,
+            },
+            CounterInstruction::SlotsRemaining,
+            CounterInstruction::SweepClosed,
+            CounterInstruction::IncrementWithTip { lamports: 37 },
+            CounterInstruction::IncrementFresh { max_age_slots: 39 },
+            CounterInstruction::SetMeta {
+                key: "40".to_string(),
+                value: "41".to_string(),
+            },
+            CounterInstruction::RemoveMeta {
+                key: "42".to_string(),
+            },
+            CounterInstruction::SetCounter { value: 43 },
+            CounterInstruction::InitializeMintCounter { initial_value: 44 },
+            CounterInstruction::RefreshBump,
+            CounterInstruction::DecrementWithRemainder { amount: 45 },
+            CounterInstruction::InitializeMultiCounter,
+            CounterInstruction::IncrementSlot { slot: 15 },
+            CounterInstruction::SetSlot {
+                slot: 15,
+                value: 46,
+            },
+            CounterInstruction::DrainCounter,
+            CounterInstruction::SetCreationFee {
+                creation_fee: 47,
+                treasury: Pubkey::new_from_array([48; 32]),
+            },
+            CounterInstruction::SetMerkleRoot {
+                merkle_root: [49; 32],
+            },
+            CounterInstruction::IncrementIfInTree {
+                proof: vec![[50; 32], [51; 32]],
+                leaf: [52; 32],
+            },
+            CounterInstruction::SyncFromLamports,
+            CounterInstruction::AddDelegate {
+                delegate: Pubkey::new_from_array([53; 32]),
+            },
+            CounterInstruction::RemoveDelegate {
+                delegate: Pubkey::new_from_array([54; 32]),
+            },
+            CounterInstruction::InitializeAndIncrement { initial_value: 55 },
+        ]
+    }
+ 
+    #[test]
+    fn test_pack_uses_single_byte_variant() {
+        assert_eq!(CounterInstruction::IncrementCounter.pack(), vec![1]);
+        assert_eq!(
+            CounterInstruction::InitializeCounter { initial_value: 2 }.pack(),
+            vec![0, 2, 0, 0, 0, 0, 0, 0, 0]
+        );
+ 
+        // Every variant round-trips through unpack with its layout-table tag
+        let instructions = sample_instructions();
+        assert_eq!(instructions.len(), INSTRUCTION_LAYOUTS.len());
+        for (instruction, (variant, _)) in instructions.iter().zip(INSTRUCTION_LAYOUTS) {
+            let packed = instruction.pack();
+            assert_eq!(packed[0], *variant, "{instruction:?}");
+            let unpacked = CounterInstruction::unpack(&packed).unwrap();
+            assert_eq!(unpacked.pack(), packed, "{instruction:?}");
+        }
+    }
+ 
+    #[tokio::test]
+    async fn test_query_account_returns_count_and_lamports() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 31).await;
+ 
+        let query = Instruction::new_with_bytes(
+            program_id,
+            &[20], // 20 = query instruction
+            vec![AccountMeta::new_readonly(counter.pubkey(), false)],
+        );
+        let result = process(&mut context, &[query], &[]).await;
+        assert!(result.result.is_ok());
+ 
+        let return_data = result.metadata.unwrap().return_data.unwrap().data;
+        assert_eq!(return_data.len(), 16);
+        let account = context
+            .banks_client
+            .get_account(counter.pubkey())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(return_data[..8], 31u64.to_le_bytes());
+        assert_eq!(return_data[8..], account.lamports.to_le_bytes());
+    }
+ 
+    #[tokio::test]
+    async fn test_set_step_and_tick() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 0).await;
+ 
+        let mut data = vec![21];
+        4u64.serialize(&mut data).unwrap();
+        let instruction =
+            authority_instruction(program_id, counter.pubkey(), context.payer.pubkey(), &data);
+        let result = process(&mut context, &[instruction], &[]).await;
+        assert!(result.result.is_ok());
+ 
+        let counter_data = get_counter(&mut context, counter.pubkey()).await;
+        assert_eq!(counter_data.step, 4);
+        assert_eq!(counter_data.count, 4);
+    }
+ 
+    #[test]
+    fn test_space_for_bounds_huge_requests() {
+        assert_eq!(CounterAccount::space_for(0), Ok(CounterAccount::LEN));
+        let limit = MAX_PERMITTED_DATA_LENGTH - CounterAccount::LEN as u64;
+        assert_eq!(
+            CounterAccount::space_for(limit),
+            Ok(MAX_PERMITTED_DATA_LENGTH as usize)
+        );
+        for extra in [limit + 1, u64::MAX] {
+            assert_eq!(
+                CounterAccount::space_for(extra),
+                Err(ProgramError::Custom(15))
+            );
+        }
+    }
+ 
+    #[tokio::test]
+    async fn test_touch_creates_then_increments() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = Keypair::new();
+ 
+        let create = Instruction::new_with_bytes(
+            program_id,
+            &[22], // 22 = touch instruction
+            vec![
+                AccountMeta::new(counter.pubkey(), true),
+                AccountMeta::new(context.payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let result = process(&mut context, &[create], &[&counter]).await;
+        assert!(result.result.is_ok());
+        assert_eq!(get_counter(&mut context, counter.pubkey()).await.count, 1);
+ 
+        let increment = Instruction::new_with_bytes(
+            program_id,
+            &[22],
+            vec![AccountMeta::new(counter.pubkey(), false)],
+        );
+        let result = process(&mut context, &[increment], &[]).await;
+        assert!(result.result.is_ok());
+        assert_eq!(get_counter(&mut context, counter.pubkey()).await.count, 2);
+    }
+ 
+    fn increment_percent_instruction(program_id: Pubkey, counter: Pubkey, bps: u16) -> Instruction {
+        let mut data = vec![23];
+        bps.serialize(&mut data).unwrap();
+        Instruction::new_with_bytes(program_id, &data, vec![AccountMeta::new(counter, false)])
+    }
+ 
+    #[tokio::test]
+    async fn test_increment_percent() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 1_000).await;
+ 
+        let instruction = increment_percent_instruction(program_id, counter.pubkey(), 500);
+        let result = process(&mut context, &[instruction], &[]).await;
+        assert!(result.result.is_ok());
+        assert_eq!(
+            get_counter(&mut context, counter.pubkey()).await.count,
+            1_050
+        );
+ 
+        // 1050 * 0.01% = 0.105, truncated to no change
+        let instruction = increment_percent_instruction(program_id, counter.pubkey(), 1);
+        let result = process(&mut context, &[instruction], &[]).await;
+        assert!(result.result.is_ok());
+        assert_eq!(
+            get_counter(&mut context, counter.pubkey()).await.count,
+            1_050
+        );
+ 
+        let instruction = increment_percent_instruction(
+            program_id,
+            counter.pubkey(),
+            CounterAccount::MAX_INCREMENT_BPS + 1,
+        );
+        let result = process(&mut context, &[instruction], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::Custom(16))
+        );
+    }
+ 
+    #[tokio::test]
+    async fn test_auto_freeze_at_max() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 0).await;
+        let authority = context.payer.pubkey();
+ 
+        let mut data = vec![5];
+        3u64.serialize(&mut data).unwrap();
+        let set_max_value = authority_instruction(program_id, counter.pubkey(), authority, &data);
+        let set_auto_freeze =
+            authority_instruction(program_id, counter.pubkey(), authority, &[24, 1]);
+        let result = process(&mut context, &[set_max_value, set_auto_freeze], &[]).await;
+        assert!(result.result.is_ok());
+ 
+        for _ in 0..3 {
+            let result = process(
+                &mut context,
+                &[increment_instruction(program_id, counter.pubkey())],
+                &[],
+            )
+            .await;
+            assert!(result.result.is_ok());
+        }
+        let counter_data = get_counter(&mut context, counter.pubkey()).await;
+        assert_eq!(counter_data.count, 3);
+        assert!(counter_data.is_frozen);
+ 
+        let result = process(
+            &mut context,
+            &[increment_instruction(program_id, counter.pubkey())],
+            &[],
+        )
+        .await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::Custom(17))
+        );
+    }
+ 
+    // Initialize the PDA counter of `authority`, paid for by the context payer,
+    // check its stored bump against the derivation and return its address
+    async fn create_pda_counter(
+        context: &mut ProgramTestContext,
+        program_id: Pubkey,
+        authority: &Keypair,
+        initial_value: u64,
+    ) -> Pubkey {
+        let (counter, bump) = CounterAccount::find_pda(&program_id, &authority.pubkey());
+        let mut data = vec![25];
+        initial_value.serialize(&mut data).unwrap();
+        let instruction = Instruction::new_with_bytes(
+            program_id,
+            &data,
+            vec![
+                AccountMeta::new(counter, false),
+                AccountMeta::new_readonly(authority.pubkey(), true),
+                AccountMeta::new(context.payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        process(context, &[instruction], &[authority])
+            .await
+            .result
+            .unwrap();
+ 
+        let counter_data = get_counter(context, counter).await;
+        assert_eq!(counter_data.bump, bump);
+        assert_eq!(
+            Pubkey::create_program_address(
+                &[
+                    CounterAccount::PDA_SEED,
+                    authority.pubkey().as_ref(),
+                    &[bump]
+                ],
+                &program_id
+            ),
+            Ok(counter)
+        );
+        counter
+    }
+ 
+    #[tokio::test]
+    async fn test_pda_counter_end_to_end() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let authority = Keypair::new();
+        let counter = create_pda_counter(&mut context, program_id, &authority, 5).await;
+ 
+        let result = process(
+            &mut context,
+            &[increment_instruction(program_id, counter)],
+            &[],
+        )
+        .await;
+        assert!(result.result.is_ok());
+        let counter_data = get_counter(&mut context, counter).await;
+        assert_eq!(counter_data.count, 6);
+        assert_eq!(counter_data.authority, authority.pubkey());
+ 
+        // An address other than the authority's PDA is refused
+        let other = Keypair::new();
+        let mut data = vec![25];
+        0u64.serialize(&mut data).unwrap();
+        let instruction = Instruction::new_with_bytes(
+            program_id,
+            &data,
+            vec![
+                AccountMeta::new(
+                    CounterAccount::find_pda(&program_id, &other.pubkey()).0,
+                    false,
+                ),
+                AccountMeta::new_readonly(authority.pubkey(), true),
+                AccountMeta::new(context.payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let result = process(&mut context, &[instruction], &[&authority]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::InvalidSeeds)
+        );
+    }
+ 
+    #[tokio::test]
+    async fn test_increment_uninitialized_account() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let fresh = Keypair::new();
+        let fund = solana_sdk::system_instruction::transfer(
+            &context.payer.pubkey(),
+            &fresh.pubkey(),
+            1_000_000,
+        );
+        assert!(process(&mut context, &[fund], &[]).await.result.is_ok());
+ 
+        let result = process(
+            &mut context,
+            &[increment_instruction(program_id, fresh.pubkey())],
+            &[],
+        )
+        .await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::Custom(18))
+        );
+        assert!(result
+            .metadata
+            .unwrap()
+            .log_messages
+            .iter()
+            .any(|log| log.contains("initialize it first")));
+    }
+ 
+    #[test]
+    fn test_every_instruction_round_trips_through_builder() {
+        let program_id = Pubkey::new_unique();
+        let accounts = vec![AccountMeta::new(Pubkey::new_unique(), false)];
+        for original in sample_instructions() {
+            let instruction = original.instruction(&program_id, accounts.clone());
+            assert_eq!(instruction.program_id, program_id);
+            assert_eq!(instruction.accounts, accounts);
+            assert_eq!(CounterInstruction::unpack(&instruction.data), Ok(original));
+        }
+    }
+ 
+    #[tokio::test]
+    async fn test_add_sample_accumulates_sum() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 0).await;
+ 
+        for value in [10u64, 20, 30] {
+            let mut data = vec![26];
+            value.serialize(&mut data).unwrap();
+            let instruction = Instruction::new_with_bytes(
+                program_id,
+                &data,
+                vec![AccountMeta::new(counter.pubkey(), false)],
+            );
+            assert!(process(&mut context, &[instruction], &[])
+                .await
+                .result
+                .is_ok());
+        }
+ 
+        let counter_data = get_counter(&mut context, counter.pubkey()).await;
+        assert_eq!(counter_data.sum, 60);
+        assert_eq!(counter_data.samples, 3);
+        assert_eq!(counter_data.mean(), Some(20));
+        assert_eq!(CounterAccount::default().mean(), None);
+    }
+ 
+    // Move the bank clock forward by `secs` seconds
+    async fn advance_clock(context: &mut ProgramTestContext, secs: i64) {
+        let mut clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+        clock.unix_timestamp += secs;
+        context.set_sysvar(&clock);
+    }
+ 
+    #[tokio::test]
+    async fn test_reset_if_elapsed() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 5).await;
+        let reset = |require_elapsed: bool| {
+            let mut data = vec![27];
+            (3_600u32, require_elapsed).serialize(&mut data).unwrap();
+            Instruction::new_with_bytes(
+                program_id,
+                &data,
+                vec![AccountMeta::new(counter.pubkey(), false)],
+            )
+        };
+ 
+        // Too early: a no-op by default, an error when required
+        assert!(process(&mut context, &[reset(false)], &[])
+            .await
+            .result
+            .is_ok());
+        assert_eq!(get_counter(&mut context, counter.pubkey()).await.count, 5);
+        let result = process(&mut context, &[reset(true)], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::Custom(19))
+        );
+ 
+        advance_clock(&mut context, 3_600).await;
+        assert!(process(&mut context, &[reset(true)], &[])
+            .await
+            .result
+            .is_ok());
+        assert_eq!(get_counter(&mut context, counter.pubkey()).await.count, 0);
+    }
+ 
+    #[tokio::test]
+    async fn test_lifetime_total_survives_reset() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 0).await;
+        let increment = || increment_instruction(program_id, counter.pubkey());
+ 
+        for _ in 0..3 {
+            assert!(process(&mut context, &[increment()], &[])
+                .await
+                .result
+                .is_ok());
+        }
+        let reset =
+            authority_instruction(program_id, counter.pubkey(), context.payer.pubkey(), &[28]);
+        assert!(process(&mut context, &[reset], &[]).await.result.is_ok());
+        assert_eq!(get_counter(&mut context, counter.pubkey()).await.count, 0);
+        for _ in 0..2 {
+            assert!(process(&mut context, &[increment()], &[])
+                .await
+                .result
+                .is_ok());
+        }
+ 
+        let counter_data = get_counter(&mut context, counter.pubkey()).await;
+        assert_eq!(counter_data.count, 2);
+        assert_eq!(counter_data.lifetime_total, 5);
+    }
+ 
+    #[tokio::test]
+    async fn test_close_counter_refunds_authority_by_default() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let authority = Keypair::new();
+        let fund = solana_sdk::system_instruction::transfer(
+            &context.payer.pubkey(),
+            &authority.pubkey(),
+            10_000_000,
+        );
+        assert!(process(&mut context, &[fund], &[]).await.result.is_ok());
+        let counter = create_pda_counter(&mut context, program_id, &authority, 3).await;
+        let rent = context.banks_client.get_balance(counter).await.unwrap();
+        let before = context
+            .banks_client
+            .get_balance(authority.pubkey())
+            .await
+            .unwrap();
+ 
+        // The context payer covers fees, so the authority gains exactly the rent
+        let close = Instruction::new_with_bytes(
+            program_id,
+            &[29], // 29 = close instruction
+            vec![
+                AccountMeta::new(counter, false),
+                AccountMeta::new(authority.pubkey(), true),
+            ],
+        );
+        let result = process(&mut context, &[close], &[&authority]).await;
+        assert!(result.result.is_ok());
+ 
+        let after = context
+            .banks_client
+            .get_balance(authority.pubkey())
+            .await
+            .unwrap();
+        assert_eq!(after - before, rent);
+        assert!(context
+            .banks_client
+            .get_account(counter)
+            .await
+            .unwrap()
+            .is_none());
+    }
+ 
+    #[tokio::test]
+    async fn test_decrement_with_progress() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 10).await;
+        let mut data = vec![5];
+        10u64.serialize(&mut data).unwrap();
+        let set_max_value =
+            authority_instruction(program_id, counter.pubkey(), context.payer.pubkey(), &data);
+        assert!(process(&mut context, &[set_max_value], &[])
+            .await
+            .result
+            .is_ok());
+ 
+        let decrement = Instruction::new_with_bytes(
+            program_id,
+            &[30], // 30 = decrement with progress instruction
+            vec![AccountMeta::new(counter.pubkey(), false)],
+        );
+        let result = process(&mut context, &[decrement], &[]).await;
+        assert!(result.result.is_ok());
+        assert_eq!(get_counter(&mut context, counter.pubkey()).await.count, 9);
+ 
+        let return_data = result.metadata.unwrap().return_data.unwrap().data;
+        assert_eq!(return_data[..8], 1u64.to_le_bytes());
+    }
+ 
+    fn initialize_full_instruction(
+        program_id: Pubkey,
+        counter: Pubkey,
+        payer: Pubkey,
+        initial_value: u64,
+        max_value: u64,
+        min_value: u64,
+    ) -> Instruction {
+        CounterInstruction::InitializeCounterFull {
+            initial_value,
+            step: 1,
+            max_value,
+            require_signer: false,
+            decimals: 0,
+            min_value,
+            category: 0,
+            emit_events: true,
+        }
+        .instruction(
+            &program_id,
+            vec![
+                AccountMeta::new(counter, true),
+                AccountMeta::new(payer, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        )
+    }
+ 
+    #[tokio::test]
+    async fn test_initialize_full_rejects_out_of_bounds_initial_value() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let payer = context.payer.pubkey();
+ 
+        for (max_value, min_value, code) in [(10, 0, 7), (100, 20, 20)] {
+            let counter = Keypair::new();
+            let instruction = initialize_full_instruction(
+                program_id,
+                counter.pubkey(),
+                payer,
+                15,
+                max_value,
+                min_value,
+            );
+            let result = process(&mut context, &[instruction], &[&counter]).await;
+            assert_eq!(
+                result.result.unwrap_err(),
+                TransactionError::InstructionError(0, InstructionError::Custom(code))
+            );
+        }
+ 
+        // Within bounds, the floor holds against decrements
+        let counter = Keypair::new();
+        let instruction =
+            initialize_full_instruction(program_id, counter.pubkey(), payer, 15, 100, 15);
+        assert!(process(&mut context, &[instruction], &[&counter])
+            .await
+            .result
+            .is_ok());
+        let decrement = Instruction::new_with_bytes(
+            program_id,
+            &[30],
+            vec![AccountMeta::new(counter.pubkey(), false)],
+        );
+        let result = process(&mut context, &[decrement], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::Custom(21))
+        );
+    }
+ 
+    #[test]
+    fn test_opcodes_are_fixed() {
+        let expected = [
+            (OP_INITIALIZE, 0),
+            (OP_INCREMENT, 1),
+            (OP_DECREMENT_SATURATING, 2),
+            (OP_SET_DELEGATE, 3),
+            (OP_SET_REQUIRE_SIGNER, 4),
+            (OP_SET_MAX_VALUE, 5),
+            (OP_NOOP, 6),
+            (OP_MIGRATE, 7),
+            (OP_CONFIGURE, 8),
+            (OP_INITIALIZE_FULL, 9),
+            (OP_SET_GLOBAL_PAUSE, 10),
+            (OP_DECREMENT_WITH_REASON, 11),
+            (OP_DESCRIBE, 12),
+            (OP_MUL_ADD, 13),
+            (OP_CLOSE_AND_TRANSFER, 14),
+            (OP_INCREMENT_BY, 15),
+            (OP_SET_MAX_STEP_PER_CALL, 16),
+            (OP_INITIALIZE_RAW, 17),
+            (OP_SET_MIN_RESERVE, 18),
+            (OP_WITHDRAW, 19),
+            (OP_QUERY_ACCOUNT, 20),
+            (OP_SET_STEP_AND_TICK, 21),
+            (OP_TOUCH, 22),
+            (OP_INCREMENT_PERCENT, 23),
+            (OP_SET_AUTO_FREEZE, 24),
+            (OP_INITIALIZE_PDA, 25),
+            (OP_ADD_SAMPLE, 26),
+            (OP_RESET_IF_ELAPSED, 27),
+            (OP_RESET, 28),
+            (OP_CLOSE, 29),
+            (OP_DECREMENT_WITH_PROGRESS, 30),
+            (OP_METRICS, 31),
+            (OP_SWAP, 32),
+            (OP_SET_CATEGORY, 33),
+            (OP_INCREMENT_AT, 34),
+            (OP_QUERY_RENT, 35),
+            (OP_SET_SATURATING, 36),
+            (OP_SET_ALLOW_ZERO, 37),
+            (OP_INCREMENT_WITH_APPROVAL, 38),
+            (OP_INCREMENT_TIMES, 39),
+            (OP_PROPOSE_AUTHORITY, 40),
+            (OP_ACCEPT_AUTHORITY, 41),
+            (OP_BULK_QUERY, 42),
+            (OP_CHECKPOINT, 43),
+            (OP_SET_TOKEN_GATE, 44),
+            (OP_INCREMENT_IF_HOLDER, 45),
+            (OP_STAMP_SLOT, 46),
+            (OP_SET_EMIT_EVENTS, 47),
+            (OP_DECREMENT_WITH_BORROW, 48),
+            (OP_COPY_CONFIG_FROM, 49),
+            (OP_INITIALIZE_STR, 50),
+            (OP_BATCH_INCREMENT, 51),
+            (OP_INITIALIZE_SHARED, 52),
+            (OP_QUERY_STALENESS, 53),
+            (OP_INITIALIZE_CONFIG, 54),
+            (OP_SET_DAILY_LIMIT, 55),
+            (OP_DECREMENT_DAILY, 56),
+            (OP_APPLY_DELTA, 57),
+            (OP_CHECK_INCREMENT_BY, 58),
+            (OP_LOCK_UNTIL, 59),
+            (OP_SET_EXPIRY, 60),
+            (OP_SLOTS_REMAINING, 61),
+            (OP_SWEEP_CLOSED, 62),
+            (OP_INCREMENT_WITH_TIP, 63),
+            (OP_INCREMENT_FRESH, 64),
+            (OP_SET_META, 65),
+            (OP_REMOVE_META, 66),
+            (OP_SET_COUNTER, 67),
+            (OP_INITIALIZE_MINT_COUNTER, 68),
+            (OP_REFRESH_BUMP, 69),
+            (OP_DECREMENT_WITH_REMAINDER, 70),
+            (OP_INITIALIZE_MULTI_COUNTER, 71),
+            (OP_INCREMENT_SLOT, 72),
+            (OP_SET_SLOT, 73),
+            (OP_DRAIN_COUNTER, 74),
+            (OP_SET_CREATION_FEE, 75),
+            (OP_SET_MERKLE_ROOT, 76),
+            (OP_INCREMENT_IF_IN_TREE, 77),
+            (OP_SYNC_FROM_LAMPORTS, 78),
+            (OP_ADD_DELEGATE, 79),
+            (OP_REMOVE_DELEGATE, 80),
+            (OP_INITIALIZE_AND_INCREMENT, 81),
+        ];
+        for ((opcode, byte), (layout_opcode, _)) in expected.iter().zip(INSTRUCTION_LAYOUTS) {
+            assert_eq!(opcode, byte);
+            assert_eq!(layout_opcode, byte);
+        }
+ 
+        // The encoded tag comes from the opcode, not the variant's position
+        for instruction in sample_instructions() {
+            assert_eq!(
+                instruction.pack()[0],
+                instruction.opcode(),
+                "{instruction:?}"
+            );
+        }
+        assert_eq!(CounterInstruction::IncrementCounter.opcode(), OP_INCREMENT);
+        assert_eq!(CounterInstruction::CloseCounter.pack(), vec![OP_CLOSE]);
+    }
+ 
+    #[tokio::test]
+    async fn test_metrics_snapshot() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 0).await;
+        let authority = context.payer.pubkey();
+ 
+        // Two increments, then a max of 2 with auto-freeze locks the counter
+        for _ in 0..2 {
+            let result = process(
+                &mut context,
+                &[increment_instruction(program_id, counter.pubkey())],
+                &[],
+            )
+            .await;
+            assert!(result.result.is_ok());
+        }
+        let mut data = vec![OP_SET_MAX_VALUE];
+        2u64.serialize(&mut data).unwrap();
+        let set_max_value = authority_instruction(program_id, counter.pubkey(), authority, &data);
+        let set_auto_freeze = authority_instruction(
+            program_id,
+            counter.pubkey(),
+            authority,
+            &[OP_SET_AUTO_FREEZE, 1],
+        );
+        let result = process(&mut context, &[set_max_value, set_auto_freeze], &[]).await;
+        assert!(result.result.is_ok());
+ 
+        let metrics = CounterInstruction::Metrics.instruction(
+            &program_id,
+            vec![AccountMeta::new_readonly(counter.pubkey(), false)],
+        );
+        let result = process(&mut context, &[metrics], &[]).await;
+        assert!(result.result.is_ok());
+        let return_data = result.metadata.unwrap().return_data.unwrap().data;
+        let counter_data = get_counter(&mut context, counter.pubkey()).await;
+        assert_eq!(
+            CounterMetrics::try_from_slice(&return_data).unwrap(),
+            CounterMetrics {
+                count: 2,
+                nonce: 4,
+                increment_count: 2,
+                last_updated: counter_data.last_updated,
+                is_frozen: true,
+            }
+        );
+    }
+ 
+    #[tokio::test]
+    async fn test_swap_counters() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let first = initialize_counter(&mut context, program_id, 3).await;
+        let second = initialize_counter(&mut context, program_id, 8).await;
+        let authority = context.payer.pubkey();
+        let swap = |second: Pubkey| {
+            CounterInstruction::SwapCounters.instruction(
+                &program_id,
+                vec![
+                    AccountMeta::new(first.pubkey(), false),
+                    AccountMeta::new(second, false),
+                    AccountMeta::new_readonly(authority, true),
+                ],
+            )
+        };
+ 
+        // Both counters share the payer as authority, passed once
+        let result = process(&mut context, &[swap(second.pubkey())], &[]).await;
+        assert!(result.result.is_ok());
+        assert_eq!(get_counter(&mut context, first.pubkey()).await.count, 8);
+        assert_eq!(get_counter(&mut context, second.pubkey()).await.count, 3);
+ 
+        let result = process(&mut context, &[swap(first.pubkey())], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::InvalidArgument)
+        );
+    }
+ 
+    #[tokio::test]
+    async fn test_set_category_shows_in_description() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 0).await;
+ 
+        let mut data = vec![OP_SET_CATEGORY];
+        42u16.serialize(&mut data).unwrap();
+        let set_category =
+            authority_instruction(program_id, counter.pubkey(), context.payer.pubkey(), &data);
+        let describe = CounterInstruction::DescribeCounter.instruction(
+            &program_id,
+            vec![AccountMeta::new_readonly(counter.pubkey(), false)],
+        );
+        let result = process(&mut context, &[set_category, describe], &[]).await;
+        assert!(result.result.is_ok());
+ 
+        let return_data = result.metadata.unwrap().return_data.unwrap().data;
+        let description = CounterDescription::try_from_slice(&return_data).unwrap();
+        assert_eq!(description.category, Some(42));
+    }
+ 
+    #[test]
+    fn test_initialize_full_accepts_older_payloads() {
+        let mut data = vec![OP_INITIALIZE_FULL];
+        (5u64, 1u64, 10u64, false, 0u8)
+            .serialize(&mut data)
+            .unwrap();
+        let legacy = CounterInstruction::unpack(&data).unwrap();
+        data.extend_from_slice(&2u64.to_le_bytes());
+        let with_floor = CounterInstruction::unpack(&data).unwrap();
+        data.extend_from_slice(&7u16.to_le_bytes());
+        let with_category = CounterInstruction::unpack(&data).unwrap();
+ 
+        data.push(0);
+        let with_events_off = CounterInstruction::unpack(&data).unwrap();
+ 
+        let expected =
+            |min_value, category, emit_events| CounterInstruction::InitializeCounterFull {
+                initial_value: 5,
+                step: 1,
+                max_value: 10,
+                require_signer: false,
+                decimals: 0,
+                min_value,
+                category,
+                emit_events,
+            };
+        assert_eq!(legacy, expected(0, 0, true));
+        assert_eq!(with_floor, expected(2, 0, true));
+        assert_eq!(with_category, expected(2, 7, true));
+        assert_eq!(with_events_off, expected(2, 7, false));
+        data.push(0);
+        assert!(CounterInstruction::unpack(&data).is_err());
+    }
+ 
+    // Feature matrix: each test below only exists under one combination of
+    // `client` and `no-entrypoint`. The default run covers the program build,
+    // `--all-features` the library client and `--features client` the program
+    // with the client helpers beside it, so all three runs are needed to
+    // compile every slice of the gated public API
+ 
+    #[cfg(not(feature = "no-entrypoint"))]
+    #[tokio::test]
+    async fn test_feature_matrix_program_build_exports_entrypoint() {
+        // The loader calls this exact symbol; deploying without it would fail
+        let _: unsafe extern "C" fn(*mut u8) -> u64 = crate::processor::entrypoint;
+ 
+        // The handler it wraps serves real instructions and rejects bad data
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let noop = CounterInstruction::NoOp.instruction(&program_id, vec![]);
+        let result = process(&mut context, &[noop], &[]).await;
+        assert!(result.result.is_ok());
+        assert!(result
+            .metadata
+            .unwrap()
+            .log_messages
+            .contains(&"Program log: counter:noop".to_string()));
+        let empty = Instruction::new_with_bytes(program_id, &[], vec![]);
+        let result = process(&mut context, &[empty], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::InvalidInstructionData)
+        );
+    }
+ 
+    #[cfg(all(feature = "client", feature = "no-entrypoint"))]
+    #[test]
+    fn test_feature_matrix_library_client_round_trips() {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+ 
+        // A library consumer builds instructions and decodes events without the
+        // program's entrypoint linked in
+        let program_id = Pubkey::new_from_array([9; 32]);
+        let counter = Pubkey::new_from_array([3; 32]);
+        for sample in sample_instructions() {
+            let instruction =
+                sample.instruction(&program_id, vec![AccountMeta::new(counter, false)]);
+            assert_eq!(instruction.program_id, program_id);
+            assert_eq!(
+                CounterInstruction::unpack(&instruction.data).unwrap(),
+                sample
+            );
+        }
+ 
+        let event = CounterEvent::CountChanged { counter, count: 7 };
+        let logs = vec![format!(
+            "Program data: {}",
+            STANDARD.encode(borsh::to_vec(&event).unwrap())
+        )];
+        assert_eq!(crate::client::parse_events(&logs), vec![event]);
+    }
+ 
+    #[cfg(all(feature = "client", not(feature = "no-entrypoint")))]
+    #[tokio::test]
+    async fn test_feature_matrix_program_and_client_share_wire_format() {
+        // With both the entrypoint and the client helpers compiled in, a
+        // client-built instruction is accepted by the program it targets
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 4).await;
+ 
+        let describe = CounterInstruction::DescribeCounter.instruction(
+            &program_id,
+            vec![AccountMeta::new_readonly(counter.pubkey(), false)],
+        );
+        let result = process(&mut context, &[describe], &[]).await;
+        assert!(result.result.is_ok());
+        assert!(crate::client::parse_events(&result.metadata.unwrap().log_messages).is_empty());
+    }
+ 
+    #[tokio::test]
+    async fn test_increment_at_rejects_timestamp_regression() {
+        let program_id = Pubkey::new_unique();
+        let counter = Pubkey::new_unique();
+        let mut program_test = program_test(program_id);
+        // Start from a counter last touched at the epoch, before any client_ts
+        let data = borsh::to_vec(&CounterAccount::new(0, Pubkey::new_unique(), 0)).unwrap();
+        program_test.add_account(
+            counter,
+            Account {
+                lamports: Rent::default().minimum_balance(data.len()),
+                data,
+                owner: program_id,
+                ..Account::default()
+            },
+        );
+        let mut context = program_test.start_with_context().await;
+        let increment_at = |client_ts| {
+            CounterInstruction::IncrementAt { client_ts }
+                .instruction(&program_id, vec![AccountMeta::new(counter, false)])
+        };
+ 
+        let result = process(&mut context, &[increment_at(100)], &[]).await;
+        assert!(result.result.is_ok());
+        let result = process(&mut context, &[increment_at(50)], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::TimestampRegression as u32)
+            )
+        );
+        let result = process(&mut context, &[increment_at(200)], &[]).await;
+        assert!(result.result.is_ok());
+ 
+        let counter_data = get_counter(&mut context, counter).await;
+        assert_eq!(counter_data.count, 2);
+        assert_eq!(counter_data.last_updated, 200);
+ 
+        // A stamp beyond the allowed skew would lock the cooldown, so it is refused
+        let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+        let future = clock.unix_timestamp + CounterAccount::MAX_CLIENT_TS_SKEW_SECS + 1;
+        let result = process(&mut context, &[increment_at(future)], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::TimestampInFuture as u32)
+            )
+        );
+        assert_eq!(get_counter(&mut context, counter).await.last_updated, 200);
+    }
+ 
+    #[tokio::test]
+    async fn test_query_rent_returns_minimum_balance() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+ 
+        // No accounts are needed; the runtime supplies the Rent sysvar
+        let query_rent =
+            CounterInstruction::QueryRent { space: 8 }.instruction(&program_id, vec![]);
+        let result = process(&mut context, &[query_rent], &[]).await;
+        assert!(result.result.is_ok());
+ 
+        let return_data = result.metadata.unwrap().return_data.unwrap().data;
+        assert_eq!(
+            return_data,
+            Rent::default().minimum_balance(8).to_le_bytes().to_vec()
+        );
+    }
+ 
+    #[tokio::test]
+    async fn test_saturating_increment_flags_clamped_calls() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, u64::MAX - 1).await;
+        let set_saturating = authority_instruction(
+            program_id,
+            counter.pubkey(),
+            context.payer.pubkey(),
+            &[OP_SET_SATURATING, 1],
+        );
+        let result = process(&mut context, &[set_saturating], &[]).await;
+        assert!(result.result.is_ok());
+ 
+        // The first call lands exactly on u64::MAX; the second is clamped
+        for expected_flag in [0, 1] {
+            let result = process(
+                &mut context,
+                &[increment_instruction(program_id, counter.pubkey())],
+                &[],
+            )
+            .await;
+            assert!(result.result.is_ok());
+            let return_data = result.metadata.unwrap().return_data.unwrap().data;
+            assert_eq!(return_data[..8], 0u64.to_le_bytes());
+            assert_eq!(return_data[8], expected_flag);
+        }
+        assert_eq!(
+            get_counter(&mut context, counter.pubkey()).await.count,
+            u64::MAX
+        );
+    }
+ 
+    #[tokio::test]
+    async fn test_allow_zero_decides_whether_decrement_closes() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let payer = context.payer.pubkey();
+ 
+        for allow_zero in [false, true] {
+            let counter = initialize_counter(&mut context, program_id, 1).await;
+            let set_allow_zero = authority_instruction(
+                program_id,
+                counter.pubkey(),
+                payer,
+                &[OP_SET_ALLOW_ZERO, u8::from(allow_zero)],
+            );
+            // The authority rides along to receive the rent if the counter closes
+            let decrement = CounterInstruction::DecrementSaturating.instruction(
+                &program_id,
+                vec![
+                    AccountMeta::new(counter.pubkey(), false),
+                    AccountMeta::new(payer, false),
+                ],
+            );
+            let result = process(&mut context, &[set_allow_zero, decrement], &[]).await;
+            assert!(result.result.is_ok());
+ 
+            let account = context
+                .banks_client
+                .get_account(counter.pubkey())
+                .await
+                .unwrap();
+            if allow_zero {
+                assert_eq!(
+                    CounterAccount::load(&account.unwrap().data).unwrap().count,
+                    0
+                );
+            } else {
+                assert!(account.is_none());
+            }
+        }
+    }
+ 
+    #[tokio::test]
+    async fn test_remainder_drain_and_borrow_close_a_counter_left_at_zero() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let payer = context.payer.pubkey();
+        let disallow_zero = |counter: Pubkey| {
+            authority_instruction(program_id, counter, payer, &[OP_SET_ALLOW_ZERO, 0])
+        };
+ 
+        let remainder_counter = initialize_counter(&mut context, program_id, 2).await;
+        let drain_counter = initialize_counter(&mut context, program_id, 7).await;
+        let remainder = CounterInstruction::DecrementWithRemainder { amount: 5 }.instruction(
+            &program_id,
+            vec![
+                AccountMeta::new(remainder_counter.pubkey(), false),
+                AccountMeta::new(payer, false),
+            ],
+        );
+        let drain = CounterInstruction::DrainCounter.instruction(
+            &program_id,
+            vec![
+                AccountMeta::new(drain_counter.pubkey(), false),
+                AccountMeta::new(payer, true),
+            ],
+        );
+        let result = process(
+            &mut context,
+            &[
+                disallow_zero(remainder_counter.pubkey()),
+                remainder,
+                disallow_zero(drain_counter.pubkey()),
+                drain,
+            ],
+            &[],
+        )
+        .await;
+        assert!(result.result.is_ok());
+ 
+        // Borrowing the whole of both counters drains each of them
+        let first = initialize_counter(&mut context, program_id, 3).await;
+        let second = initialize_counter(&mut context, program_id, 10).await;
+        let borrow = CounterInstruction::DecrementWithBorrow { amount: 13 }.instruction(
+            &program_id,
+            vec![
+                AccountMeta::new(first.pubkey(), false),
+                AccountMeta::new(second.pubkey(), false),
+                AccountMeta::new(payer, true),
+            ],
+        );
+        let result = process(
+            &mut context,
+            &[
+                disallow_zero(first.pubkey()),
+                disallow_zero(second.pubkey()),
+                borrow,
+            ],
+            &[],
+        )
+        .await;
+        assert!(result.result.is_ok());
+ 
+        for counter in [remainder_counter, drain_counter, first, second] {
+            let account = context
+                .banks_client
+                .get_account(counter.pubkey())
+                .await
+                .unwrap();
+            assert!(account.is_none());
+        }
+    }
+ 
+    // Build an ed25519 precompile instruction verifying `signer`'s signature
+    // over `message`, laid out as the runtime expects: a signature count and
+    // padding byte, one set of offsets, then the key, signature and message
+    fn ed25519_verify_instruction(signer: &Keypair, message: &[u8]) -> Instruction {
+        let signature = signer.sign_message(message);
+        let pubkey_at: u16 = 16;
+        let signature_at = pubkey_at + 32;
+        let message_at = signature_at + 64;
+        let mut data = vec![1, 0];
+        for field in [
+            signature_at,
+            u16::MAX,
+            pubkey_at,
+            u16::MAX,
+            message_at,
+            message.len() as u16,
+            u16::MAX,
+        ] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        data.extend_from_slice(signer.pubkey().as_ref());
+        data.extend_from_slice(signature.as_ref());
+        data.extend_from_slice(message);
+        Instruction::new_with_bytes(solana_program::ed25519_program::id(), &data, vec![])
+    }
+ 
+    #[tokio::test]
+    async fn test_increment_with_approval_requires_authority_signature() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 0).await;
+        let increment = CounterInstruction::IncrementWithApproval.instruction(
+            &program_id,
+            vec![
+                AccountMeta::new(counter.pubkey(), false),
+                AccountMeta::new_readonly(solana_program::sysvar::instructions::id(), false),
+            ],
+        );
+        let approval = |nonce: u64| format!("increment:{}:{}", counter.pubkey(), nonce);
+        let approval_missing = TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(CounterError::ApprovalMissing as u32),
+        );
+ 
+        // Without a verify instruction, or with a non-authority signer, nothing moves
+        let result = process(&mut context, std::slice::from_ref(&increment), &[]).await;
+        assert_eq!(result.result.unwrap_err(), approval_missing);
+        let stranger = Keypair::new();
+        let verify = ed25519_verify_instruction(&stranger, approval(0).as_bytes());
+        let result = process(&mut context, &[verify, increment.clone()], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(
+                1,
+                InstructionError::Custom(CounterError::ApprovalMissing as u32)
+            )
+        );
+ 
+        // A corrupted signature is rejected by the precompile itself
+        let mut forged = ed25519_verify_instruction(&context.payer, approval(0).as_bytes());
+        forged.data[48] ^= 1;
+        let blockhash = context.get_new_latest_blockhash().await.unwrap();
+        let transaction = Transaction::new_signed_with_payer(
+            &[forged, increment.clone()],
+            Some(&context.payer.pubkey()),
+            &[&context.payer],
+            blockhash,
+        );
+        assert!(context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .is_err());
+ 
+        let verify = ed25519_verify_instruction(&context.payer, approval(0).as_bytes());
+        let result = process(&mut context, &[verify.clone(), increment.clone()], &[]).await;
+        assert!(result.result.is_ok());
+        assert_eq!(get_counter(&mut context, counter.pubkey()).await.count, 1);
+ 
+        // The nonce has moved on, so replaying the same approval fails
+        let result = process(&mut context, &[verify, increment], &[]).await;
+        assert!(result.result.is_err());
+        assert_eq!(get_counter(&mut context, counter.pubkey()).await.count, 1);
+    }
+ 
+    #[tokio::test]
+    async fn test_increment_times_reverts_the_whole_run_on_failure() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 0).await;
+        let payer = context.payer.pubkey();
+        let increment_times = |n| {
+            CounterInstruction::IncrementTimes { n }
+                .instruction(&program_id, vec![AccountMeta::new(counter.pubkey(), false)])
+        };
+ 
+        let result = process(&mut context, &[increment_times(3)], &[]).await;
+        assert!(result.result.is_ok());
+        assert_eq!(get_counter(&mut context, counter.pubkey()).await.count, 3);
+ 
+        // Builtins have no compute meter, so ComputeBudgetExhausted only fires
+        // on-chain; a run stopped by max_value part way through reverts the same
+        let set_max_value = authority_instruction(
+            program_id,
+            counter.pubkey(),
+            payer,
+            &CounterInstruction::SetMaxValue { max_value: 100 }.pack(),
+        );
+        let result = process(
+            &mut context,
+            &[set_max_value, increment_times(u32::MAX)],
+            &[],
+        )
+        .await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(
+                1,
+                InstructionError::Custom(CounterError::MaxValueExceeded as u32)
+            )
+        );
+        assert_eq!(get_counter(&mut context, counter.pubkey()).await.count, 3);
+    }
+ 
+    #[tokio::test]
+    async fn test_authority_transfer_takes_effect_on_accept() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 0).await;
+        let old_authority = context.payer.pubkey();
+        let new_authority = Keypair::new();
+        let set_max_value = |authority, max_value| {
+            authority_instruction(
+                program_id,
+                counter.pubkey(),
+                authority,
+                &CounterInstruction::SetMaxValue { max_value }.pack(),
+            )
+        };
+        let unauthorized = TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(CounterError::Unauthorized as u32),
+        );
+ 
+        let propose = authority_instruction(
+            program_id,
+            counter.pubkey(),
+            old_authority,
+            &CounterInstruction::ProposeAuthority {
+                new_authority: new_authority.pubkey(),
+            }
+            .pack(),
+        );
+        let result = process(&mut context, &[propose], &[]).await;
+        assert!(result.result.is_ok());
+ 
+        // Until the proposal is accepted, control stays with the old authority
+        let result = process(&mut context, &[set_max_value(old_authority, 10)], &[]).await;
+        assert!(result.result.is_ok());
+        let result = process(
+            &mut context,
+            &[set_max_value(new_authority.pubkey(), 20)],
+            &[&new_authority],
+        )
+        .await;
+        assert_eq!(result.result.unwrap_err(), unauthorized);
+ 
+        let accept = authority_instruction(
+            program_id,
+            counter.pubkey(),
+            new_authority.pubkey(),
+            &CounterInstruction::AcceptAuthority.pack(),
+        );
+        let result = process(&mut context, &[accept], &[&new_authority]).await;
+        assert!(result.result.is_ok());
+        let counter_data = get_counter(&mut context, counter.pubkey()).await;
+        assert_eq!(counter_data.authority, new_authority.pubkey());
+        assert_eq!(counter_data.pending_authority, Pubkey::default());
+ 
+        let result = process(&mut context, &[set_max_value(old_authority, 30)], &[]).await;
+        assert_eq!(result.result.unwrap_err(), unauthorized);
+        let result = process(
+            &mut context,
+            &[set_max_value(new_authority.pubkey(), 40)],
+            &[&new_authority],
+        )
+        .await;
+        assert!(result.result.is_ok());
+        assert_eq!(
+            get_counter(&mut context, counter.pubkey()).await.max_value,
+            40
+        );
+    }
+ 
+    #[tokio::test]
+    async fn test_bulk_query_returns_counts_in_order() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let mut counters = Vec::new();
+        for initial_value in [7, 0, 42] {
+            counters.push(initialize_counter(&mut context, program_id, initial_value).await);
+        }
+ 
+        let bulk_query = CounterInstruction::BulkQuery.instruction(
+            &program_id,
+            counters
+                .iter()
+                .map(|counter| AccountMeta::new_readonly(counter.pubkey(), false))
+                .collect(),
+        );
+        let result = process(&mut context, &[bulk_query], &[]).await;
+        assert!(result.result.is_ok());
+ 
+        let return_data = result.metadata.unwrap().return_data.unwrap().data;
+        let counts: Vec<u64> = return_data
+            .chunks(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        assert_eq!(counts, vec![7, 0, 42]);
+    }
+ 
+    #[test]
+    fn test_load_instructions_sysvar_checks_the_key() {
+        let sysvar_key = solana_program::sysvar::instructions::id();
+        let wrong_key = Pubkey::new_unique();
+        let owner = Pubkey::default();
+        let (mut sysvar_lamports, mut wrong_lamports) = (0, 0);
+        let (mut sysvar_data, mut wrong_data) = (vec![], vec![]);
+        let sysvar_account = AccountInfo::new(
+            &sysvar_key,
+            false,
+            false,
+            &mut sysvar_lamports,
+            &mut sysvar_data,
+            &owner,
+            false,
+            0,
+        );
+        let wrong_account = AccountInfo::new(
+            &wrong_key,
+            false,
+            false,
+            &mut wrong_lamports,
+            &mut wrong_data,
+            &owner,
+            false,
+            0,
+        );
+ 
+        let accounts = [wrong_account.clone(), sysvar_account.clone()];
+        let loaded = crate::processor::load_instructions_sysvar(&accounts).unwrap();
+        assert_eq!(loaded.key, &sysvar_key);
+        assert_eq!(
+            crate::processor::load_instructions_sysvar(&[sysvar_account, wrong_account])
+                .unwrap_err(),
+            CounterError::InvalidInstructionsSysvar.into()
+        );
+        assert_eq!(
+            crate::processor::load_instructions_sysvar(&[]).unwrap_err(),
+            ProgramError::NotEnoughAccountKeys
+        );
+    }
+ 
+    #[tokio::test]
+    async fn test_checkpoint_appends_history_entries() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 0).await;
+        let (history, _) = HistoryEntry::find_address(&program_id, &counter.pubkey());
+        let checkpoint = CounterInstruction::Checkpoint.instruction(
+            &program_id,
+            vec![
+                AccountMeta::new_readonly(counter.pubkey(), false),
+                AccountMeta::new(history, false),
+                AccountMeta::new(context.payer.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+ 
+        for _ in 0..3 {
+            let increment = increment_instruction(program_id, counter.pubkey());
+            let result = process(&mut context, &[checkpoint.clone(), increment], &[]).await;
+            assert!(result.result.is_ok());
+        }
+ 
+        let data = context
+            .banks_client
+            .get_account(history)
+            .await
+            .unwrap()
+            .expect("history account not found")
+            .data;
+        let entries: Vec<HistoryEntry> = data
+            .chunks(HistoryEntry::LEN)
+            .map(|chunk| HistoryEntry::try_from_slice(chunk).unwrap())
+            .collect();
+        let counts: Vec<u64> = entries.iter().map(|entry| entry.count).collect();
+        assert_eq!(counts, vec![0, 1, 2]);
+        assert!(entries.windows(2).all(|pair| pair[0].slot <= pair[1].slot));
+    }
+ 
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn test_client_decoders_read_program_return_data() {
+        use crate::client::*;
+ 
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 5).await;
+        let readonly = || vec![AccountMeta::new_readonly(counter.pubkey(), false)];
+        let return_data = |result: BanksTransactionResultWithMetadata| {
+            assert!(result.result.is_ok());
+            result.metadata.unwrap().return_data.unwrap().data
+        };
+ 
+        let increment = increment_instruction(program_id, counter.pubkey());
+        let bytes = return_data(process(&mut context, &[increment], &[]).await);
+        let counter_data = get_counter(&mut context, counter.pubkey()).await;
+        assert_eq!(decode_increment_result(&bytes), Some(u64::MAX - 6));
+        assert_eq!(decode_saturation_flag(&bytes), None);
+        assert_eq!(
+            decode_state_hash(&bytes),
+            Some(hash(&borsh::to_vec(&counter_data).unwrap()))
+        );
+ 
+        let metrics = CounterInstruction::Metrics.instruction(&program_id, readonly());
+        let bytes = return_data(process(&mut context, &[metrics], &[]).await);
+        assert_eq!(decode_metrics(&bytes).unwrap().count, 6);
+ 
+        let describe = CounterInstruction::DescribeCounter.instruction(&program_id, readonly());
+        let bytes = return_data(process(&mut context, &[describe], &[]).await);
+        assert_eq!(decode_description(&bytes).unwrap().nonce, Some(1));
+ 
+        let query = CounterInstruction::QueryAccount.instruction(&program_id, readonly());
+        let bytes = return_data(process(&mut context, &[query], &[]).await);
+        let lamports = Rent::default().minimum_balance(CounterAccount::LEN);
+        assert_eq!(decode_query_account(&bytes), Some((6, lamports)));
+ 
+        let query_rent =
+            CounterInstruction::QueryRent { space: 8 }.instruction(&program_id, vec![]);
+        let bytes = return_data(process(&mut context, &[query_rent], &[]).await);
+        assert_eq!(
+            decode_rent(&bytes),
+            Some(Rent::default().minimum_balance(8))
+        );
+ 
+        let bulk_query = CounterInstruction::BulkQuery.instruction(&program_id, readonly());
+        let bytes = return_data(process(&mut context, &[bulk_query], &[]).await);
+        assert_eq!(decode_bulk_query(&bytes), Some(vec![6]));
+ 
+        let staleness = CounterInstruction::QueryStaleness.instruction(&program_id, readonly());
+        let bytes = return_data(process(&mut context, &[staleness], &[]).await);
+        assert_eq!(decode_staleness(&bytes), Some(0));
+ 
+        let check =
+            CounterInstruction::CheckIncrementBy { amount: 1 }.instruction(&program_id, readonly());
+        let bytes = return_data(process(&mut context, &[check], &[]).await);
+        assert_eq!(decode_would_overflow(&bytes), Some(false));
+ 
+        let slots = CounterInstruction::SlotsRemaining.instruction(&program_id, readonly());
+        let bytes = return_data(process(&mut context, &[slots], &[]).await);
+        assert_eq!(decode_slots_remaining(&bytes), Some(0));
+ 
+        // Truncated or misaligned bytes decode to nothing rather than garbage
+        assert_eq!(decode_increment_result(&[0; 8]), None);
+        assert_eq!(decode_rent(&[0; 7]), None);
+        assert_eq!(decode_bulk_query(&[0; 9]), None);
+        assert_eq!(decode_staleness(&[0; 9]), None);
+        assert_eq!(decode_state_hash(&[0; 31]), None);
+    }
+ 
+    // Token account data in the SPL Token layout, initialized
+    fn token_account_data(mint: &Pubkey, owner: &Pubkey, amount: u64) -> Vec<u8> {
+        let mut data = vec![0; TokenHolding::LEN];
+        data[..32].copy_from_slice(mint.as_ref());
+        data[32..64].copy_from_slice(owner.as_ref());
+        data[64..72].copy_from_slice(&amount.to_le_bytes());
+        data[108] = 1;
+        data
+    }
+ 
+    #[tokio::test]
+    async fn test_increment_if_holder_checks_the_token_account() {
+        let program_id = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let holder = Keypair::new();
+        let stranger = Keypair::new();
+        let (rich_account, poor_account) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut program_test = program_test(program_id);
+        for (address, amount) in [(rich_account, 10), (poor_account, 4)] {
+            program_test.add_account(
+                address,
+                Account {
+                    lamports: Rent::default().minimum_balance(TokenHolding::LEN),
+                    data: token_account_data(&mint, &holder.pubkey(), amount),
+                    owner: TokenHolding::PROGRAM_ID,
+                    ..Account::default()
+                },
+            );
+        }
+        let mut context = program_test.start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 0).await;
+        let set_gate = authority_instruction(
+            program_id,
+            counter.pubkey(),
+            context.payer.pubkey(),
+            &CounterInstruction::SetTokenGate {
+                gate_mint: mint,
+                min_amount: 5,
+            }
+            .pack(),
+        );
+        let result = process(&mut context, &[set_gate], &[]).await;
+        assert!(result.result.is_ok());
+ 
+        let increment = |token_account, signer: &Keypair| {
+            CounterInstruction::IncrementIfHolder.instruction(
+                &program_id,
+                vec![
+                    AccountMeta::new(counter.pubkey(), false),
+                    AccountMeta::new_readonly(token_account, false),
+                    AccountMeta::new_readonly(signer.pubkey(), true),
+                ],
+            )
+        };
+        let not_holder = TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(CounterError::NotTokenHolder as u32),
+        );
+ 
+        let result = process(
+            &mut context,
+            &[increment(rich_account, &holder)],
+            &[&holder],
+        )
+        .await;
+        assert!(result.result.is_ok());
+        let result = process(
+            &mut context,
+            &[increment(rich_account, &stranger)],
+            &[&stranger],
+        )
+        .await;
+        assert_eq!(result.result.unwrap_err(), not_holder);
+        let result = process(
+            &mut context,
+            &[increment(poor_account, &holder)],
+            &[&holder],
+        )
+        .await;
+        assert_eq!(result.result.unwrap_err(), not_holder);
+        assert_eq!(get_counter(&mut context, counter.pubkey()).await.count, 1);
+    }
+ 
+    // Deterministic xorshift byte stream, so fuzz inputs repeat on every run
+    fn fuzz_bytes(seed: u64, len: usize) -> Vec<u8> {
+        let mut state = seed | 1;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+ 
+    // Seed corpus for the account deserializer: empty, short, legacy, exact
+    // with and without the discriminator, exact but at its limits, and oversized
+    fn fuzz_corpus() -> Vec<Vec<u8>> {
+        let mut with_discriminator = fuzz_bytes(3, CounterAccount::LEN);
+        with_discriminator[..8].copy_from_slice(&CounterAccount::DISCRIMINATOR);
+        let at_limits = CounterAccount {
+            count: u64::MAX,
+            step: u64::MAX,
+            cooldown_secs: u32::MAX,
+            last_updated: i64::MIN,
+            ..CounterAccount::default()
+        };
+        let mut oversized = borsh::to_vec(&CounterAccount::default()).unwrap();
+        oversized.extend_from_slice(&fuzz_bytes(4, 64));
+        vec![
+            vec![],
+            fuzz_bytes(1, 5),
+            fuzz_bytes(2, CounterAccount::LEGACY_LEN),
+            fuzz_bytes(5, CounterAccount::LEN),
+            with_discriminator,
+            borsh::to_vec(&at_limits).unwrap(),
+            oversized,
+        ]
+    }
+ 
+    #[test]
+    fn test_fuzz_account_deserializer_yields_typed_errors() {
+        let random = (0..2_000u64).map(|seed| {
+            let len = seed as usize % (CounterAccount::LEN * 2);
+            let mut data = fuzz_bytes(seed, len);
+            // Half the inputs get past the discriminator check
+            if seed % 2 == 0 && len >= 8 {
+                data[..8].copy_from_slice(&CounterAccount::DISCRIMINATOR);
+            }
+            data
+        });
+        for data in fuzz_corpus().into_iter().chain(random) {
+            match CounterAccount::unpack(&data) {
+                Ok(counter_data) if data.len() == CounterAccount::LEN => {
+                    assert_eq!(borsh::to_vec(&counter_data).unwrap(), data);
+                }
+                Ok(_) => assert_eq!(data.len(), CounterAccount::LEGACY_LEN),
+                Err(error) => assert_eq!(error, ProgramError::InvalidAccountData),
+            }
+        }
+    }
+ 
+    #[tokio::test]
+    async fn test_fuzz_corpus_through_increment_fails_cleanly() {
+        let program_id = Pubkey::new_unique();
+        let mut program_test = program_test(program_id);
+        let corpus = fuzz_corpus();
+        let addresses: Vec<Pubkey> = corpus.iter().map(|_| Pubkey::new_unique()).collect();
+        for (address, data) in addresses.iter().zip(&corpus) {
+            program_test.add_account(
+                *address,
+                Account {
+                    lamports: Rent::default().minimum_balance(data.len()),
+                    data: data.clone(),
+                    owner: program_id,
+                    ..Account::default()
+                },
+            );
+        }
+        let mut context = program_test.start_with_context().await;
+ 
+        // Every input either increments or fails with a typed instruction error;
+        // a panic in the handler would abort the test instead
+        for (address, data) in addresses.iter().zip(&corpus) {
+            let result = process(
+                &mut context,
+                &[increment_instruction(program_id, *address)],
+                &[],
+            )
+            .await;
+            match (CounterAccount::unpack(data), result.result) {
+                (Ok(_), Ok(())) => {}
+                // Decodable data may still be refused, e.g. a legacy account
+                // too small to store the current layout
+                (Ok(_), Err(TransactionError::InstructionError(0, error))) => {
+                    if let InstructionError::Custom(code) = error {
+                        assert!(CounterError::try_from(code).is_ok());
+                    }
+                }
+                (Err(_), Err(error)) => assert_eq!(
+                    error,
+                    TransactionError::InstructionError(0, InstructionError::InvalidAccountData)
+                ),
+                (decoded, result) => panic!("unexpected {decoded:?} -> {result:?}"),
+            }
+        }
+    }
+ 
+    #[tokio::test]
+    async fn test_stamp_slot_records_current_slot() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 0).await;
+        context.warp_to_slot(50).unwrap();
+ 
+        let stamp = CounterInstruction::StampSlot
+            .instruction(&program_id, vec![AccountMeta::new(counter.pubkey(), false)]);
+        let result = process(&mut context, &[stamp], &[]).await;
+        assert!(result.result.is_ok());
+ 
+        let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+        assert!(clock.slot >= 50);
+        assert_eq!(
+            get_counter(&mut context, counter.pubkey()).await.count,
+            clock.slot
+        );
+    }
+ 
+    #[tokio::test]
+    async fn test_emit_events_toggle() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let payer = context.payer.pubkey();
+        let counter = Keypair::new();
+        let initialize = CounterInstruction::InitializeCounterFull {
+            initial_value: 0,
+            step: 1,
+            max_value: 100,
+            require_signer: false,
+            decimals: 0,
+            min_value: 0,
+            category: 0,
+            emit_events: false,
+        }
+        .instruction(
+            &program_id,
+            vec![
+                AccountMeta::new(counter.pubkey(), true),
+                AccountMeta::new(payer, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let result = process(&mut context, &[initialize], &[&counter]).await;
+        assert!(result.result.is_ok());
+        assert!(
+            !get_counter(&mut context, counter.pubkey())
+                .await
+                .emit_events
+        );
+ 
+        // With events off an increment leaves no program-data line behind
+        let increment = increment_instruction(program_id, counter.pubkey());
+        let result = process(&mut context, std::slice::from_ref(&increment), &[]).await;
+        assert!(result.result.is_ok());
+        let logs = result.metadata.unwrap().log_messages;
+        assert!(!logs.iter().any(|log| log.starts_with("Program data: ")));
+ 
+        // Only the authority may switch them back on. Builtins under
+        // program-test print sol_log_data to stdout rather than the
+        // transaction logs, so the flag itself is what is checked here
+        let stranger = Keypair::new();
+        let data = CounterInstruction::SetEmitEvents { emit_events: true }.pack();
+        let set_by_stranger =
+            authority_instruction(program_id, counter.pubkey(), stranger.pubkey(), &data);
+        let result = process(&mut context, &[set_by_stranger], &[&stranger]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Unauthorized as u32)
+            )
+        );
+        let set = authority_instruction(program_id, counter.pubkey(), payer, &data);
+        assert!(process(&mut context, &[set], &[]).await.result.is_ok());
+        let counter_data = get_counter(&mut context, counter.pubkey()).await;
+        assert!(counter_data.emit_events);
+        assert!(process(&mut context, &[increment], &[])
+            .await
+            .result
+            .is_ok());
+        assert_eq!(get_counter(&mut context, counter.pubkey()).await.count, 2);
+    }
+ 
+    #[tokio::test]
+    async fn test_decrement_with_borrow_draws_shortfall_from_second_counter() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let first = initialize_counter(&mut context, program_id, 3).await;
+        let second = initialize_counter(&mut context, program_id, 10).await;
+        let payer = context.payer.pubkey();
+        let borrow = |amount| {
+            CounterInstruction::DecrementWithBorrow { amount }.instruction(
+                &program_id,
+                vec![
+                    AccountMeta::new(first.pubkey(), false),
+                    AccountMeta::new(second.pubkey(), false),
+                    AccountMeta::new_readonly(payer, true),
+                ],
+            )
+        };
+ 
+        let result = process(&mut context, &[borrow(5)], &[]).await;
+        assert!(result.result.is_ok());
+        assert_eq!(get_counter(&mut context, first.pubkey()).await.count, 0);
+        assert_eq!(get_counter(&mut context, second.pubkey()).await.count, 8);
+ 
+        // Neither counter moves when both together cannot cover the amount
+        let result = process(&mut context, &[borrow(9)], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::InsufficientAcrossBuckets as u32)
+            )
+        );
+        assert_eq!(get_counter(&mut context, first.pubkey()).await.count, 0);
+        assert_eq!(get_counter(&mut context, second.pubkey()).await.count, 8);
+    }
+ 
+    #[tokio::test]
+    async fn test_copy_config_from_template() {
+        let program_id = Pubkey::new_unique();
+        let template = Pubkey::new_unique();
+        let mut program_test = program_test(program_id);
+        let template_data = CounterAccount {
+            count: 40,
+            step: 5,
+            max_value: 500,
+            min_value: 2,
+            cooldown_secs: 30,
+            max_step_per_call: 50,
+            decimals: 2,
+            auto_freeze_at_max: true,
+            saturating: true,
+            allow_zero: false,
+            emit_events: false,
+            ..CounterAccount::new(40, Pubkey::new_unique(), 0)
+        };
+        program_test.add_account(
+            template,
+            Account {
+                lamports: 1_000_000_000,
+                data: borsh::to_vec(&template_data).unwrap(),
+                owner: program_id,
+                ..Account::default()
+            },
+        );
+        let mut context = program_test.start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 7).await;
+        let payer = context.payer.pubkey();
+ 
+        let copy = CounterInstruction::CopyConfigFrom.instruction(
+            &program_id,
+            vec![
+                AccountMeta::new(counter.pubkey(), false),
+                AccountMeta::new_readonly(payer, true),
+                AccountMeta::new_readonly(template, false),
+            ],
+        );
+        assert!(process(&mut context, &[copy], &[]).await.result.is_ok());
+ 
+        let copied = get_counter(&mut context, counter.pubkey()).await;
+        assert_eq!(copied.count, 7);
+        assert_eq!(copied.authority, payer);
+        assert_eq!(copied.step, template_data.step);
+        assert_eq!(copied.max_value, template_data.max_value);
+        assert_eq!(copied.min_value, template_data.min_value);
+        assert_eq!(copied.cooldown_secs, template_data.cooldown_secs);
+        assert_eq!(copied.max_step_per_call, template_data.max_step_per_call);
+        assert_eq!(copied.decimals, template_data.decimals);
+        assert_eq!(copied.require_signer, template_data.require_signer);
+        assert_eq!(copied.auto_freeze_at_max, template_data.auto_freeze_at_max);
+        assert_eq!(copied.saturating, template_data.saturating);
+        assert_eq!(copied.allow_zero, template_data.allow_zero);
+        assert_eq!(copied.emit_events, template_data.emit_events);
+ 
+        // A template whose bounds exclude the target's count is refused
+        let low_counter = initialize_counter(&mut context, program_id, 1).await;
+        let copy = CounterInstruction::CopyConfigFrom.instruction(
+            &program_id,
+            vec![
+                AccountMeta::new(low_counter.pubkey(), false),
+                AccountMeta::new_readonly(payer, true),
+                AccountMeta::new_readonly(template, false),
+            ],
+        );
+        assert_eq!(
+            process(&mut context, &[copy], &[])
+                .await
+                .result
+                .unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::BelowMinValue as u32)
+            )
+        );
+    }
+ 
+    #[tokio::test]
+    async fn test_initialize_counter_from_decimal_string() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let payer = context.payer.pubkey();
+        let initialize = |counter: Pubkey, value: &str| {
+            CounterInstruction::InitializeCounterStr {
+                value: value.to_string(),
+            }
+            .instruction(
+                &program_id,
+                vec![
+                    AccountMeta::new(counter, true),
+                    AccountMeta::new(payer, true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                ],
+            )
+        };
+ 
+        let counter = Keypair::new();
+        let result = process(
+            &mut context,
+            &[initialize(counter.pubkey(), "12345")],
+            &[&counter],
+        )
+        .await;
+        assert!(result.result.is_ok());
+        assert_eq!(
+            get_counter(&mut context, counter.pubkey()).await.count,
+            12345
+        );
+ 
+        // Non-numeric text and values past u64::MAX are both rejected
+        for value in ["12a", "18446744073709551616"] {
+            let counter = Keypair::new();
+            let result = process(
+                &mut context,
+                &[initialize(counter.pubkey(), value)],
+                &[&counter],
+            )
+            .await;
+            assert_eq!(
+                result.result.unwrap_err(),
+                TransactionError::InstructionError(0, InstructionError::InvalidInstructionData)
+            );
+        }
+    }
+ 
+    #[tokio::test]
+    async fn test_batch_increment_rejects_duplicate_counters() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let first = initialize_counter(&mut context, program_id, 1).await;
+        let second = initialize_counter(&mut context, program_id, 5).await;
+        let batch = |counters: &[Pubkey]| {
+            CounterInstruction::BatchIncrement.instruction(
+                &program_id,
+                counters
+                    .iter()
+                    .map(|counter| AccountMeta::new(*counter, false))
+                    .collect(),
+            )
+        };
+ 
+        let result = process(
+            &mut context,
+            &[batch(&[first.pubkey(), second.pubkey()])],
+            &[],
+        )
+        .await;
+        assert!(result.result.is_ok());
+        assert_eq!(get_counter(&mut context, first.pubkey()).await.count, 2);
+        assert_eq!(get_counter(&mut context, second.pubkey()).await.count, 6);
+ 
+        // Listing a counter twice fails outright instead of incrementing it twice
+        let duplicated = batch(&[first.pubkey(), second.pubkey(), first.pubkey()]);
+        let result = process(&mut context, &[duplicated], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::DuplicateAccountInBatch as u32)
+            )
+        );
+        assert_eq!(get_counter(&mut context, first.pubkey()).await.count, 2);
+        assert_eq!(get_counter(&mut context, second.pubkey()).await.count, 6);
+    }
+ 
+    #[tokio::test]
+    async fn test_initialize_counter_shared_splits_rent() {
+        let program_id = Pubkey::new_unique();
+        let first_payer = Keypair::new();
+        let second_payer = Keypair::new();
+        let mut program_test = program_test(program_id);
+        for payer in [&first_payer, &second_payer] {
+            program_test.add_account(
+                payer.pubkey(),
+                Account {
+                    lamports: 1_000_000_000,
+                    ..Account::default()
+                },
+            );
+        }
+        let mut context = program_test.start_with_context().await;
+        let rent = context.banks_client.get_rent().await.unwrap();
+        let required = rent.minimum_balance(CounterAccount::LEN);
+ 
+        for first_share_bps in [5_000, 7_500] {
+            let counter = Keypair::new();
+            let before = [
+                context
+                    .banks_client
+                    .get_balance(first_payer.pubkey())
+                    .await
+                    .unwrap(),
+                context
+                    .banks_client
+                    .get_balance(second_payer.pubkey())
+                    .await
+                    .unwrap(),
+            ];
+            let initialize = CounterInstruction::InitializeCounterShared {
+                initial_value: 4,
+                first_share_bps,
+            }
+            .instruction(
+                &program_id,
+                vec![
+                    AccountMeta::new(counter.pubkey(), true),
+                    AccountMeta::new(first_payer.pubkey(), true),
+                    AccountMeta::new(second_payer.pubkey(), true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                ],
+            );
+            let result = process(
+                &mut context,
+                &[initialize],
+                &[&counter, &first_payer, &second_payer],
+            )
+            .await;
+            assert!(result.result.is_ok());
+ 
+            // The context payer covers the fee, so each payer loses only its share
+            let second_share = required * (10_000 - u64::from(first_share_bps)) / 10_000;
+            let after = [
+                context
+                    .banks_client
+                    .get_balance(first_payer.pubkey())
+                    .await
+                    .unwrap(),
+                context
+                    .banks_client
+                    .get_balance(second_payer.pubkey())
+                    .await
+                    .unwrap(),
+            ];
+            assert_eq!(before[0] - after[0], required - second_share);
+            assert_eq!(before[1] - after[1], second_share);
+            assert_eq!(
+                context
+                    .banks_client
+                    .get_balance(counter.pubkey())
+                    .await
+                    .unwrap(),
+                required
+            );
+            let counter_data = get_counter(&mut context, counter.pubkey()).await;
+            assert_eq!(counter_data.count, 4);
+            assert_eq!(counter_data.authority, first_payer.pubkey());
+        }
+    }
+ 
+    #[tokio::test]
+    async fn test_query_staleness_tracks_clock() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 0).await;
+        let increment = increment_instruction(program_id, counter.pubkey());
+        assert!(process(&mut context, &[increment], &[])
+            .await
+            .result
+            .is_ok());
+        let query = CounterInstruction::QueryStaleness.instruction(
+            &program_id,
+            vec![AccountMeta::new_readonly(counter.pubkey(), false)],
+        );
+ 
+        advance_clock(&mut context, 120).await;
+        let result = process(&mut context, std::slice::from_ref(&query), &[]).await;
+        assert!(result.result.is_ok());
+        let return_data = result.metadata.unwrap().return_data.unwrap();
+        assert_eq!(return_data.data, 120i64.to_le_bytes());
+ 
+        // A clock behind last_updated reads as fresh, not negative
+        advance_clock(&mut context, -500).await;
+        let result = process(&mut context, &[query], &[]).await;
+        let return_data = result.metadata.unwrap().return_data.unwrap();
+        assert_eq!(return_data.data, 0i64.to_le_bytes());
+    }
+ 
+    #[tokio::test]
+    async fn test_initialize_config_requires_upgrade_authority() {
+        let program_id = Pubkey::new_unique();
+        let upgrade_authority = Keypair::new();
+        let mut program_test = program_test(program_id);
+        add_program_data(&mut program_test, program_id, upgrade_authority.pubkey());
+        program_test.add_account(
+            upgrade_authority.pubkey(),
+            Account {
+                lamports: 1_000_000_000,
+                ..Account::default()
+            },
+        );
+        let mut context = program_test.start_with_context().await;
+        let admin = Pubkey::new_unique();
+        let initialize_config = |signer: Pubkey| {
+            let mut instruction = set_global_pause_instruction(program_id, signer, false);
+            instruction.data = CounterInstruction::InitializeConfig { admin }.pack();
+            instruction
+        };
+ 
+        // An imposter cannot claim the config, however well funded
+        let imposter = context.payer.pubkey();
+        let result = process(&mut context, &[initialize_config(imposter)], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Unauthorized as u32)
+            )
+        );
+ 
+        let instruction = initialize_config(upgrade_authority.pubkey());
+        let result = process(&mut context, &[instruction], &[&upgrade_authority]).await;
+        assert!(result.result.is_ok());
+        let (config_address, bump) = GlobalConfig::find_address(&program_id);
+        let data = context
+            .banks_client
+            .get_account(config_address)
+            .await
+            .unwrap()
+            .expect("config account not found")
+            .data;
+        let config = GlobalConfig::try_from_slice(&data).unwrap();
+        assert_eq!(config.admin, admin);
+        assert_eq!(config.bump, bump);
+        assert!(!config.paused);
+ 
+        // Once created it cannot be initialized again, and pausing keeps the admin
+        let instruction = initialize_config(upgrade_authority.pubkey());
+        let result = process(&mut context, &[instruction], &[&upgrade_authority]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::AccountAlreadyInitialized)
+        );
+        let pause = set_global_pause_instruction(program_id, upgrade_authority.pubkey(), true);
+        assert!(process(&mut context, &[pause], &[&upgrade_authority])
+            .await
+            .result
+            .is_ok());
+        let data = context
+            .banks_client
+            .get_account(config_address)
+            .await
+            .unwrap()
+            .unwrap()
+            .data;
+        let config = GlobalConfig::try_from_slice(&data).unwrap();
+        assert!(config.paused);
+        assert_eq!(config.admin, admin);
+    }
+ 
+    #[tokio::test]
+    async fn test_decrement_daily_enforces_limit_per_window() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 100).await;
+        let payer = context.payer.pubkey();
+        let data = CounterInstruction::SetDailyLimit { daily_limit: 10 }.pack();
+        let set_limit = authority_instruction(program_id, counter.pubkey(), payer, &data);
+        assert!(process(&mut context, &[set_limit], &[])
+            .await
+            .result
+            .is_ok());
+        let decrement = |amount| {
+            CounterInstruction::DecrementDaily { amount }
+                .instruction(&program_id, vec![AccountMeta::new(counter.pubkey(), false)])
+        };
+ 
+        // Spend right up to the limit, then one more is refused
+        for amount in [6, 4] {
+            let result = process(&mut context, &[decrement(amount)], &[]).await;
+            assert!(result.result.is_ok());
+        }
+        let result = process(&mut context, &[decrement(1)], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::DailyLimitExceeded as u32)
+            )
+        );
+        assert_eq!(get_counter(&mut context, counter.pubkey()).await.count, 90);
+ 
+        // A day later the window has reset
+        advance_clock(&mut context, CounterAccount::DAY_SECS).await;
+        let result = process(&mut context, &[decrement(10)], &[]).await;
+        assert!(result.result.is_ok());
+        let counter_data = get_counter(&mut context, counter.pubkey()).await;
+        assert_eq!(counter_data.count, 80);
+        assert_eq!(counter_data.daily_spent, 10);
+    }
+ 
+    #[tokio::test]
+    async fn test_apply_delta_moves_both_ways() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 10).await;
+        let apply = |delta| {
+            CounterInstruction::ApplyDelta { delta }
+                .instruction(&program_id, vec![AccountMeta::new(counter.pubkey(), false)])
+        };
+ 
+        for delta in [5, -3] {
+            let result = process(&mut context, &[apply(delta)], &[]).await;
+            assert!(result.result.is_ok());
+        }
+        assert_eq!(get_counter(&mut context, counter.pubkey()).await.count, 12);
+ 
+        let result = process(&mut context, &[apply(-13)], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Underflow as u32)
+            )
+        );
+    }
+ 
+    #[tokio::test]
+    async fn test_check_increment_by_reports_overflow_without_mutating() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, u64::MAX - 5).await;
+        let before = get_counter(&mut context, counter.pubkey()).await;
+        let check = |amount| {
+            CounterInstruction::CheckIncrementBy { amount }.instruction(
+                &program_id,
+                vec![AccountMeta::new_readonly(counter.pubkey(), false)],
+            )
+        };
+ 
+        for (amount, would_overflow) in [(5, false), (6, true)] {
+            let result = process(&mut context, &[check(amount)], &[]).await;
+            assert!(result.result.is_ok());
+            let return_data = result.metadata.unwrap().return_data.unwrap();
+            assert_eq!(return_data.data, vec![u8::from(would_overflow)]);
+        }
+        assert_eq!(get_counter(&mut context, counter.pubkey()).await, before);
+ 
+        // max_value counts as a bound too, well short of u64::MAX
+        let payer = context.payer.pubkey();
+        let bounded = initialize_counter(&mut context, program_id, 8).await;
+        let data = CounterInstruction::SetMaxValue { max_value: 10 }.pack();
+        let set_max = authority_instruction(program_id, bounded.pubkey(), payer, &data);
+        assert!(process(&mut context, &[set_max], &[]).await.result.is_ok());
+        let check = CounterInstruction::CheckIncrementBy { amount: 3 }.instruction(
+            &program_id,
+            vec![AccountMeta::new_readonly(bounded.pubkey(), false)],
+        );
+        let result = process(&mut context, &[check], &[]).await;
+        assert_eq!(result.metadata.unwrap().return_data.unwrap().data, vec![1]);
+    }
+ 
+    #[tokio::test]
+    async fn test_lock_until_blocks_updates_until_unlock_ts() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 0).await;
+        let payer = context.payer.pubkey();
+        let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+        let data = CounterInstruction::LockUntil {
+            unlock_ts: clock.unix_timestamp + 100,
+        }
+        .pack();
+        let lock = authority_instruction(program_id, counter.pubkey(), payer, &data);
+        assert!(process(&mut context, &[lock], &[]).await.result.is_ok());
+ 
+        let increment = increment_instruction(program_id, counter.pubkey());
+        let result = process(&mut context, std::slice::from_ref(&increment), &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::TimeLocked as u32)
+            )
+        );
+ 
+        advance_clock(&mut context, 100).await;
+        let result = process(&mut context, &[increment], &[]).await;
+        assert!(result.result.is_ok());
+        assert_eq!(get_counter(&mut context, counter.pubkey()).await.count, 1);
+    }
+ 
+    #[tokio::test]
+    async fn test_wrong_owner_is_logged() {
+        let program_id = Pubkey::new_unique();
+        let other_program = Pubkey::new_unique();
+        let counter = Pubkey::new_unique();
+        let mut program_test = program_test(program_id);
+        program_test.add_account(
+            counter,
+            Account {
+                lamports: 1_000_000_000,
+                data: borsh::to_vec(&CounterAccount::default()).unwrap(),
+                owner: other_program,
+                ..Account::default()
+            },
+        );
+        let mut context = program_test.start_with_context().await;
+ 
+        let increment = increment_instruction(program_id, counter);
+        let result = process(&mut context, &[increment], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::IncorrectProgramId)
+        );
+        let expected = format!(
+            "Program log: Account {} is owned by {}, expected {}",
+            counter, other_program, program_id
+        );
+        assert!(result.metadata.unwrap().log_messages.contains(&expected));
+    }
+ 
+    #[tokio::test]
+    async fn test_slots_remaining_counts_down_to_expiry() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 0).await;
+        let payer = context.payer.pubkey();
+        let data = CounterInstruction::SetExpiry {
+            expires_at_slot: 200,
+        }
+        .pack();
+        let set_expiry = authority_instruction(program_id, counter.pubkey(), payer, &data);
+        assert!(process(&mut context, &[set_expiry], &[])
+            .await
+            .result
+            .is_ok());
+        let query = CounterInstruction::SlotsRemaining.instruction(
+            &program_id,
+            vec![AccountMeta::new_readonly(counter.pubkey(), false)],
+        );
+ 
+        // Past the expiry slot the countdown rests at zero
+        for slot in [50, 250] {
+            context.warp_to_slot(slot).unwrap();
+            let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+            let expected = 200u64.saturating_sub(clock.slot);
+            let result = process(&mut context, std::slice::from_ref(&query), &[]).await;
+            let return_data = result.metadata.unwrap().return_data.unwrap();
+            assert_eq!(return_data.data, expected.to_le_bytes());
+        }
+    }
+ 
+    #[tokio::test]
+    async fn test_sweep_closed_reclaims_only_empty_counters() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let mut counters = Vec::new();
+        for initial_value in [0, 5, 0] {
+            counters.push(initialize_counter(&mut context, program_id, initial_value).await);
+        }
+        let destination = Pubkey::new_unique();
+        let rent = context
+            .banks_client
+            .get_balance(counters[0].pubkey())
+            .await
+            .unwrap();
+ 
+        let mut accounts = vec![
+            AccountMeta::new_readonly(context.payer.pubkey(), true),
+            AccountMeta::new(destination, false),
+        ];
+        accounts.extend(
+            counters
+                .iter()
+                .map(|counter| AccountMeta::new(counter.pubkey(), false)),
+        );
+        let sweep = CounterInstruction::SweepClosed.instruction(&program_id, accounts);
+        assert!(process(&mut context, &[sweep], &[]).await.result.is_ok());
+ 
+        for (counter, closed) in counters.iter().zip([true, false, true]) {
+            let account = context
+                .banks_client
+                .get_account(counter.pubkey())
+                .await
+                .unwrap();
+            assert_eq!(account.is_none(), closed);
+        }
+        assert_eq!(
+            get_counter(&mut context, counters[1].pubkey()).await.count,
+            5
+        );
+        assert_eq!(
+            context.banks_client.get_balance(destination).await.unwrap(),
+            2 * rent
+        );
+    }
+ 
+    #[tokio::test]
+    async fn test_increment_with_tip_moves_lamports() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 0).await;
+        let payer = context.payer.pubkey();
+        let tip = |lamports| {
+            CounterInstruction::IncrementWithTip { lamports }.instruction(
+                &program_id,
+                vec![
+                    AccountMeta::new(counter.pubkey(), false),
+                    AccountMeta::new(payer, true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                ],
+            )
+        };
+        let balance_before = context
+            .banks_client
+            .get_balance(counter.pubkey())
+            .await
+            .unwrap();
+ 
+        // A zero tip only increments
+        for lamports in [1_000, 0] {
+            let result = process(&mut context, &[tip(lamports)], &[]).await;
+            assert!(result.result.is_ok());
+        }
+        let counter_data = get_counter(&mut context, counter.pubkey()).await;
+        assert_eq!(counter_data.count, 2);
+        assert_eq!(counter_data.total_tips, 1_000);
+        assert_eq!(
+            context
+                .banks_client
+                .get_balance(counter.pubkey())
+                .await
+                .unwrap(),
+            balance_before + 1_000
+        );
+    }
+ 
+    #[tokio::test]
+    async fn test_increment_fresh_rejects_stale_gaps() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 0).await;
+        let fresh = CounterInstruction::IncrementFresh { max_age_slots: 5 }
+            .instruction(&program_id, vec![AccountMeta::new(counter.pubkey(), false)]);
+ 
+        // The first call always lands; the next one 4 slots later is in time
+        for slot in [10, 14] {
+            context.warp_to_slot(slot).unwrap();
+            let result = process(&mut context, std::slice::from_ref(&fresh), &[]).await;
+            assert!(result.result.is_ok());
+        }
+        let counter_data = get_counter(&mut context, counter.pubkey()).await;
+        assert_eq!(counter_data.count, 2);
+        assert_eq!(counter_data.last_increment_slot, 14);
+ 
+        context.warp_to_slot(30).unwrap();
+        let result = process(&mut context, &[fresh], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::IncrementStale as u32)
+            )
+        );
+    }
+ 
+    #[tokio::test]
+    async fn test_counter_metadata_set_update_remove() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 0).await;
+        let (meta, _) = CounterMetadata::find_address(&program_id, &counter.pubkey());
+        let payer = context.payer.pubkey();
+        let accounts = || {
+            vec![
+                AccountMeta::new_readonly(counter.pubkey(), false),
+                AccountMeta::new(meta, false),
+                AccountMeta::new(payer, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ]
+        };
+        let set = |key: &str, value: &str| {
+            CounterInstruction::SetMeta {
+                key: key.to_string(),
+                value: value.to_string(),
+            }
+            .instruction(&program_id, accounts())
+        };
+        let remove = |key: &str| {
+            CounterInstruction::RemoveMeta {
+                key: key.to_string(),
+            }
+            .instruction(&program_id, accounts())
+        };
+ 
+        // Removing a key that was never set succeeds without effect
+        let instructions = [
+            set("name", "visits"),
+            set("unit", "clicks"),
+            set("name", "page views"),
+            remove("unit"),
+            remove("missing"),
+        ];
+        for instruction in instructions {
+            let result = process(&mut context, &[instruction], &[]).await;
+            assert!(result.result.is_ok());
+        }
+        let account = context
+            .banks_client
+            .get_account(meta)
+            .await
+            .unwrap()
+            .expect("metadata account not found");
+        let metadata = CounterMetadata::try_from_slice(&account.data).unwrap();
+        assert_eq!(
+            metadata.entries,
+            vec![("name".to_string(), "page views".to_string())]
+        );
+        assert_eq!(account.data.len(), borsh::to_vec(&metadata).unwrap().len());
+ 
+        let oversized = set("blob", &"x".repeat(CounterMetadata::MAX_LEN));
+        let result = process(&mut context, &[oversized], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::MetadataTooLarge as u32)
+            )
+        );
+    }
+ 
+    #[tokio::test]
+    async fn test_set_counter_returns_previous_value() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 7).await;
+        let payer = context.payer.pubkey();
+        let data = CounterInstruction::SetCounter { value: 99 }.pack();
+        let set = authority_instruction(program_id, counter.pubkey(), payer, &data);
+ 
+        let result = process(&mut context, &[set], &[]).await;
+        assert!(result.result.is_ok());
+        let return_data = result.metadata.unwrap().return_data.unwrap();
+        assert_eq!(return_data.data[..8], 7u64.to_le_bytes());
+        let counter_data = get_counter(&mut context, counter.pubkey()).await;
+        assert_eq!(counter_data.count, 99);
+        assert_eq!(
+            return_data.data[8..],
+            hash(&borsh::to_vec(&counter_data).unwrap()).to_bytes()
+        );
+    }
+ 
+    #[tokio::test]
+    async fn test_batch_increment_rejects_oversized_batches() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 0).await;
+ 
+        // 33 distinct keys would not fit in a legacy transaction, and the size
+        // check runs before the duplicate check, so one counter repeated will do
+        let batch = CounterInstruction::BatchIncrement.instruction(
+            &program_id,
+            vec![AccountMeta::new(counter.pubkey(), false); MAX_BATCH + 1],
+        );
+        let result = process(&mut context, &[batch], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::BatchTooLarge as u32)
+            )
+        );
+        assert_eq!(get_counter(&mut context, counter.pubkey()).await.count, 0);
+    }
+ 
+    #[tokio::test]
+    async fn test_initialize_mint_counter_at_derived_address() {
+        let program_id = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let not_a_mint = Pubkey::new_unique();
+        let mut program_test = program_test(program_id);
+        for (address, owner) in [(mint, TokenHolding::PROGRAM_ID), (not_a_mint, program_id)] {
+            program_test.add_account(
+                address,
+                Account {
+                    lamports: 1_000_000_000,
+                    data: vec![0; TokenHolding::MINT_LEN],
+                    owner,
+                    ..Account::default()
+                },
+            );
+        }
+        let mut context = program_test.start_with_context().await;
+        let payer = context.payer.pubkey();
+        let initialize = |mint: Pubkey| {
+            let (counter, _) = CounterAccount::find_mint_pda(&program_id, &mint);
+            CounterInstruction::InitializeMintCounter { initial_value: 3 }.instruction(
+                &program_id,
+                vec![
+                    AccountMeta::new(counter, false),
+                    AccountMeta::new_readonly(mint, false),
+                    AccountMeta::new(payer, true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                ],
+            )
+        };
+ 
+        let result = process(&mut context, &[initialize(not_a_mint)], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::IncorrectProgramId)
+        );
+ 
+        let result = process(&mut context, &[initialize(mint)], &[]).await;
+        assert!(result.result.is_ok());
+        let (counter, bump) =
+            Pubkey::find_program_address(&[b"mint_counter".as_ref(), mint.as_ref()], &program_id);
+        let counter_data = get_counter(&mut context, counter).await;
+        assert_eq!(counter_data.count, 3);
+        assert_eq!(counter_data.bump, bump);
+        assert_eq!(counter_data.authority, payer);
+    }
+ 
+    #[tokio::test]
+    async fn test_bulk_query_rejects_oversized_return_data() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 0).await;
+ 
+        // One counter past the 1024-byte limit, repeated so the transaction fits
+        let bulk_query = CounterInstruction::BulkQuery.instruction(
+            &program_id,
+            vec![
+                AccountMeta::new_readonly(counter.pubkey(), false);
+                MAX_RETURN_DATA / RETURN_U64_LEN + 1
+            ],
+        );
+        let result = process(&mut context, &[bulk_query], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::ReturnDataTooLarge as u32)
+            )
+        );
+    }
+ 
+    #[tokio::test]
+    async fn test_refresh_bump_restores_canonical_bump() {
+        let program_id = Pubkey::new_unique();
+        let authority = Keypair::new();
+        let (counter, bump) = CounterAccount::find_pda(&program_id, &authority.pubkey());
+        // A PDA whose authority has moved on from the one it was seeded by, and
+        // a mint counter, seeded by its mint
+        let original_authority = Pubkey::new_unique();
+        let (transferred, transferred_bump) =
+            CounterAccount::find_pda(&program_id, &original_authority);
+        let mint = Pubkey::new_unique();
+        let (mint_counter, mint_bump) = CounterAccount::find_mint_pda(&program_id, &mint);
+        let mut program_test = program_test(program_id);
+        // PDA counters stored with a bump that does not derive their address
+        for (address, canonical_bump) in [
+            (counter, bump),
+            (transferred, transferred_bump),
+            (mint_counter, mint_bump),
+        ] {
+            let data = borsh::to_vec(&CounterAccount {
+                bump: canonical_bump.wrapping_sub(1),
+                ..CounterAccount::new(4, authority.pubkey(), 0)
+            })
+            .unwrap();
+            program_test.add_account(
+                address,
+                Account {
+                    lamports: Rent::default().minimum_balance(data.len()),
+                    data,
+                    owner: program_id,
+                    ..Account::default()
+                },
+            );
+        }
+        let mut context = program_test.start_with_context().await;
+ 
+        let refresh = CounterInstruction::RefreshBump.instruction(
+            &program_id,
+            vec![
+                AccountMeta::new(counter, false),
+                AccountMeta::new_readonly(authority.pubkey(), true),
+            ],
+        );
+        let result = process(&mut context, &[refresh], &[&authority]).await;
+        assert!(result.result.is_ok());
+        let counter_data = get_counter(&mut context, counter).await;
+        assert_eq!(counter_data.bump, bump);
+        assert_eq!(counter_data.count, 4);
+ 
+        // The current authority no longer derives a transferred counter, the
+        // key it was created from does, as a mint does its counter
+        let refresh_with_seed = |counter, seed: Option<Pubkey>| {
+            let mut accounts = vec![
+                AccountMeta::new(counter, false),
+                AccountMeta::new_readonly(authority.pubkey(), true),
+            ];
+            accounts.extend(seed.map(|seed| AccountMeta::new_readonly(seed, false)));
+            CounterInstruction::RefreshBump.instruction(&program_id, accounts)
+        };
+        let result = process(
+            &mut context,
+            &[refresh_with_seed(transferred, None)],
+            &[&authority],
+        )
+        .await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::InvalidSeeds)
+        );
+        let result = process(
+            &mut context,
+            &[
+                refresh_with_seed(transferred, Some(original_authority)),
+                refresh_with_seed(mint_counter, Some(mint)),
+            ],
+            &[&authority],
+        )
+        .await;
+        assert!(result.result.is_ok());
+        assert_eq!(
+            get_counter(&mut context, transferred).await.bump,
+            transferred_bump
+        );
+        assert_eq!(
+            get_counter(&mut context, mint_counter).await.bump,
+            mint_bump
+        );
+ 
+        // A keypair counter has no canonical bump to restore
+        let keypair_counter = initialize_counter(&mut context, program_id, 0).await;
+        let refresh = CounterInstruction::RefreshBump.instruction(
+            &program_id,
+            vec![
+                AccountMeta::new(keypair_counter.pubkey(), false),
+                AccountMeta::new_readonly(context.payer.pubkey(), true),
+            ],
+        );
+        let result = process(&mut context, &[refresh], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::InvalidSeeds)
+        );
+    }
+ 
+    #[tokio::test]
+    async fn test_decrement_with_remainder_stops_at_zero() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 3).await;
+        let decrement = CounterInstruction::DecrementWithRemainder { amount: 5 }
+            .instruction(&program_id, vec![AccountMeta::new(counter.pubkey(), false)]);
+ 
+        let result = process(&mut context, &[decrement], &[]).await;
+        assert!(result.result.is_ok());
+        let return_data = result.metadata.unwrap().return_data.unwrap();
+        assert_eq!(return_data.data[..8], 2u64.to_le_bytes());
+        let counter_data = get_counter(&mut context, counter.pubkey()).await;
+        assert_eq!(counter_data.count, 0);
+        assert_eq!(
+            return_data.data[8..],
+            hash(&borsh::to_vec(&counter_data).unwrap()).to_bytes()
+        );
+    }
+ 
+    #[tokio::test]
+    async fn test_multi_counter_slots_advance_independently() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let multi = Keypair::new();
+        let payer = context.payer.pubkey();
+        let initialize = CounterInstruction::InitializeMultiCounter.instruction(
+            &program_id,
+            vec![
+                AccountMeta::new(multi.pubkey(), true),
+                AccountMeta::new(payer, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let result = process(&mut context, &[initialize], &[&multi]).await;
+        assert!(result.result.is_ok());
+ 
+        let increment_slot = |slot| {
+            CounterInstruction::IncrementSlot { slot }
+                .instruction(&program_id, vec![AccountMeta::new(multi.pubkey(), false)])
+        };
+        let increments = [increment_slot(0), increment_slot(15), increment_slot(0)];
+        let result = process(&mut context, &increments, &[]).await;
+        assert!(result.result.is_ok());
+ 
+        let account = context
+            .banks_client
+            .get_account(multi.pubkey())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(account.data.len(), MultiCounter::LEN);
+        let multi_data = MultiCounter::unpack(&account.data).unwrap();
+        assert_eq!(multi_data.authority, payer);
+        let mut expected = [0; MultiCounter::SLOTS];
+        expected[0] = 2;
+        expected[15] = 1;
+        assert_eq!(multi_data.counts, expected);
+ 
+        let set_slot = CounterInstruction::SetSlot { slot: 16, value: 1 }.instruction(
+            &program_id,
+            vec![
+                AccountMeta::new(multi.pubkey(), false),
+                AccountMeta::new_readonly(payer, true),
+            ],
+        );
+        let result = process(&mut context, &[set_slot], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::SlotOutOfRange as u32)
+            )
+        );
+    }
+ 
+    #[tokio::test]
+    async fn test_drain_counter_returns_and_clears_count() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 12).await;
+        let payer = context.payer.pubkey();
+        let data = CounterInstruction::DrainCounter.pack();
+        let drain = authority_instruction(program_id, counter.pubkey(), payer, &data);
+ 
+        let result = process(&mut context, &[drain], &[]).await;
+        assert!(result.result.is_ok());
+        let return_data = result.metadata.unwrap().return_data.unwrap();
+        assert_eq!(return_data.data[..8], 12u64.to_le_bytes());
+        let counter_data = get_counter(&mut context, counter.pubkey()).await;
+        assert_eq!(counter_data.count, 0);
+        assert_eq!(
+            return_data.data[8..],
+            hash(&borsh::to_vec(&counter_data).unwrap()).to_bytes()
+        );
+ 
+        // Only the authority may drain
+        let stranger = Keypair::new();
+        let drain = authority_instruction(program_id, counter.pubkey(), stranger.pubkey(), &data);
+        let result = process(&mut context, &[drain], &[&stranger]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Unauthorized as u32)
+            )
+        );
+    }
+ 
+    #[tokio::test]
+    async fn test_initializers_charge_configured_creation_fee() {
+        let program_id = Pubkey::new_unique();
+        let upgrade_authority = Keypair::new();
+        let creator = Keypair::new();
+        let mut program_test = program_test(program_id);
+        add_program_data(&mut program_test, program_id, upgrade_authority.pubkey());
+        for funded in [upgrade_authority.pubkey(), creator.pubkey()] {
+            program_test.add_account(
+                funded,
+                Account {
+                    lamports: 1_000_000_000,
+                    ..Account::default()
+                },
+            );
+        }
+        let mut context = program_test.start_with_context().await;
+        let admin = Keypair::new();
+        let treasury = Pubkey::new_unique();
+        let creation_fee = 2_000_000;
+ 
+        let mut initialize_config =
+            set_global_pause_instruction(program_id, upgrade_authority.pubkey(), false);
+        initialize_config.data = CounterInstruction::InitializeConfig {
+            admin: admin.pubkey(),
+        }
+        .pack();
+        let config = GlobalConfig::find_address(&program_id).0;
+        let set_creation_fee = |treasury| {
+            CounterInstruction::SetCreationFee {
+                creation_fee,
+                treasury,
+            }
+            .instruction(
+                &program_id,
+                vec![
+                    AccountMeta::new(config, false),
+                    AccountMeta::new_readonly(admin.pubkey(), true),
+                    AccountMeta::new_readonly(treasury, false),
+                ],
+            )
+        };
+        let result = process(&mut context, &[initialize_config], &[&upgrade_authority]).await;
+        assert!(result.result.is_ok());
+ 
+        // Neither the default pubkey nor an executable account can take the fee
+        for unpayable in [Pubkey::default(), program_id] {
+            let result = process(&mut context, &[set_creation_fee(unpayable)], &[&admin]).await;
+            assert_eq!(
+                result.result.unwrap_err(),
+                TransactionError::InstructionError(
+                    0,
+                    InstructionError::Custom(CounterError::InvalidTreasury as u32)
+                )
+            );
+        }
+        let result = process(&mut context, &[set_creation_fee(treasury)], &[&admin]).await;
+        assert!(result.result.is_ok());
+ 
+        let counter = Keypair::new();
+        let initialize = CounterInstruction::InitializeCounter { initial_value: 0 }.instruction(
+            &program_id,
+            vec![
+                AccountMeta::new(counter.pubkey(), true),
+                AccountMeta::new(creator.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(config, false),
+                AccountMeta::new(treasury, false),
+            ],
+        );
+        let result = process(&mut context, &[initialize], &[&counter, &creator]).await;
+        assert!(result.result.is_ok());
+ 
+        let treasury_balance = context.banks_client.get_balance(treasury).await.unwrap();
+        assert_eq!(treasury_balance, creation_fee);
+        let creator_balance = context
+            .banks_client
+            .get_balance(creator.pubkey())
+            .await
+            .unwrap();
+        let rent = Rent::default().minimum_balance(CounterAccount::space_for(0).unwrap());
+        assert_eq!(creator_balance, 1_000_000_000 - rent - creation_fee);
+ 
+        // Other initializers go through the same creation path and pay too
+        let full_counter = Keypair::new();
+        let mut initialize_full = initialize_full_instruction(
+            program_id,
+            full_counter.pubkey(),
+            creator.pubkey(),
+            0,
+            10,
+            0,
+        );
+        initialize_full.accounts.extend([
+            AccountMeta::new_readonly(config, false),
+            AccountMeta::new(treasury, false),
+        ]);
+        let result = process(&mut context, &[initialize_full], &[&full_counter, &creator]).await;
+        assert!(result.result.is_ok());
+        let treasury_balance = context.banks_client.get_balance(treasury).await.unwrap();
+        assert_eq!(treasury_balance, 2 * creation_fee);
+ 
+        // The original account list, without the config, still initializes
+        // and is charged no fee
+        let legacy_counter = Keypair::new();
+        let initialize = CounterInstruction::InitializeCounter { initial_value: 0 }.instruction(
+            &program_id,
+            vec![
+                AccountMeta::new(legacy_counter.pubkey(), true),
+                AccountMeta::new(creator.pubkey(), true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        );
+        let result = process(&mut context, &[initialize], &[&legacy_counter, &creator]).await;
+        assert!(result.result.is_ok());
+        let treasury_balance = context.banks_client.get_balance(treasury).await.unwrap();
+        assert_eq!(treasury_balance, 2 * creation_fee);
+    }
+ 
+    #[tokio::test]
+    async fn test_increment_if_in_tree_verifies_the_proof() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 0).await;
+ 
+        // A four-member allowlist: root = H(H(a, b), H(c, d))
+        let members: Vec<Keypair> = (0..4).map(|_| Keypair::new()).collect();
+        let leaves: Vec<[u8; 32]> = members
+            .iter()
+            .map(|member| merkle_leaf(&member.pubkey()))
+            .collect();
+        let left = merkle_parent(&leaves[0], &leaves[1]);
+        let right = merkle_parent(&leaves[2], &leaves[3]);
+        let merkle_root = merkle_parent(&left, &right);
+        let set_root = authority_instruction(
+            program_id,
+            counter.pubkey(),
+            context.payer.pubkey(),
+            &CounterInstruction::SetMerkleRoot { merkle_root }.pack(),
+        );
+        let result = process(&mut context, &[set_root], &[]).await;
+        assert!(result.result.is_ok());
+ 
+        let increment = |proof: Vec<[u8; 32]>, signer: &Keypair| {
+            CounterInstruction::IncrementIfInTree {
+                proof,
+                leaf: merkle_leaf(&signer.pubkey()),
+            }
+            .instruction(
+                &program_id,
+                vec![
+                    AccountMeta::new(counter.pubkey(), false),
+                    AccountMeta::new_readonly(signer.pubkey(), true),
+                ],
+            )
+        };
+        let not_in_allowlist = TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(CounterError::NotInAllowlist as u32),
+        );
+ 
+        let proof = vec![leaves[3], left];
+        let result = process(
+            &mut context,
+            &[increment(proof, &members[2])],
+            &[&members[2]],
+        )
+        .await;
+        assert!(result.result.is_ok());
+        assert_eq!(get_counter(&mut context, counter.pubkey()).await.count, 1);
+ 
+        // A sibling from the wrong branch, or someone outside the tree, fails
+        let proof = vec![leaves[1], left];
+        let result = process(
+            &mut context,
+            &[increment(proof, &members[2])],
+            &[&members[2]],
+        )
+        .await;
+        assert_eq!(result.result.unwrap_err(), not_in_allowlist);
+        let stranger = Keypair::new();
+        let proof = vec![leaves[3], left];
+        let result = process(&mut context, &[increment(proof, &stranger)], &[&stranger]).await;
+        assert_eq!(result.result.unwrap_err(), not_in_allowlist);
+        assert_eq!(get_counter(&mut context, counter.pubkey()).await.count, 1);
+    }
+ 
+    #[test]
+    fn test_error_codes_round_trip_through_program_error() {
+        let errors: Vec<CounterError> = (0..)
+            .map_while(|code| CounterError::try_from(code).ok())
+            .collect();
+        // Every variant up to InvalidTreasury, code 45, with no gaps
+        assert_eq!(errors.len(), 46);
+        for (code, error) in (0u32..).zip(errors) {
+            assert_eq!(error as u32, code);
+            assert_eq!(ProgramError::from(error), ProgramError::Custom(code));
+            assert_eq!(
+                <CounterError as DecodeError<CounterError>>::decode_custom_error_to_enum(code),
+                Some(error)
+            );
+        }
+        assert_eq!(
+            CounterError::try_from(46),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+ 
+    #[tokio::test]
+    async fn test_sync_from_lamports_mirrors_surplus_above_rent() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 7).await;
+        let payer = context.payer.pubkey();
+        let deposit =
+            solana_sdk::system_instruction::transfer(&payer, &counter.pubkey(), 1_234_567);
+        let data = CounterInstruction::SyncFromLamports.pack();
+        let sync = authority_instruction(program_id, counter.pubkey(), payer, &data);
+ 
+        let result = process(&mut context, &[deposit, sync], &[]).await;
+        assert!(result.result.is_ok());
+        assert_eq!(
+            get_counter(&mut context, counter.pubkey()).await.count,
+            1_234_567
+        );
+    }
+ 
+    #[test]
+    fn test_remove_delegate_keeps_list_contiguous() {
+        let delegates: Vec<Pubkey> = (1..=CounterAccount::MAX_DELEGATES as u8)
+            .map(|byte| Pubkey::new_from_array([byte; 32]))
+            .collect();
+        let mut counter_data = CounterAccount::default();
+        for delegate in &delegates {
+            counter_data.add_delegate(*delegate).unwrap();
+        }
+        assert_eq!(
+            counter_data.add_delegate(Pubkey::new_unique()),
+            Err(CounterError::TooManyDelegates.into())
+        );
+ 
+        counter_data.remove_delegate(delegates[1]).unwrap();
+        assert_eq!(
+            counter_data.delegates,
+            [delegates[0], delegates[2], delegates[3], Pubkey::default()]
+        );
+        for delegate in [delegates[3], delegates[0], delegates[2]] {
+            counter_data.remove_delegate(delegate).unwrap();
+        }
+        assert_eq!(
+            counter_data.delegates,
+            [Pubkey::default(); CounterAccount::MAX_DELEGATES]
+        );
+        assert_eq!(
+            counter_data.remove_delegate(Pubkey::default()),
+            Err(CounterError::InvalidDelegate.into())
+        );
+    }
+ 
+    #[tokio::test]
+    async fn test_delegates_are_unique_and_stay_usable_after_removal() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 0).await;
+        let payer = context.payer.pubkey();
+        let (first, second) = (Keypair::new(), Keypair::new());
+        let update = |instruction: CounterInstruction| {
+            authority_instruction(program_id, counter.pubkey(), payer, &instruction.pack())
+        };
+        let add = |delegate: &Keypair| {
+            update(CounterInstruction::AddDelegate {
+                delegate: delegate.pubkey(),
+            })
+        };
+        let setup = [
+            update(CounterInstruction::SetRequireSigner {
+                require_signer: true,
+            }),
+            add(&first),
+            add(&second),
+        ];
+        let result = process(&mut context, &setup, &[]).await;
+        assert!(result.result.is_ok());
+ 
+        let result = process(&mut context, &[add(&first)], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::DelegateAlreadyExists as u32)
+            )
+        );
+        let zero = update(CounterInstruction::AddDelegate {
+            delegate: Pubkey::default(),
+        });
+        let result = process(&mut context, &[zero], &[]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::InvalidDelegate as u32)
+            )
+        );
+ 
+        let remove = update(CounterInstruction::RemoveDelegate {
+            delegate: first.pubkey(),
+        });
+        let result = process(&mut context, &[remove], &[]).await;
+        assert!(result.result.is_ok());
+        let counter_data = get_counter(&mut context, counter.pubkey()).await;
+        assert_eq!(counter_data.delegates[0], second.pubkey());
+ 
+        let increment = |signer: &Keypair| {
+            Instruction::new_with_bytes(
+                program_id,
+                &CounterInstruction::IncrementCounter.pack(),
+                vec![
+                    AccountMeta::new(counter.pubkey(), false),
+                    AccountMeta::new_readonly(signer.pubkey(), true),
+                ],
+            )
+        };
+        let result = process(&mut context, &[increment(&second)], &[&second]).await;
+        assert!(result.result.is_ok());
+        let result = process(&mut context, &[increment(&first)], &[&first]).await;
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CounterError::Unauthorized as u32)
+            )
+        );
+        assert_eq!(get_counter(&mut context, counter.pubkey()).await.count, 1);
+    }
+ 
+    #[tokio::test]
+    async fn test_initialize_and_increment_stores_first_increment() {
+        let program_id = Pubkey::new_unique();
+        let mut context = program_test(program_id).start_with_context().await;
+        let counter = Keypair::new();
+        let initialize = CounterInstruction::InitializeAndIncrement { initial_value: 0 }
+            .instruction(
+                &program_id,
+                vec![
+                    AccountMeta::new(counter.pubkey(), true),
+                    AccountMeta::new(context.payer.pubkey(), true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                ],
+            );
+ 
+        let result = process(&mut context, &[initialize], &[&counter]).await;
+        assert!(result.result.is_ok());
+        let counter_data = get_counter(&mut context, counter.pubkey()).await;
+        assert_eq!(counter_data.count, 1);
+        assert_eq!(counter_data.authority, context.payer.pubkey());
+    }
+ 
+    #[tokio::test]
+    async fn test_pda_creation_survives_prefunded_address() {
+        let program_id = Pubkey::new_unique();
+        let authority = Keypair::new();
+        let upgrade_authority = Keypair::new();
+        let (counter, _) = CounterAccount::find_pda(&program_id, &authority.pubkey());
+        let (config, _) = GlobalConfig::find_address(&program_id);
+        let mut program_test = program_test(program_id);
+        add_program_data(&mut program_test, program_id, upgrade_authority.pubkey());
+        // Someone sends a lamport to each PDA before it is created
+        for (address, lamports) in [
+            (counter, 1),
+            (config, 1),
+            (upgrade_authority.pubkey(), 1_000_000_000),
+        ] {
+            program_test.add_account(
+                address,
+                Account {
+                    lamports,
+                    ..Account::default()
+                },
+            );
+        }
+        let mut context = program_test.start_with_context().await;
+ 
+        let counter = create_pda_counter(&mut context, program_id, &authority, 5).await;
+        let account = context
+            .banks_client
+            .get_account(counter)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(account.owner, program_id);
+        assert_eq!(
+            account.lamports,
+            Rent::default().minimum_balance(account.data.len())
+        );
+        assert_eq!(get_counter(&mut context, counter).await.count, 5);
+ 
+        let pause = set_global_pause_instruction(program_id, upgrade_authority.pubkey(), true);
+        let result = process(&mut context, &[pause], &[&upgrade_authority]).await;
+        assert!(result.result.is_ok());
+        let data = context
+            .banks_client
+            .get_account(config)
+            .await
+            .unwrap()
+            .unwrap()
+            .data;
+        assert!(GlobalConfig::try_from_slice(&data).unwrap().paused);
+    }
+ 
+    #[tokio::test]
+    async fn test_config_lookalike_is_not_taken_for_the_config() {
+        let program_id = Pubkey::new_unique();
+        let lookalike = Pubkey::new_unique();
+        let mut program_test = program_test(program_id);
+        // Program-owned and config-sized, but not at the config PDA
+        let mut data = vec![0xff; GlobalConfig::LEN];
+        data[..8].copy_from_slice(&GlobalConfig::DISCRIMINATOR);
+        program_test.add_account(
+            lookalike,
+            Account {
+                lamports: Rent::default().minimum_balance(data.len()),
+                data,
+                owner: program_id,
+                ..Account::default()
+            },
+        );
+        let mut context = program_test.start_with_context().await;
+        let counter = initialize_counter(&mut context, program_id, 0).await;
+ 
+        let mut increment = increment_instruction(program_id, counter.pubkey());
+        increment
+            .accounts
+            .push(AccountMeta::new_readonly(lookalike, false));
+        let result = process(&mut context, &[increment], &[]).await;
+        assert!(result.result.is_ok());
+        assert_eq!(get_counter(&mut context, counter.pubkey()).await.count, 1);
+    }
+}
@@ -0,0 +1,143 @@
+//! Count arithmetic, kept free of Solana types so every boundary can be
+//! tested without a bank.
+use crate::error::CounterError;
+ 
+// A count value with the checked operations the handlers apply to it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CounterMath(pub u64);
+ 
+impl CounterMath {
+    // Basis points in one whole
+    pub const BPS_DENOMINATOR: u64 = 10_000;
+ 
+    pub fn checked_add(self, amount: u64) -> Result<u64, CounterError> {
+        self.0.checked_add(amount).ok_or(CounterError::Overflow)
+    }
+ 
+    pub fn checked_sub(self, amount: u64) -> Result<u64, CounterError> {
+        self.0.checked_sub(amount).ok_or(CounterError::Underflow)
+    }
+ 
+    // `count * factor + addend`
+    pub fn checked_mul_add(self, factor: u64, addend: u64) -> Result<u64, CounterError> {
+        self.0
+            .checked_mul(factor)
+            .and_then(|product| product.checked_add(addend))
+            .ok_or(CounterError::Overflow)
+    }
+ 
+    pub fn checked_div(self, divisor: u64) -> Result<u64, CounterError> {
+        self.0
+            .checked_div(divisor)
+            .ok_or(CounterError::DivisionByZero)
+    }
+ 
+    // Grow by `bps` basis points, truncating the increase toward zero. The
+    // product is widened so it cannot overflow before the division.
+    pub fn checked_add_bps(self, bps: u16) -> Result<u64, CounterError> {
+        let increase = u128::from(self.0) * u128::from(bps) / u128::from(Self::BPS_DENOMINATOR);
+        u64::try_from(increase)
+            .ok()
+            .and_then(|increase| self.0.checked_add(increase))
+            .ok_or(CounterError::Overflow)
+    }
+ 
+    // Move by a signed `delta`, naming the direction that ran out of range
+    pub fn checked_add_signed(self, delta: i64) -> Result<u64, CounterError> {
+        self.0.checked_add_signed(delta).ok_or(if delta < 0 {
+            CounterError::Underflow
+        } else {
+            CounterError::Overflow
+        })
+    }
+}
+ 
+#[cfg(test)]
+mod test {
+    use super::CounterMath;
+    use crate::error::CounterError;
+ 
+    #[test]
+    fn test_add_and_sub_boundaries() {
+        assert_eq!(CounterMath(0).checked_add(0), Ok(0));
+        assert_eq!(CounterMath(0).checked_add(1), Ok(1));
+        assert_eq!(CounterMath(u64::MAX - 1).checked_add(1), Ok(u64::MAX));
+        assert_eq!(
+            CounterMath(u64::MAX).checked_add(1),
+            Err(CounterError::Overflow)
+        );
+        assert_eq!(
+            CounterMath(1).checked_add(u64::MAX),
+            Err(CounterError::Overflow)
+        );
+ 
+        assert_eq!(CounterMath(1).checked_sub(1), Ok(0));
+        assert_eq!(CounterMath(u64::MAX).checked_sub(u64::MAX), Ok(0));
+        assert_eq!(CounterMath(0).checked_sub(1), Err(CounterError::Underflow));
+        assert_eq!(CounterMath(1).checked_sub(2), Err(CounterError::Underflow));
+    }
+ 
+    #[test]
+    fn test_mul_add_and_div_boundaries() {
+        assert_eq!(CounterMath(0).checked_mul_add(u64::MAX, 1), Ok(1));
+        assert_eq!(CounterMath(3).checked_mul_add(4, 5), Ok(17));
+        assert_eq!(CounterMath(1).checked_mul_add(u64::MAX, 0), Ok(u64::MAX));
+        assert_eq!(
+            CounterMath(2).checked_mul_add(u64::MAX, 0),
+            Err(CounterError::Overflow)
+        );
+        assert_eq!(
+            CounterMath(1).checked_mul_add(u64::MAX, 1),
+            Err(CounterError::Overflow)
+        );
+ 
+        assert_eq!(CounterMath(7).checked_div(2), Ok(3));
+        assert_eq!(CounterMath(0).checked_div(1), Ok(0));
+        assert_eq!(CounterMath(u64::MAX).checked_div(u64::MAX), Ok(1));
+        assert_eq!(
+            CounterMath(1).checked_div(0),
+            Err(CounterError::DivisionByZero)
+        );
+    }
+ 
+    #[test]
+    fn test_bps_boundaries() {
+        assert_eq!(CounterMath(0).checked_add_bps(u16::MAX), Ok(0));
+        assert_eq!(CounterMath(100).checked_add_bps(0), Ok(100));
+        assert_eq!(CounterMath(100).checked_add_bps(250), Ok(102));
+        // 1 * 9_999 / 10_000 truncates to no increase at all
+        assert_eq!(CounterMath(1).checked_add_bps(9_999), Ok(1));
+        assert_eq!(CounterMath(1).checked_add_bps(10_000), Ok(2));
+        assert_eq!(
+            CounterMath(u64::MAX / 2).checked_add_bps(10_000),
+            Ok(u64::MAX - 1)
+        );
+        assert_eq!(
+            CounterMath(u64::MAX).checked_add_bps(1),
+            Err(CounterError::Overflow)
+        );
+    }
+ 
+    #[test]
+    fn test_signed_delta_boundaries() {
+        assert_eq!(CounterMath(10).checked_add_signed(5), Ok(15));
+        assert_eq!(CounterMath(10).checked_add_signed(-10), Ok(0));
+        assert_eq!(CounterMath(0).checked_add_signed(0), Ok(0));
+        assert_eq!(
+            CounterMath(0).checked_add_signed(-1),
+            Err(CounterError::Underflow)
+        );
+        assert_eq!(
+            CounterMath(0).checked_add_signed(i64::MIN),
+            Err(CounterError::Underflow)
+        );
+        assert_eq!(
+            CounterMath(u64::MAX).checked_add_signed(i64::MIN),
+            Ok(u64::MAX / 2)
+        );
+        assert_eq!(
+            CounterMath(u64::MAX).checked_add_signed(1),
+            Err(CounterError::Overflow)
+        );
+    }
+}
@@ -0,0 +1,3043 @@
+//! Program entrypoint and instruction handlers.
+use crate::{
+    error::CounterError,
+    instruction::{CounterInstruction, MAX_BATCH, MAX_PROOF_LEN},
+    math::CounterMath,
+    seeds,
+    state::*,
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    ed25519_program,
+    entrypoint::ProgramResult,
+    msg,
+    program::{invoke, invoke_signed},
+    program_error::{PrintProgramError, ProgramError},
+    pubkey::Pubkey,
+    system_instruction, system_program,
+    sysvar::{
+        clock::Clock,
+        instructions::{self, get_instruction_relative},
+        rent::Rent,
+        Sysvar,
+    },
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use std::str::FromStr;
+ 
+// Program entrypoint, disabled when the crate is consumed as a library
+#[cfg(not(feature = "no-entrypoint"))]
+solana_program::entrypoint!(process_instruction);
+ 
+// Function to route instructions to the correct handler
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    // Route the instruction, logging any failure before it reaches the runtime
+    log_err(route_instruction(program_id, accounts, instruction_data))
+}
+ 
+// Unpack the instruction data and dispatch it to its handler
+fn route_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    // Unpack instruction data
+    let instruction = CounterInstruction::unpack(instruction_data)?;
+ 
+    // Refuse state changes while the global kill-switch is engaged
+    if instruction.is_pausable() {
+        GlobalConfig::check_not_paused(program_id, accounts)?;
+    }
+ 
+    // Match instruction type
+    match instruction {
+        CounterInstruction::InitializeCounter { initial_value } => {
+            process_initialize_counter(program_id, accounts, initial_value)?
+        }
+        CounterInstruction::IncrementCounter => process_increment_counter(program_id, accounts)?,
+        CounterInstruction::DecrementSaturating => {
+            process_decrement_saturating(program_id, accounts)?
+        }
+        CounterInstruction::SetDelegate { delegate_pda } => {
+            process_set_delegate(program_id, accounts, delegate_pda)?
+        }
+        CounterInstruction::SetRequireSigner { require_signer } => {
+            process_set_require_signer(program_id, accounts, require_signer)?
+        }
+        CounterInstruction::SetMaxValue { max_value } => {
+            process_set_max_value(program_id, accounts, max_value)?
+        }
+        CounterInstruction::NoOp => process_noop()?,
+        CounterInstruction::MigrateCounter => process_migrate_counter(program_id, accounts)?,
+        CounterInstruction::Configure {
+            step,
+            max_value,
+            cooldown_secs,
+        } => process_configure(program_id, accounts, step, max_value, cooldown_secs)?,
+        CounterInstruction::InitializeCounterFull {
+            initial_value,
+            step,
+            max_value,
+            require_signer,
+            decimals,
+            min_value,
+            category,
+            emit_events,
+        } => process_initialize_counter_full(
+            program_id,
+            accounts,
+            CounterAccount {
+                count: initial_value,
+                step,
+                max_value,
+                require_signer,
+                decimals,
+                min_value,
+                category,
+                emit_events,
+                ..CounterAccount::default()
+            },
+        )?,
+        CounterInstruction::SetGlobalPause { paused } => {
+            process_set_global_pause(program_id, accounts, paused)?
+        }
+        CounterInstruction::DecrementWithReason { reason } => {
+            process_decrement_with_reason(program_id, accounts, reason)?
+        }
+        CounterInstruction::DescribeCounter => process_describe_counter(program_id, accounts)?,
+        CounterInstruction::MulAdd { factor, addend } => {
+            process_mul_add(program_id, accounts, factor, addend)?
+        }
+        CounterInstruction::CloseAndTransfer => process_close_and_transfer(program_id, accounts)?,
+        CounterInstruction::IncrementBy { amount } => {
+            process_increment_by(program_id, accounts, amount)?
+        }
+        CounterInstruction::SetMaxStepPerCall { max_step_per_call } => {
+            process_set_max_step_per_call(program_id, accounts, max_step_per_call)?
+        }
+        CounterInstruction::InitializeRaw { data } => {
+            process_initialize_raw(program_id, accounts, &data)?
+        }
+        CounterInstruction::SetMinReserve { min_reserve } => {
+            process_set_min_reserve(program_id, accounts, min_reserve)?
+        }
+        CounterInstruction::Withdraw { lamports } => {
+            process_withdraw(program_id, accounts, lamports)?
+        }
+        CounterInstruction::QueryAccount => process_query_account(program_id, accounts)?,
+        CounterInstruction::SetStepAndTick { step } => {
+            process_set_step_and_tick(program_id, accounts, step)?
+        }
+        CounterInstruction::Touch => process_touch(program_id, accounts)?,
+        CounterInstruction::IncrementPercent { bps } => {
+            process_increment_percent(program_id, accounts, bps)?
+        }
+        CounterInstruction::SetAutoFreeze { auto_freeze_at_max } => {
+            process_set_auto_freeze(program_id, accounts, auto_freeze_at_max)?
+        }
+        CounterInstruction::InitializePdaCounter { initial_value } => {
+            process_initialize_pda_counter(program_id, accounts, initial_value)?
+        }
+        CounterInstruction::AddSample { value } => process_add_sample(program_id, accounts, value)?,
+        CounterInstruction::ResetIfElapsed {
+            period_secs,
+            require_elapsed,
+        } => process_reset_if_elapsed(program_id, accounts, period_secs, require_elapsed)?,
+        CounterInstruction::ResetCounter => process_reset_counter(program_id, accounts)?,
+        CounterInstruction::CloseCounter => process_close_counter(program_id, accounts)?,
+        CounterInstruction::DecrementWithProgress => {
+            process_decrement_with_progress(program_id, accounts)?
+        }
+        CounterInstruction::Metrics => process_metrics(program_id, accounts)?,
+        CounterInstruction::SwapCounters => process_swap_counters(program_id, accounts)?,
+        CounterInstruction::SetCategory { category } => {
+            process_set_category(program_id, accounts, category)?
+        }
+        CounterInstruction::IncrementAt { client_ts } => {
+            process_increment_at(program_id, accounts, client_ts)?
+        }
+        CounterInstruction::QueryRent { space } => process_query_rent(space)?,
+        CounterInstruction::SetSaturating { saturating } => {
+            process_set_saturating(program_id, accounts, saturating)?
+        }
+        CounterInstruction::SetAllowZero { allow_zero } => {
+            process_set_allow_zero(program_id, accounts, allow_zero)?
+        }
+        CounterInstruction::IncrementWithApproval => {
+            process_increment_with_approval(program_id, accounts)?
+        }
+        CounterInstruction::IncrementTimes { n } => {
+            process_increment_times(program_id, accounts, n)?
+        }
+        CounterInstruction::ProposeAuthority { new_authority } => {
+            process_propose_authority(program_id, accounts, new_authority)?
+        }
+        CounterInstruction::AcceptAuthority => process_accept_authority(program_id, accounts)?,
+        CounterInstruction::BulkQuery => process_bulk_query(program_id, accounts)?,
+        CounterInstruction::Checkpoint => process_checkpoint(program_id, accounts)?,
+        CounterInstruction::SetTokenGate {
+            gate_mint,
+            min_amount,
+        } => process_set_token_gate(program_id, accounts, gate_mint, min_amount)?,
+        CounterInstruction::IncrementIfHolder => process_increment_if_holder(program_id, accounts)?,
+        CounterInstruction::StampSlot => process_stamp_slot(program_id, accounts)?,
+        CounterInstruction::SetEmitEvents { emit_events } => {
+            process_set_emit_events(program_id, accounts, emit_events)?
+        }
+        CounterInstruction::DecrementWithBorrow { amount } => {
+            process_decrement_with_borrow(program_id, accounts, amount)?
+        }
+        CounterInstruction::CopyConfigFrom => process_copy_config_from(program_id, accounts)?,
+        CounterInstruction::InitializeCounterStr { value } => {
+            process_initialize_counter_str(program_id, accounts, &value)?
+        }
+        CounterInstruction::BatchIncrement => process_batch_increment(program_id, accounts)?,
+        CounterInstruction::InitializeCounterShared {
+            initial_value,
+            first_share_bps,
+        } => {
+            process_initialize_counter_shared(program_id, accounts, initial_value, first_share_bps)?
+        }
+        CounterInstruction::QueryStaleness => process_query_staleness(program_id, accounts)?,
+        CounterInstruction::InitializeConfig { admin } => {
+            process_initialize_config(program_id, accounts, admin)?
+        }
+        CounterInstruction::SetDailyLimit { daily_limit } => {
+            process_set_daily_limit(program_id, accounts, daily_limit)?
+        }
+        CounterInstruction::DecrementDaily { amount } => {
+            process_decrement_daily(program_id, accounts, amount)?
+        }
+        CounterInstruction::ApplyDelta { delta } => {
+            process_apply_delta(program_id, accounts, delta)?
+        }
+        CounterInstruction::CheckIncrementBy { amount } => {
+            process_check_increment_by(program_id, accounts, amount)?
+        }
+        CounterInstruction::LockUntil { unlock_ts } => {
+            process_lock_until(program_id, accounts, unlock_ts)?
+        }
+        CounterInstruction::SetExpiry { expires_at_slot } => {
+            process_set_expiry(program_id, accounts, expires_at_slot)?
+        }
+        CounterInstruction::SlotsRemaining => process_slots_remaining(program_id, accounts)?,
+        CounterInstruction::SweepClosed => process_sweep_closed(program_id, accounts)?,
+        CounterInstruction::IncrementWithTip { lamports } => {
+            process_increment_with_tip(program_id, accounts, lamports)?
+        }
+        CounterInstruction::IncrementFresh { max_age_slots } => {
+            process_increment_fresh(program_id, accounts, max_age_slots)?
+        }
+        CounterInstruction::SetMeta { key, value } => {
+            process_set_meta(program_id, accounts, key, value)?
+        }
+        CounterInstruction::RemoveMeta { key } => process_remove_meta(program_id, accounts, key)?,
+        CounterInstruction::SetCounter { value } => {
+            process_set_counter(program_id, accounts, value)?
+        }
+        CounterInstruction::InitializeMintCounter { initial_value } => {
+            process_initialize_mint_counter(program_id, accounts, initial_value)?
+        }
+        CounterInstruction::RefreshBump => process_refresh_bump(program_id, accounts)?,
+        CounterInstruction::DecrementWithRemainder { amount } => {
+            process_decrement_with_remainder(program_id, accounts, amount)?
+        }
+        CounterInstruction::InitializeMultiCounter => {
+            process_initialize_multi_counter(program_id, accounts)?
+        }
+        CounterInstruction::IncrementSlot { slot } => {
+            process_increment_slot(program_id, accounts, slot)?
+        }
+        CounterInstruction::SetSlot { slot, value } => {
+            process_set_slot(program_id, accounts, slot, value)?
+        }
+        CounterInstruction::DrainCounter => process_drain_counter(program_id, accounts)?,
+        CounterInstruction::SetCreationFee {
+            creation_fee,
+            treasury,
+        } => process_set_creation_fee(program_id, accounts, creation_fee, treasury)?,
+        CounterInstruction::SetMerkleRoot { merkle_root } => {
+            process_set_merkle_root(program_id, accounts, merkle_root)?
+        }
+        CounterInstruction::IncrementIfInTree { proof, leaf } => {
+            process_increment_if_in_tree(program_id, accounts, &proof, &leaf)?
+        }
+        CounterInstruction::SyncFromLamports => process_sync_from_lamports(program_id, accounts)?,
+        CounterInstruction::AddDelegate { delegate } => {
+            process_add_delegate(program_id, accounts, delegate)?
+        }
+        CounterInstruction::RemoveDelegate { delegate } => {
+            process_remove_delegate(program_id, accounts, delegate)?
+        }
+        CounterInstruction::InitializeAndIncrement { initial_value } => {
+            process_initialize_and_increment(program_id, accounts, initial_value)?
+        }
+    };
+    Ok(())
+}
+ 
+// Log a failed result, naming the CounterError variant for custom errors
+fn log_err(result: ProgramResult) -> ProgramResult {
+    if let Err(error) = &result {
+        error.print::<CounterError>();
+    }
+    result
+}
+ 
+// Initialize a new counter account
+fn process_initialize_counter(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    initial_value: u64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+ 
+    let counter_account = next_account_info(accounts_iter)?;
+    let payer_account = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    require_not_executable(counter_account)?;
+    require_writable(counter_account)?;
+ 
+    create_counter_account(
+        program_id,
+        accounts,
+        counter_account,
+        payer_account,
+        system_program,
+        CounterAccount::space_for(0)?,
+        &[],
+    )?;
+ 
+    // Create a new CounterAccount struct with the initial value, owned by the payer
+    let counter_data = CounterAccount::new(
+        initial_value,
+        *payer_account.key,
+        Clock::get()?.unix_timestamp,
+    );
+ 
+    // Get a mutable reference to the counter account's data
+    let mut account_data = counter_account.data.borrow_mut();
+ 
+    // Serialize the CounterAccount struct into the account's data
+    counter_data.store(&mut account_data)?;
+    counter_data.return_state_hash(&[])?;
+    counter_data.emit_event(CounterEvent::Initialized {
+        counter: *counter_account.key,
+        authority: counter_data.authority,
+        count: counter_data.count,
+    })?;
+ 
+    msg!("Counter initialized with value: {}", initial_value);
+ 
+    Ok(())
+}
+ 
+// Initialize a new counter whose rent is split between two payers, the first
+// covering `first_share_bps` of it plus any odd lamport. Accounts: the
+// counter, the first payer, who becomes the authority, the second payer and
+// the system program; all but the last sign.
+fn process_initialize_counter_shared(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    initial_value: u64,
+    first_share_bps: u16,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let first_payer = next_account_info(accounts_iter)?;
+    let second_payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    require_not_executable(counter_account)?;
+    require_writable(counter_account)?;
+    if first_share_bps > 10_000 {
+        return Err(CounterError::BpsTooLarge.into());
+    }
+ 
+    // The first payer alone pays any creation fee
+    charge_creation_fee(program_id, accounts, first_payer, system_program)?;
+ 
+    // Round the second share down so the first payer picks up the remainder
+    let account_space = CounterAccount::space_for(0)?;
+    let required_lamports = Rent::get()?.minimum_balance(account_space);
+    let second_share =
+        u128::from(required_lamports) * u128::from(10_000 - first_share_bps) / 10_000;
+    let second_share = u64::try_from(second_share).map_err(|_| CounterError::Overflow)?;
+    let first_share = required_lamports - second_share;
+    for (payer_account, share) in [(first_payer, first_share), (second_payer, second_share)] {
+        invoke(
+            &system_instruction::transfer(payer_account.key, counter_account.key, share),
+            &[
+                payer_account.clone(),
+                counter_account.clone(),
+                system_program.clone(),
+            ],
+        )?;
+    }
+    invoke(
+        &system_instruction::allocate(counter_account.key, account_space as u64),
+        &[counter_account.clone(), system_program.clone()],
+    )?;
+    invoke(
+        &system_instruction::assign(counter_account.key, program_id),
+        &[counter_account.clone(), system_program.clone()],
+    )?;
+ 
+    let counter_data = CounterAccount::new(
+        initial_value,
+        *first_payer.key,
+        Clock::get()?.unix_timestamp,
+    );
+    counter_data.store(&mut counter_account.data.borrow_mut())?;
+    counter_data.return_state_hash(&[])?;
+    counter_data.emit_event(CounterEvent::Initialized {
+        counter: *counter_account.key,
+        authority: counter_data.authority,
+        count: counter_data.count,
+    })?;
+ 
+    msg!(
+        "Counter initialized with value: {}, rent split {} / {}",
+        initial_value,
+        first_share,
+        second_share
+    );
+    Ok(())
+}
+ 
+// Initialize a new counter from a decimal string, for clients that find text
+// easier to build than a u64
+fn process_initialize_counter_str(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    value: &str,
+) -> ProgramResult {
+    let initial_value = u64::from_str(value).map_err(|_| ProgramError::InvalidInstructionData)?;
+    process_initialize_counter(program_id, accounts, initial_value)
+}
+ 
+// Initialize a new counter account with its full configuration in one shot
+// `config` carries the requested count and settings; the authority and
+// timestamp are filled in here
+fn process_initialize_counter_full(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    config: CounterAccount,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+ 
+    let counter_account = next_account_info(accounts_iter)?;
+    let payer_account = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    require_not_executable(counter_account)?;
+    require_writable(counter_account)?;
+ 
+    // Validate the configuration before paying for the account
+    config.validate_config()?;
+ 
+    create_counter_account(
+        program_id,
+        accounts,
+        counter_account,
+        payer_account,
+        system_program,
+        CounterAccount::space_for(0)?,
+        &[],
+    )?;
+ 
+    let counter_data = CounterAccount {
+        authority: *payer_account.key,
+        last_updated: Clock::get()?.unix_timestamp,
+        ..config
+    };
+    counter_data.store(&mut counter_account.data.borrow_mut())?;
+    counter_data.return_state_hash(&[])?;
+    counter_data.emit_event(CounterEvent::Initialized {
+        counter: *counter_account.key,
+        authority: counter_data.authority,
+        count: counter_data.count,
+    })?;
+ 
+    msg!(
+        "Counter initialized with value: {}, step: {}, max_value: {}, require_signer: {}",
+        counter_data.display_amount(),
+        counter_data.step,
+        counter_data.max_value,
+        counter_data.require_signer
+    );
+    Ok(())
+}
+ 
+// Initialize a counter at the PDA seeded by `[b"counter", authority]`, so each
+// authority has one counter whose address clients can derive
+fn process_initialize_pda_counter(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    initial_value: u64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+ 
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+    let payer_account = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    require_not_executable(counter_account)?;
+    require_writable(counter_account)?;
+    if !authority_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+ 
+    let (counter_address, bump) = CounterAccount::find_pda(program_id, authority_account.key);
+    if *counter_account.key != counter_address {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    let bump_seed = [bump];
+    let signer_seeds = seeds::with_bump(&seeds::counter_seeds(authority_account.key), &bump_seed);
+    create_counter_account(
+        program_id,
+        accounts,
+        counter_account,
+        payer_account,
+        system_program,
+        CounterAccount::space_for(0)?,
+        &[&signer_seeds],
+    )?;
+ 
+    let counter_data = CounterAccount {
+        bump,
+        ..CounterAccount::new(
+            initial_value,
+            *authority_account.key,
+            Clock::get()?.unix_timestamp,
+        )
+    };
+    counter_data.store(&mut counter_account.data.borrow_mut())?;
+    counter_data.return_state_hash(&[])?;
+    counter_data.emit_event(CounterEvent::Initialized {
+        counter: *counter_account.key,
+        authority: counter_data.authority,
+        count: counter_data.count,
+    })?;
+ 
+    msg!("PDA counter initialized with value: {}", initial_value);
+    Ok(())
+}
+ 
+// Initialize the counter at the PDA seeded by `[b"mint_counter", mint]`, once
+// the mint is confirmed to be an SPL Token mint. Accounts: the counter, the
+// mint, the payer, who signs and becomes the authority, and the system program.
+fn process_initialize_mint_counter(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    initial_value: u64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+ 
+    let counter_account = next_account_info(accounts_iter)?;
+    let mint_account = next_account_info(accounts_iter)?;
+    let payer_account = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    require_not_executable(counter_account)?;
+    require_writable(counter_account)?;
+    assert_owned_by(mint_account, &TokenHolding::PROGRAM_ID)?;
+    if mint_account.data_len() != TokenHolding::MINT_LEN {
+        msg!("Account {} is not a mint", mint_account.key);
+        return Err(ProgramError::InvalidAccountData);
+    }
+ 
+    let (counter_address, bump) = CounterAccount::find_mint_pda(program_id, mint_account.key);
+    if *counter_account.key != counter_address {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    let bump_seed = [bump];
+    let signer_seeds = seeds::with_bump(&seeds::mint_counter_seeds(mint_account.key), &bump_seed);
+    create_counter_account(
+        program_id,
+        accounts,
+        counter_account,
+        payer_account,
+        system_program,
+        CounterAccount::space_for(0)?,
+        &[&signer_seeds],
+    )?;
+ 
+    let counter_data = CounterAccount {
+        bump,
+        ..CounterAccount::new(
+            initial_value,
+            *payer_account.key,
+            Clock::get()?.unix_timestamp,
+        )
+    };
+    counter_data.store(&mut counter_account.data.borrow_mut())?;
+    counter_data.return_state_hash(&[])?;
+    counter_data.emit_event(CounterEvent::Initialized {
+        counter: *counter_account.key,
+        authority: counter_data.authority,
+        count: counter_data.count,
+    })?;
+ 
+    msg!(
+        "Mint counter for {} initialized with value: {}",
+        mint_account.key,
+        initial_value
+    );
+    Ok(())
+}
+ 
+// Initialize a counter from client-built account bytes, written verbatim once
+// they are known to decode as a current-layout counter. Only the exact current
+// layout is accepted, as every other handler loads exactly that: anything
+// longer is RawDataTooLarge, anything else InvalidInstructionData.
+fn process_initialize_raw(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+ 
+    let counter_account = next_account_info(accounts_iter)?;
+    let payer_account = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    require_not_executable(counter_account)?;
+    require_writable(counter_account)?;
+ 
+    if data.len() > CounterAccount::LEN {
+        return Err(CounterError::RawDataTooLarge.into());
+    }
+    if CounterAccount::layout_version(data) != CounterAccount::VERSION {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let counter_data =
+        CounterAccount::unpack(data).map_err(|_| ProgramError::InvalidInstructionData)?;
+    counter_data.validate_config()?;
+ 
+    create_counter_account(
+        program_id,
+        accounts,
+        counter_account,
+        payer_account,
+        system_program,
+        data.len(),
+        &[],
+    )?;
+    counter_account.data.borrow_mut().copy_from_slice(data);
+    counter_data.return_state_hash(&[])?;
+    counter_data.emit_event(CounterEvent::Initialized {
+        counter: *counter_account.key,
+        authority: counter_data.authority,
+        count: counter_data.count,
+    })?;
+ 
+    msg!(
+        "Counter initialized from raw data with value: {}",
+        counter_data.display_amount()
+    );
+    Ok(())
+}
+ 
+// Create a rent-exempt, program-owned account of `account_space` bytes,
+// signing with `signer_seeds` when the counter is a PDA. A PDA's address is
+// known in advance, so anyone may fund it first; lamports already there are
+// kept and only the shortfall is transferred before allocating and assigning,
+// as create_account would refuse the address.
+fn create_program_account<'a>(
+    program_id: &Pubkey,
+    account: &AccountInfo<'a>,
+    payer_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    account_space: usize,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    // Calculate minimum balance for rent exemption
+    let required_lamports = Rent::get()?.minimum_balance(account_space);
+    if account.lamports() == 0 {
+        return invoke_signed(
+            &system_instruction::create_account(
+                payer_account.key,    // Account paying for the new account
+                account.key,          // Account to be created
+                required_lamports,    // Amount of lamports to transfer to the new account
+                account_space as u64, // Size in bytes to allocate for the data field
+                program_id,           // Set program owner to our program
+            ),
+            &[
+                payer_account.clone(),
+                account.clone(),
+                system_program.clone(),
+            ],
+            signer_seeds,
+        );
+    }
+ 
+    let shortfall = required_lamports.saturating_sub(account.lamports());
+    if shortfall > 0 {
+        invoke(
+            &system_instruction::transfer(payer_account.key, account.key, shortfall),
+            &[
+                payer_account.clone(),
+                account.clone(),
+                system_program.clone(),
+            ],
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::allocate(account.key, account_space as u64),
+        &[account.clone(), system_program.clone()],
+        signer_seeds,
+    )?;
+    invoke_signed(
+        &system_instruction::assign(account.key, program_id),
+        &[account.clone(), system_program.clone()],
+        signer_seeds,
+    )
+}
+ 
+// Create a counter account as create_program_account does, first charging the
+// payer the configured creation fee. Every initializer creates its account
+// here, so none of them skips the fee.
+fn create_counter_account<'a>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo<'a>],
+    counter_account: &AccountInfo<'a>,
+    payer_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    account_space: usize,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    charge_creation_fee(program_id, accounts, payer_account, system_program)?;
+    create_program_account(
+        program_id,
+        counter_account,
+        payer_account,
+        system_program,
+        account_space,
+        signer_seeds,
+    )
+}
+ 
+// Transfer the configured creation fee from the payer to the treasury. The
+// config PDA is an optional trailing account, so the original account lists
+// keep working: without it, or until the config is created, there is no fee.
+// The treasury need only be passed while a fee is set.
+fn charge_creation_fee<'a>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo<'a>],
+    payer_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+) -> ProgramResult {
+    let Some(config) = GlobalConfig::find(program_id, accounts)? else {
+        return Ok(());
+    };
+    if config.creation_fee == 0 {
+        return Ok(());
+    }
+    let treasury_account = accounts
+        .iter()
+        .find(|account| *account.key == config.treasury)
+        .ok_or(ProgramError::NotEnoughAccountKeys)?;
+    invoke(
+        &system_instruction::transfer(payer_account.key, treasury_account.key, config.creation_fee),
+        &[
+            payer_account.clone(),
+            treasury_account.clone(),
+            system_program.clone(),
+        ],
+    )?;
+ 
+    msg!("Creation fee paid: {}", config.creation_fee);
+    Ok(())
+}
+ 
+// Initialize a counter and increment it once in the same instruction, storing
+// `initial_value + 1`. Accounts match InitializeCounter; the return data is
+// that of the increment.
+fn process_initialize_and_increment(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    initial_value: u64,
+) -> ProgramResult {
+    process_initialize_counter(program_id, accounts, initial_value)?;
+    process_increment_counter(program_id, accounts)
+}
+ 
+// Grow a legacy count-only account to the current layout
+fn process_migrate_counter(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+ 
+    let counter_account = next_account_info(accounts_iter)?;
+    let payer_account = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    require_not_executable(counter_account)?;
+    require_writable(counter_account)?;
+ 
+    // Verify account ownership
+    require_program_owned(program_id, counter_account)?;
+ 
+    // Legacy accounts were created from a keypair, which is the only proof of control
+    if !counter_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if counter_account.data_len() != CounterAccount::LEGACY_LEN {
+        return Err(CounterError::AlreadyMigrated.into());
+    }
+ 
+    // Read the count stored by the legacy layout
+    let count = u64::try_from_slice(&counter_account.data.borrow())?;
+ 
+    // Grow the account, keeping it rent-exempt at the new size
+    realloc_rent_exempt(
+        counter_account,
+        payer_account,
+        system_program,
+        CounterAccount::LEN,
+    )?;
+ 
+    // Rewrite the account with the current layout, owned by the payer
+    let counter_data = CounterAccount::new(count, *payer_account.key, Clock::get()?.unix_timestamp);
+    counter_data.store(&mut counter_account.data.borrow_mut())?;
+    counter_data.return_state_hash(&[])?;
+ 
+    msg!("Counter migrated with value: {}", count);
+    Ok(())
+}
+ 
+// Resize an account, first topping up its lamports so it stays rent-exempt
+fn realloc_rent_exempt<'a>(
+    account: &AccountInfo<'a>,
+    payer_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    new_len: usize,
+) -> ProgramResult {
+    let new_min = Rent::get()?.minimum_balance(new_len);
+    let shortfall = new_min.saturating_sub(account.lamports());
+    if shortfall > 0 {
+        if payer_account.lamports() < shortfall {
+            return Err(ProgramError::InsufficientFunds);
+        }
+        invoke(
+            &system_instruction::transfer(payer_account.key, account.key, shortfall),
+            &[
+                payer_account.clone(),
+                account.clone(),
+                system_program.clone(),
+            ],
+        )?;
+    }
+    account.realloc(new_len, true)
+}
+ 
+// Update an existing counter's value
+fn process_increment_counter(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let mut saturated = false;
+    let counter_data = process_count_update(program_id, accounts, |counter_data| {
+        // Increment the counter value by its step, clamping at max_value
+        // instead of failing when the counter saturates
+        match counter_data.count.checked_add(counter_data.step) {
+            Some(count) if count <= counter_data.max_value => counter_data.count = count,
+            _ if counter_data.saturating => {
+                counter_data.count = counter_data.max_value;
+                saturated = true;
+            }
+            Some(_) => return Err(CounterError::MaxValueExceeded.into()),
+            None => return Err(CounterError::Overflow.into()),
+        }
+        Ok(())
+    })?;
+ 
+    // Report the remaining headroom below max_value as 8 LE bytes, followed in
+    // saturating mode by a byte flagging whether this call was clamped
+    let headroom = counter_data.max_value - counter_data.count;
+    let mut prefix = headroom.to_le_bytes().to_vec();
+    if counter_data.saturating {
+        prefix.push(u8::from(saturated));
+    }
+    counter_data.return_state_hash(&prefix)?;
+ 
+    msg!("Counter incremented to: {}", counter_data.display_amount());
+    msg!("Counter headroom: {}", headroom);
+    Ok(())
+}
+ 
+// Increment by the counter's step, recording a client-supplied timestamp that
+// must move strictly forward so replayed or reordered events are rejected. It
+// may not run ahead of the clock by more than MAX_CLIENT_TS_SKEW_SECS, or a
+// far-future stamp would hold the cooldown forever.
+fn process_increment_at(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    client_ts: i64,
+) -> ProgramResult {
+    let now = Clock::get()?.unix_timestamp;
+    if client_ts > now.saturating_add(CounterAccount::MAX_CLIENT_TS_SKEW_SECS) {
+        return Err(CounterError::TimestampInFuture.into());
+    }
+    let counter_data = process_count_update(program_id, accounts, |counter_data| {
+        if client_ts <= counter_data.last_updated {
+            return Err(CounterError::TimestampRegression.into());
+        }
+        counter_data.count = CounterMath(counter_data.count).checked_add(counter_data.step)?;
+        if counter_data.count > counter_data.max_value {
+            return Err(CounterError::MaxValueExceeded.into());
+        }
+        counter_data.last_updated = client_ts;
+        Ok(())
+    })?;
+    counter_data.return_state_hash(&[])?;
+ 
+    msg!(
+        "Counter incremented to: {} at {}",
+        counter_data.display_amount(),
+        client_ts
+    );
+    Ok(())
+}
+ 
+// Increment by the counter's step once the authority has approved it off-chain:
+// the preceding instruction must be an ed25519 verification of
+// `increment:<counter>:<nonce>` by the authority. The nonce moves on with
+// every update, so each approval is good for one increment. The instructions
+// sysvar comes last, after the counter and any required signer.
+fn process_increment_with_approval(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let counter_account = accounts.first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+    let instructions_sysvar = load_instructions_sysvar(accounts)?;
+    let counter_data = process_count_update(program_id, accounts, |counter_data| {
+        let message = format!("increment:{}:{}", counter_account.key, counter_data.nonce);
+        check_ed25519_approval(
+            instructions_sysvar,
+            &counter_data.authority,
+            message.as_bytes(),
+        )?;
+        counter_data.count = CounterMath(counter_data.count).checked_add(counter_data.step)?;
+        if counter_data.count > counter_data.max_value {
+            return Err(CounterError::MaxValueExceeded.into());
+        }
+        Ok(())
+    })?;
+    counter_data.return_state_hash(&[])?;
+ 
+    msg!(
+        "Counter incremented with approval to: {}",
+        counter_data.display_amount()
+    );
+    Ok(())
+}
+ 
+// Units kept back for storing the counter and logging once the loop ends
+#[cfg(target_os = "solana")]
+const INCREMENT_TIMES_RESERVE_CU: u64 = 20_000;
+// Iterations between compute checks, so the check itself stays cheap
+const COMPUTE_CHECK_INTERVAL: u32 = 64;
+ 
+// Apply the counter's step `n` times, stopping with ComputeBudgetExhausted
+// while there is still budget to fail cleanly, so the partial work reverts
+// with a typed error instead of the runtime's compute abort
+fn process_increment_times(program_id: &Pubkey, accounts: &[AccountInfo], n: u32) -> ProgramResult {
+    let counter_data = process_count_update(program_id, accounts, |counter_data| {
+        for done in 0..n {
+            if done % COMPUTE_CHECK_INTERVAL == 0 && compute_running_low() {
+                return Err(CounterError::ComputeBudgetExhausted.into());
+            }
+            counter_data.count = CounterMath(counter_data.count).checked_add(counter_data.step)?;
+            if counter_data.count > counter_data.max_value {
+                return Err(CounterError::MaxValueExceeded.into());
+            }
+        }
+        Ok(())
+    })?;
+    counter_data.return_state_hash(&[])?;
+ 
+    msg!(
+        "Counter incremented {} times to: {}",
+        n,
+        counter_data.display_amount()
+    );
+    Ok(())
+}
+ 
+// Whether the instruction is down to its last INCREMENT_TIMES_RESERVE_CU
+// compute units. Native builds, such as program-test builtins, have no meter
+// to read and never run low.
+fn compute_running_low() -> bool {
+    #[cfg(target_os = "solana")]
+    {
+        solana_program::compute_units::sol_remaining_compute_units() < INCREMENT_TIMES_RESERVE_CU
+    }
+    #[cfg(not(target_os = "solana"))]
+    {
+        false
+    }
+}
+ 
+// Increment by the counter's step for a signer holding at least
+// gate_min_amount of gate_mint. The token account and its signing owner are
+// the last two accounts; only their data is inspected, with no CPI.
+fn process_increment_if_holder(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let [_, .., token_account, holder_account] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    if !holder_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    assert_owned_by(token_account, &TokenHolding::PROGRAM_ID)?;
+    let holding = TokenHolding::unpack(&token_account.data.borrow())?;
+ 
+    let counter_data = process_count_update(program_id, accounts, |counter_data| {
+        let holds_enough = counter_data.gate_mint != Pubkey::default()
+            && holding.mint == counter_data.gate_mint
+            && holding.owner == *holder_account.key
+            && holding.amount >= counter_data.gate_min_amount;
+        if !holds_enough {
+            return Err(CounterError::NotTokenHolder.into());
+        }
+        counter_data.count = CounterMath(counter_data.count).checked_add(counter_data.step)?;
+        if counter_data.count > counter_data.max_value {
+            return Err(CounterError::MaxValueExceeded.into());
+        }
+        Ok(())
+    })?;
+    counter_data.return_state_hash(&[])?;
+ 
+    msg!(
+        "Counter incremented by holder {} to: {}",
+        holder_account.key,
+        counter_data.display_amount()
+    );
+    Ok(())
+}
+ 
+// Increment by step for a member of the counter's Merkle allowlist. Accounts:
+// the counter, followed last by the member, who signs and whose key hashes to
+// `leaf`.
+fn process_increment_if_in_tree(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    proof: &[[u8; 32]],
+    leaf: &[u8; 32],
+) -> ProgramResult {
+    let [_, .., member_account] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    if !member_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if proof.len() > MAX_PROOF_LEN {
+        return Err(CounterError::ProofTooLong.into());
+    }
+    if *leaf != merkle_leaf(member_account.key) {
+        return Err(CounterError::NotInAllowlist.into());
+    }
+ 
+    let counter_data = process_count_update(program_id, accounts, |counter_data| {
+        counter_data.check_in_tree(leaf, proof)?;
+        counter_data.count = CounterMath(counter_data.count).checked_add(counter_data.step)?;
+        if counter_data.count > counter_data.max_value {
+            return Err(CounterError::MaxValueExceeded.into());
+        }
+        Ok(())
+    })?;
+    counter_data.return_state_hash(&[])?;
+ 
+    msg!(
+        "Counter incremented by allowlisted {} to: {}",
+        member_account.key,
+        counter_data.display_amount()
+    );
+    Ok(())
+}
+ 
+// Increment an existing counter by a caller-chosen amount, bounded per call
+fn process_increment_by(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+) -> ProgramResult {
+    let counter_data = process_count_update(program_id, accounts, |counter_data| {
+        if amount > counter_data.max_step_per_call {
+            return Err(CounterError::StepTooLarge.into());
+        }
+        counter_data.count = CounterMath(counter_data.count).checked_add(amount)?;
+        if counter_data.count > counter_data.max_value {
+            return Err(CounterError::MaxValueExceeded.into());
+        }
+        Ok(())
+    })?;
+ 
+    // Report the remaining headroom below max_value, as IncrementCounter does
+    let headroom = counter_data.max_value - counter_data.count;
+    counter_data.return_state_hash(&headroom.to_le_bytes())?;
+ 
+    msg!(
+        "Counter incremented by {} to: {}",
+        amount,
+        counter_data.display_amount()
+    );
+    Ok(())
+}
+ 
+// Grow the counter by `bps` basis points, truncating the increase toward zero
+fn process_increment_percent(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    bps: u16,
+) -> ProgramResult {
+    if bps > CounterAccount::MAX_INCREMENT_BPS {
+        return Err(CounterError::BpsTooLarge.into());
+    }
+    let counter_data = process_count_update(program_id, accounts, |counter_data| {
+        counter_data.count = CounterMath(counter_data.count).checked_add_bps(bps)?;
+        if counter_data.count > counter_data.max_value {
+            return Err(CounterError::MaxValueExceeded.into());
+        }
+        Ok(())
+    })?;
+    counter_data.return_state_hash(&[])?;
+ 
+    msg!(
+        "Counter grew by {} bps to: {}",
+        bps,
+        counter_data.display_amount()
+    );
+    Ok(())
+}
+ 
+// Accumulate a sample into the running sum so clients can derive the mean
+fn process_add_sample(program_id: &Pubkey, accounts: &[AccountInfo], value: u64) -> ProgramResult {
+    let counter_data = process_count_update(program_id, accounts, |counter_data| {
+        counter_data.sum = counter_data
+            .sum
+            .checked_add(u128::from(value))
+            .ok_or(CounterError::Overflow)?;
+        counter_data.samples = counter_data
+            .samples
+            .checked_add(1)
+            .ok_or(CounterError::Overflow)?;
+        Ok(())
+    })?;
+    counter_data.return_state_hash(&[])?;
+ 
+    msg!(
+        "Counter sample {} added, sum: {}, samples: {}",
+        value,
+        counter_data.sum,
+        counter_data.samples
+    );
+    Ok(())
+}
+ 
+// Reset the count to its floor once `period_secs` have passed since the last
+// update. Before then this succeeds without changes, or fails with NotElapsed
+// when `require_elapsed` is set.
+fn process_reset_if_elapsed(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    period_secs: u32,
+    require_elapsed: bool,
+) -> ProgramResult {
+    let counter_account = accounts.first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+    require_program_owned(program_id, counter_account)?;
+    let last_updated = CounterAccount::load(&counter_account.data.borrow())?.last_updated;
+    let elapsed = Clock::get()?.unix_timestamp.saturating_sub(last_updated);
+    if elapsed < i64::from(period_secs) {
+        if require_elapsed {
+            return Err(CounterError::NotElapsed.into());
+        }
+        msg!("Counter reset period has not elapsed, nothing to do");
+        return Ok(());
+    }
+ 
+    let counter_data = process_count_update(program_id, accounts, |counter_data| {
+        counter_data.count = counter_data.min_value;
+        Ok(())
+    })?;
+    counter_data.return_state_hash(&[])?;
+ 
+    msg!("Counter reset after {} seconds", elapsed);
+    Ok(())
+}
+ 
+// Increment by the step only if the previous IncrementFresh landed at most
+// `max_age_slots` slots ago, then record the current slot as
+// last_increment_slot. A counter with no recorded slot always accepts the
+// first call, and other instructions leave the slot alone, so a chain of
+// fresh increments breaks once a gap grows past the caller's bound.
+fn process_increment_fresh(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    max_age_slots: u64,
+) -> ProgramResult {
+    let slot = Clock::get()?.slot;
+    let counter_data = process_count_update(program_id, accounts, |counter_data| {
+        let age = slot.saturating_sub(counter_data.last_increment_slot);
+        if counter_data.last_increment_slot != 0 && age > max_age_slots {
+            return Err(CounterError::IncrementStale.into());
+        }
+        counter_data.count = CounterMath(counter_data.count).checked_add(counter_data.step)?;
+        if counter_data.count > counter_data.max_value {
+            return Err(CounterError::MaxValueExceeded.into());
+        }
+        counter_data.last_increment_slot = slot;
+        Ok(())
+    })?;
+    counter_data.return_state_hash(&[])?;
+ 
+    msg!("Counter incremented at slot {}", slot);
+    msg!("Counter incremented to: {}", counter_data.display_amount());
+    Ok(())
+}
+ 
+// Increment by the step and move `lamports` from the tipper into the counter,
+// adding them to total_tips. Accounts: the counter, the tipper, who signs and
+// also counts as the counter's signer, and the system program.
+fn process_increment_with_tip(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    lamports: u64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let tipper_account = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    if !tipper_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+ 
+    let counter_data = process_count_update(program_id, accounts, |counter_data| {
+        counter_data.count = CounterMath(counter_data.count).checked_add(counter_data.step)?;
+        if counter_data.count > counter_data.max_value {
+            return Err(CounterError::MaxValueExceeded.into());
+        }
+        counter_data.total_tips = counter_data
+            .total_tips
+            .checked_add(lamports)
+            .ok_or(CounterError::Overflow)?;
+        Ok(())
+    })?;
+    if lamports > 0 {
+        invoke(
+            &system_instruction::transfer(tipper_account.key, counter_account.key, lamports),
+            &[
+                tipper_account.clone(),
+                counter_account.clone(),
+                system_program.clone(),
+            ],
+        )?;
+    }
+    counter_data.return_state_hash(&[])?;
+ 
+    msg!("Counter tipped {} lamports", lamports);
+    msg!("Counter incremented to: {}", counter_data.display_amount());
+    Ok(())
+}
+ 
+// Create the counter at 1 on first use and increment it afterwards. Creation
+// takes the payer and system program after the counter; incrementing does not.
+fn process_touch(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let counter_account = accounts.first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+    if counter_account.owner == program_id && !counter_account.data_is_empty() {
+        process_increment_counter(program_id, accounts)
+    } else {
+        process_initialize_counter(program_id, accounts, 1)
+    }
+}
+ 
+// Overwrite the count with the current slot, recording when the counter was
+// last touched
+fn process_stamp_slot(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let slot = Clock::get()?.slot;
+    let counter_data = process_count_update(program_id, accounts, |counter_data| {
+        if slot > counter_data.max_value {
+            return Err(CounterError::MaxValueExceeded.into());
+        }
+        counter_data.count = slot;
+        Ok(())
+    })?;
+    counter_data.return_state_hash(&[])?;
+ 
+    msg!("Counter stamped with slot: {}", slot);
+    Ok(())
+}
+ 
+// Decrement an existing counter's value, flooring at min_value (zero by
+// default) instead of erroring
+fn process_decrement_saturating(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let counter_data = process_count_update(program_id, accounts, |counter_data| {
+        // Decrement the counter value, staying at the floor rather than underflowing
+        counter_data.count = counter_data
+            .count
+            .saturating_sub(1)
+            .max(counter_data.min_value);
+        Ok(())
+    })?;
+    counter_data.return_state_hash(&[])?;
+ 
+    msg!("Counter decremented to: {}", counter_data.display_amount());
+    close_if_drained(&counter_data, accounts)
+}
+ 
+// Decrement an existing counter's value, recording why it dropped
+fn process_decrement_with_reason(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    reason: u8,
+) -> ProgramResult {
+    let counter_data = process_count_update(program_id, accounts, |counter_data| {
+        counter_data.count = CounterMath(counter_data.count).checked_sub(1)?;
+        counter_data.last_reason = reason;
+        Ok(())
+    })?;
+    counter_data.return_state_hash(&[])?;
+ 
+    msg!("counter:decrement reason={}", reason);
+    msg!("Counter decremented to: {}", counter_data.display_amount());
+    close_if_drained(&counter_data, accounts)
+}
+ 
+// Decrement by one and report the progress made down from max_value
+fn process_decrement_with_progress(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let counter_data = process_count_update(program_id, accounts, |counter_data| {
+        counter_data.count = CounterMath(counter_data.count).checked_sub(1)?;
+        Ok(())
+    })?;
+ 
+    // Report `max_value - count` as 8 LE bytes, zero if count is not below max
+    let progress = counter_data.max_value.saturating_sub(counter_data.count);
+    counter_data.return_state_hash(&progress.to_le_bytes())?;
+ 
+    msg!("Counter decremented to: {}", counter_data.display_amount());
+    msg!("Counter progress: {}", progress);
+    close_if_drained(&counter_data, accounts)
+}
+ 
+// Decrement by `amount`, stopping at the floor (zero unless min_value is set)
+// instead of failing, and return the part of `amount` left unapplied as 8 LE
+// bytes
+fn process_decrement_with_remainder(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+) -> ProgramResult {
+    let mut remainder = 0;
+    let counter_data = process_count_update(program_id, accounts, |counter_data| {
+        let applied = amount.min(counter_data.count.saturating_sub(counter_data.min_value));
+        remainder = amount - applied;
+        counter_data.count = CounterMath(counter_data.count).checked_sub(applied)?;
+        Ok(())
+    })?;
+    counter_data.return_state_hash(&remainder.to_le_bytes())?;
+ 
+    msg!(
+        "Counter decremented to: {}, {} unapplied",
+        counter_data.display_amount(),
+        remainder
+    );
+    close_if_drained(&counter_data, accounts)
+}
+ 
+// Decrement by `amount`, counting it against daily_limit for the 24h window
+// that began at day_start. A window that has run out starts afresh at `now`.
+fn process_decrement_daily(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+) -> ProgramResult {
+    let now = Clock::get()?.unix_timestamp;
+    let counter_data = process_count_update(program_id, accounts, |counter_data| {
+        if now.saturating_sub(counter_data.day_start) >= CounterAccount::DAY_SECS {
+            counter_data.day_start = now;
+            counter_data.daily_spent = 0;
+        }
+        counter_data.daily_spent = counter_data
+            .daily_spent
+            .checked_add(amount)
+            .filter(|spent| *spent <= counter_data.daily_limit)
+            .ok_or(CounterError::DailyLimitExceeded)?;
+        counter_data.count = CounterMath(counter_data.count).checked_sub(amount)?;
+        Ok(())
+    })?;
+    counter_data.return_state_hash(&[])?;
+ 
+    msg!(
+        "Counter decremented by {}, {} spent today",
+        amount,
+        counter_data.daily_spent
+    );
+    msg!("Counter decremented to: {}", counter_data.display_amount());
+    close_if_drained(&counter_data, accounts)
+}
+ 
+// Close a counter a decrement left at zero when it may not rest there,
+// refunding its rent to the authority, which must be among `accounts`
+fn close_if_drained(counter_data: &CounterAccount, accounts: &[AccountInfo]) -> ProgramResult {
+    let counter_account = accounts.first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+    close_counter_if_drained(counter_account, counter_data, accounts)
+}
+ 
+// As close_if_drained, for a counter that need not come first in `accounts`
+fn close_counter_if_drained(
+    counter_account: &AccountInfo,
+    counter_data: &CounterAccount,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if counter_data.allow_zero || counter_data.count > 0 {
+        return Ok(());
+    }
+    let authority_account = accounts
+        .iter()
+        .find(|account| account.key == &counter_data.authority)
+        .ok_or(ProgramError::NotEnoughAccountKeys)?;
+    require_writable(authority_account)?;
+ 
+    // As with CloseCounter, no configured reserve survives the close
+    if counter_data.min_reserve > 0 {
+        return Err(CounterError::ReserveBreached.into());
+    }
+    close_account(counter_account, authority_account)?;
+ 
+    msg!("Counter reached zero and closed");
+    Ok(())
+}
+ 
+// Add a signed `delta` to the count, covering both directions in one
+// instruction
+fn process_apply_delta(program_id: &Pubkey, accounts: &[AccountInfo], delta: i64) -> ProgramResult {
+    let counter_data = process_count_update(program_id, accounts, |counter_data| {
+        counter_data.count = CounterMath(counter_data.count).checked_add_signed(delta)?;
+        if counter_data.count > counter_data.max_value {
+            return Err(CounterError::MaxValueExceeded.into());
+        }
+        Ok(())
+    })?;
+    counter_data.return_state_hash(&[])?;
+ 
+    msg!("Counter moved by {}", delta);
+    msg!("Counter updated to: {}", counter_data.display_amount());
+    close_if_drained(&counter_data, accounts)
+}
+ 
+// Set `count = count * factor + addend`, checking each step for overflow
+fn process_mul_add(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    factor: u64,
+    addend: u64,
+) -> ProgramResult {
+    let counter_data = process_count_update(program_id, accounts, |counter_data| {
+        counter_data.count = CounterMath(counter_data.count).checked_mul_add(factor, addend)?;
+        if counter_data.count > counter_data.max_value {
+            return Err(CounterError::MaxValueExceeded.into());
+        }
+        Ok(())
+    })?;
+    counter_data.return_state_hash(&[])?;
+ 
+    msg!("Counter multiplied by {} plus {}", factor, addend);
+    msg!("Counter updated to: {}", counter_data.display_amount());
+    Ok(())
+}
+ 
+// Exchange the counts of two counters. Each counter's authority signs; a
+// shared authority may be passed once.
+fn process_swap_counters(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let first_account = next_account_info(accounts_iter)?;
+    let second_account = next_account_info(accounts_iter)?;
+    let first_authority = next_account_info(accounts_iter)?;
+    let second_authority = accounts_iter.next().unwrap_or(first_authority);
+    for counter_account in [first_account, second_account] {
+        require_not_executable(counter_account)?;
+        require_writable(counter_account)?;
+        require_program_owned(program_id, counter_account)?;
+    }
+    if first_account.key == second_account.key {
+        return Err(ProgramError::InvalidArgument);
+    }
+ 
+    let mut first_data_ref = first_account.data.borrow_mut();
+    let mut second_data_ref = second_account.data.borrow_mut();
+    let mut first_data = CounterAccount::load(&first_data_ref)?;
+    let mut second_data = CounterAccount::load(&second_data_ref)?;
+    first_data.check_authority(first_authority)?;
+    second_data.check_authority(second_authority)?;
+ 
+    let now = Clock::get()?.unix_timestamp;
+    let (first_count, second_count) = (first_data.count, second_data.count);
+    for (counter_data, count) in [
+        (&mut first_data, second_count),
+        (&mut second_data, first_count),
+    ] {
+        counter_data.check_not_frozen()?;
+        counter_data.check_unlocked(now)?;
+        if count > counter_data.max_value {
+            return Err(CounterError::MaxValueExceeded.into());
+        }
+        if count < counter_data.min_value {
+            return Err(CounterError::BelowMinValue.into());
+        }
+        let previous_count = counter_data.count;
+        counter_data.count = count;
+        counter_data.record_growth(previous_count)?;
+        counter_data.last_updated = now;
+        counter_data.apply_auto_freeze();
+        counter_data.bump_nonce();
+    }
+    first_data.store(&mut first_data_ref)?;
+    second_data.store(&mut second_data_ref)?;
+ 
+    for (counter_account, counter_data) in
+        [(first_account, &first_data), (second_account, &second_data)]
+    {
+        counter_data.emit_event(CounterEvent::CountChanged {
+            counter: *counter_account.key,
+            count: counter_data.count,
+        })?;
+    }
+ 
+    msg!("Counters swapped: {} <-> {}", first_count, second_count);
+    Ok(())
+}
+ 
+// Take `amount` off the first counter, drawing any shortfall below its floor
+// from the second. Accounts match SwapCounters: both counters, then the first
+// authority and, when it differs, the second.
+fn process_decrement_with_borrow(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let first_account = next_account_info(accounts_iter)?;
+    let second_account = next_account_info(accounts_iter)?;
+    let first_authority = next_account_info(accounts_iter)?;
+    let second_authority = accounts_iter.next().unwrap_or(first_authority);
+    for counter_account in [first_account, second_account] {
+        require_not_executable(counter_account)?;
+        require_writable(counter_account)?;
+        require_program_owned(program_id, counter_account)?;
+    }
+    if first_account.key == second_account.key {
+        return Err(ProgramError::InvalidArgument);
+    }
+ 
+    let mut first_data_ref = first_account.data.borrow_mut();
+    let mut second_data_ref = second_account.data.borrow_mut();
+    let mut first_data = CounterAccount::load(&first_data_ref)?;
+    let mut second_data = CounterAccount::load(&second_data_ref)?;
+    first_data.check_authority(first_authority)?;
+    second_data.check_authority(second_authority)?;
+ 
+    // The first counter gives what it holds above its floor, the second the rest
+    let from_first = amount.min(first_data.count.saturating_sub(first_data.min_value));
+    let shortfall = amount - from_first;
+    if shortfall > second_data.count.saturating_sub(second_data.min_value) {
+        return Err(CounterError::InsufficientAcrossBuckets.into());
+    }
+ 
+    let now = Clock::get()?.unix_timestamp;
+    for (counter_data, taken) in [(&mut first_data, from_first), (&mut second_data, shortfall)] {
+        if taken == 0 {
+            continue;
+        }
+        counter_data.check_not_frozen()?;
+        counter_data.check_unlocked(now)?;
+        counter_data.check_cooldown(now)?;
+        let previous_count = counter_data.count;
+        counter_data.count -= taken;
+        counter_data.record_growth(previous_count)?;
+        counter_data.last_updated = now;
+        counter_data.apply_auto_freeze();
+        counter_data.bump_nonce();
+    }
+    first_data.store(&mut first_data_ref)?;
+    second_data.store(&mut second_data_ref)?;
+    drop(first_data_ref);
+    drop(second_data_ref);
+ 
+    for (counter_account, counter_data) in
+        [(first_account, &first_data), (second_account, &second_data)]
+    {
+        counter_data.emit_event(CounterEvent::CountChanged {
+            counter: *counter_account.key,
+            count: counter_data.count,
+        })?;
+    }
+ 
+    msg!(
+        "Counter decremented by {}, borrowing {} from the second counter",
+        amount,
+        shortfall
+    );
+    for (counter_account, counter_data, taken) in [
+        (first_account, &first_data, from_first),
+        (second_account, &second_data, shortfall),
+    ] {
+        if taken > 0 {
+            close_counter_if_drained(counter_account, counter_data, accounts)?;
+        }
+    }
+    Ok(())
+}
+ 
+// Append the counter's count and the current slot to its history PDA, creating
+// the PDA on the first checkpoint. The authority signs and pays for the growth.
+fn process_checkpoint(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let history_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    require_program_owned(program_id, counter_account)?;
+    require_writable(history_account)?;
+ 
+    let counter_data = CounterAccount::load(&counter_account.data.borrow())?;
+    counter_data.check_authority(authority_account)?;
+    let (history_address, bump) = HistoryEntry::find_address(program_id, counter_account.key);
+    if *history_account.key != history_address {
+        return Err(ProgramError::InvalidSeeds);
+    }
+ 
+    let offset = history_account.data_len();
+    if history_account.owner != program_id {
+        let bump_seed = [bump];
+        let signer_seeds = seeds::with_bump(&seeds::history_seeds(counter_account.key), &bump_seed);
+        create_program_account(
+            program_id,
+            history_account,
+            authority_account,
+            system_program,
+            HistoryEntry::LEN,
+            &[&signer_seeds],
+        )?;
+    } else if offset / HistoryEntry::LEN >= HistoryEntry::MAX_ENTRIES {
+        return Err(CounterError::HistoryFull.into());
+    } else {
+        realloc_rent_exempt(
+            history_account,
+            authority_account,
+            system_program,
+            offset + HistoryEntry::LEN,
+        )?;
+    }
+ 
+    let entry = HistoryEntry {
+        count: counter_data.count,
+        slot: Clock::get()?.slot,
+    };
+    let bytes = borsh::to_vec(&entry).map_err(|_| ProgramError::InvalidAccountData)?;
+    history_account
+        .data
+        .borrow_mut()
+        .get_mut(offset..)
+        .ok_or(ProgramError::AccountDataTooSmall)?
+        .copy_from_slice(&bytes);
+ 
+    msg!("Checkpointed count {} at slot {}", entry.count, entry.slot);
+    Ok(())
+}
+ 
+// Add a counter's value to another counter, then close it and refund its rent
+// to the signing authority
+fn process_close_and_transfer(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let source_account = next_account_info(accounts_iter)?;
+    let destination_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+    require_not_executable(source_account)?;
+    require_writable(source_account)?;
+    require_not_executable(destination_account)?;
+    require_writable(destination_account)?;
+    require_writable(authority_account)?;
+ 
+    // Both counters must belong to this program and be distinct
+    require_program_owned(program_id, source_account)?;
+    require_program_owned(program_id, destination_account)?;
+    if source_account.key == destination_account.key {
+        return Err(ProgramError::InvalidArgument);
+    }
+ 
+    // Only the source's authority may close it
+    let source_data = CounterAccount::load(&source_account.data.borrow())?;
+    source_data.check_authority(authority_account)?;
+ 
+    // The destination keeps its own signer, cooldown and bound checks
+    let mut data = destination_account.data.borrow_mut();
+    let mut destination_data = CounterAccount::load(&data)?;
+    destination_data.check_signer(accounts_iter)?;
+    destination_data.check_not_frozen()?;
+    let now = Clock::get()?.unix_timestamp;
+    destination_data.check_unlocked(now)?;
+    destination_data.check_cooldown(now)?;
+    let previous_count = destination_data.count;
+    destination_data.count = CounterMath(destination_data.count).checked_add(source_data.count)?;
+    destination_data.record_growth(previous_count)?;
+    if destination_data.count > destination_data.max_value {
+        return Err(CounterError::MaxValueExceeded.into());
+    }
+    destination_data.last_updated = now;
+    destination_data.apply_auto_freeze();
+    destination_data.bump_nonce();
+    destination_data.store(&mut data)?;
+    drop(data);
+ 
+    // Closing drains everything, which no configured reserve survives
+    if source_data.min_reserve > 0 {
+        return Err(CounterError::ReserveBreached.into());
+    }
+    close_account(source_account, authority_account)?;
+    destination_data.return_state_hash(&[])?;
+    destination_data.emit_event(CounterEvent::CountChanged {
+        counter: *destination_account.key,
+        count: destination_data.count,
+    })?;
+ 
+    msg!(
+        "Counter closed, transferring {} to destination",
+        source_data.count
+    );
+    msg!("Counter updated to: {}", destination_data.display_amount());
+    Ok(())
+}
+ 
+// Send lamports held by a counter to its authority, keeping rent plus the
+// configured reserve behind
+fn process_withdraw(program_id: &Pubkey, accounts: &[AccountInfo], lamports: u64) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+    require_not_executable(counter_account)?;
+    require_writable(counter_account)?;
+    require_writable(authority_account)?;
+ 
+    require_program_owned(program_id, counter_account)?;
+    let counter_data = CounterAccount::load(&counter_account.data.borrow())?;
+    counter_data.check_authority(authority_account)?;
+ 
+    let remaining = counter_account
+        .lamports()
+        .checked_sub(lamports)
+        .ok_or(ProgramError::InsufficientFunds)?;
+    counter_data.check_reserve(remaining, counter_account.data_len())?;
+    move_lamports(counter_account, authority_account, lamports)?;
+ 
+    msg!("Withdrew {} lamports from counter", lamports);
+    Ok(())
+}
+ 
+// Close a counter, refunding its rent to the optional third account or, when
+// none is passed, to the signing authority
+fn process_close_counter(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+    let destination_account = accounts_iter.next().unwrap_or(authority_account);
+    require_not_executable(counter_account)?;
+    require_writable(counter_account)?;
+    require_writable(destination_account)?;
+ 
+    require_program_owned(program_id, counter_account)?;
+    let counter_data = CounterAccount::load(&counter_account.data.borrow())?;
+    counter_data.check_authority(authority_account)?;
+    if counter_account.key == destination_account.key {
+        return Err(ProgramError::InvalidArgument);
+    }
+ 
+    // Closing drains everything, which no configured reserve survives
+    if counter_data.min_reserve > 0 {
+        return Err(CounterError::ReserveBreached.into());
+    }
+    close_account(counter_account, destination_account)?;
+ 
+    msg!(
+        "Counter closed, rent refunded to {}",
+        destination_account.key
+    );
+    Ok(())
+}
+ 
+// Set a metadata entry on a counter, creating its metadata PDA on first use
+fn process_set_meta(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    key: String,
+    value: String,
+) -> ProgramResult {
+    process_meta_update(program_id, accounts, |metadata| {
+        metadata.set(key.clone(), value)
+    })?;
+    msg!("Counter metadata set: {}", key);
+    Ok(())
+}
+ 
+// Remove a metadata entry from a counter; a missing key changes nothing
+fn process_remove_meta(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    key: String,
+) -> ProgramResult {
+    process_meta_update(program_id, accounts, |metadata| metadata.remove(&key))?;
+    msg!("Counter metadata removed: {}", key);
+    Ok(())
+}
+ 
+// Apply a change to a counter's metadata and resize its PDA to fit. Accounts
+// match Checkpoint: the counter, its metadata PDA, the authority, who signs
+// and pays for growth, and the system program.
+fn process_meta_update(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    update: impl FnOnce(&mut CounterMetadata),
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let meta_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    require_program_owned(program_id, counter_account)?;
+    require_writable(meta_account)?;
+ 
+    let counter_data = CounterAccount::load(&counter_account.data.borrow())?;
+    counter_data.check_authority(authority_account)?;
+    let (meta_address, bump) = CounterMetadata::find_address(program_id, counter_account.key);
+    if *meta_account.key != meta_address {
+        return Err(ProgramError::InvalidSeeds);
+    }
+ 
+    let exists = meta_account.owner == program_id;
+    let mut metadata = if exists {
+        CounterMetadata::try_from_slice(&meta_account.data.borrow())?
+    } else {
+        CounterMetadata::default()
+    };
+    update(&mut metadata);
+    let bytes = borsh::to_vec(&metadata).map_err(|_| ProgramError::InvalidAccountData)?;
+    if bytes.len() > CounterMetadata::MAX_LEN {
+        return Err(CounterError::MetadataTooLarge.into());
+    }
+ 
+    if exists {
+        realloc_rent_exempt(meta_account, authority_account, system_program, bytes.len())?;
+    } else if metadata.entries.is_empty() {
+        // Nothing to store, so there is no need to create the account
+        return Ok(());
+    } else {
+        let bump_seed = [bump];
+        let signer_seeds = seeds::with_bump(&seeds::meta_seeds(counter_account.key), &bump_seed);
+        create_program_account(
+            program_id,
+            meta_account,
+            authority_account,
+            system_program,
+            bytes.len(),
+            &[&signer_seeds],
+        )?;
+    }
+    meta_account.data.borrow_mut().copy_from_slice(&bytes);
+ 
+    msg!("Counter metadata holds {} entries", metadata.entries.len());
+    Ok(())
+}
+ 
+// Close every counter at zero among `accounts` and send their rent to one
+// destination, skipping counters that still hold a count. Accounts: the
+// authority of the swept counters, who signs, the destination, then the
+// counters.
+fn process_sweep_closed(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let authority_account = next_account_info(accounts_iter)?;
+    let destination_account = next_account_info(accounts_iter)?;
+    require_writable(destination_account)?;
+    let counter_accounts = accounts_iter.as_slice();
+    require_valid_batch(counter_accounts)?;
+ 
+    let mut closed = 0;
+    for counter_account in counter_accounts {
+        require_not_executable(counter_account)?;
+        require_writable(counter_account)?;
+        require_program_owned(program_id, counter_account)?;
+        if counter_account.key == destination_account.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+        let counter_data = CounterAccount::load(&counter_account.data.borrow())?;
+        if counter_data.count != 0 {
+            continue;
+        }
+        counter_data.check_authority(authority_account)?;
+ 
+        // As with CloseCounter, no configured reserve survives the close
+        if counter_data.min_reserve > 0 {
+            return Err(CounterError::ReserveBreached.into());
+        }
+        close_account(counter_account, destination_account)?;
+        closed += 1;
+    }
+ 
+    msg!(
+        "Swept {} empty counters, rent sent to {}",
+        closed,
+        destination_account.key
+    );
+    Ok(())
+}
+ 
+// Move all of an account's lamports to `recipient` and hand the emptied
+// account back to the system program
+fn close_account(account: &AccountInfo, recipient: &AccountInfo) -> ProgramResult {
+    move_lamports(account, recipient, account.lamports())?;
+    account.realloc(0, false)?;
+    account.assign(&system_program::id());
+    Ok(())
+}
+ 
+// Move `lamports` from a program-owned account to `recipient`
+fn move_lamports(account: &AccountInfo, recipient: &AccountInfo, lamports: u64) -> ProgramResult {
+    let remaining = account
+        .lamports()
+        .checked_sub(lamports)
+        .ok_or(ProgramError::InsufficientFunds)?;
+    let credited = recipient
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    **account.try_borrow_mut_lamports()? = remaining;
+    **recipient.try_borrow_mut_lamports()? = credited;
+    Ok(())
+}
+ 
+// Apply a change to an existing counter's value, returning the stored state
+fn process_count_update(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    update: impl FnOnce(&mut CounterAccount) -> ProgramResult,
+) -> Result<CounterAccount, ProgramError> {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    require_not_executable(counter_account)?;
+    require_writable(counter_account)?;
+ 
+    // Verify account ownership
+    require_program_owned(program_id, counter_account)?;
+ 
+    // Mutable borrow the account data
+    let mut data = counter_account.data.borrow_mut();
+ 
+    // Deserialize the account data into our CounterAccount struct
+    let mut counter_data = CounterAccount::load(&data)?;
+ 
+    // Verify the authority or delegate signed when the counter requires it
+    counter_data.check_signer(accounts_iter)?;
+    counter_data.check_not_frozen()?;
+ 
+    // Enforce the time lock and the cooldown between updates
+    let now = Clock::get()?.unix_timestamp;
+    counter_data.check_unlocked(now)?;
+    counter_data.check_cooldown(now)?;
+ 
+    let previous_count = counter_data.count;
+    let previous_updated = counter_data.last_updated;
+    update(&mut counter_data)?;
+    if counter_data.count < counter_data.min_value {
+        return Err(CounterError::BelowMinValue.into());
+    }
+    counter_data.record_growth(previous_count)?;
+    // Handlers that stamp their own time keep it; the rest record the clock
+    if counter_data.last_updated == previous_updated {
+        counter_data.last_updated = now;
+    }
+    counter_data.apply_auto_freeze();
+    counter_data.bump_nonce();
+ 
+    // Serialize the updated counter data back into the account
+    counter_data.store(&mut data)?;
+    counter_data.emit_event(CounterEvent::CountChanged {
+        counter: *counter_account.key,
+        count: counter_data.count,
+    })?;
+    Ok(counter_data)
+}
+ 
+// Set the PDA, typically owned by another program, allowed to sign increments
+fn process_set_delegate(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    delegate_pda: Pubkey,
+) -> ProgramResult {
+    process_authority_update(program_id, accounts, |counter_data| {
+        counter_data.delegate_pda = delegate_pda;
+        msg!("Counter delegate set to: {}", delegate_pda);
+        Ok(())
+    })
+}
+ 
+// List one more delegate allowed to sign count updates
+fn process_add_delegate(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    delegate: Pubkey,
+) -> ProgramResult {
+    process_authority_update(program_id, accounts, |counter_data| {
+        counter_data.add_delegate(delegate)?;
+        msg!("Counter delegate added: {}", delegate);
+        Ok(())
+    })
+}
+ 
+// Drop a listed delegate
+fn process_remove_delegate(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    delegate: Pubkey,
+) -> ProgramResult {
+    process_authority_update(program_id, accounts, |counter_data| {
+        counter_data.remove_delegate(delegate)?;
+        msg!("Counter delegate removed: {}", delegate);
+        Ok(())
+    })
+}
+ 
+// Toggle whether increments and decrements need an authorized signer
+fn process_set_require_signer(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    require_signer: bool,
+) -> ProgramResult {
+    process_authority_update(program_id, accounts, |counter_data| {
+        counter_data.require_signer = require_signer;
+        msg!("Counter require_signer set to: {}", require_signer);
+        Ok(())
+    })
+}
+ 
+// Set the ceiling that increments may not exceed
+fn process_set_max_value(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    max_value: u64,
+) -> ProgramResult {
+    process_authority_update(program_id, accounts, |counter_data| {
+        if max_value < counter_data.count {
+            return Err(CounterError::MaxValueBelowCount.into());
+        }
+        counter_data.max_value = max_value;
+        msg!("Counter max_value set to: {}", max_value);
+        Ok(())
+    })
+}
+ 
+// Set the largest amount a single IncrementBy may add
+fn process_set_max_step_per_call(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    max_step_per_call: u64,
+) -> ProgramResult {
+    process_authority_update(program_id, accounts, |counter_data| {
+        counter_data.max_step_per_call = max_step_per_call;
+        msg!("Counter max_step_per_call set to: {}", max_step_per_call);
+        Ok(())
+    })
+}
+ 
+// Set the lamports a counter must keep above rent through withdrawals
+fn process_set_min_reserve(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    min_reserve: u64,
+) -> ProgramResult {
+    process_authority_update(program_id, accounts, |counter_data| {
+        counter_data.min_reserve = min_reserve;
+        msg!("Counter min_reserve set to: {}", min_reserve);
+        Ok(())
+    })
+}
+ 
+// Change the step and advance the counter by the new step in one move
+fn process_set_step_and_tick(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    step: u64,
+) -> ProgramResult {
+    process_authority_update(program_id, accounts, |counter_data| {
+        if step == 0 {
+            return Err(CounterError::InvalidStep.into());
+        }
+        counter_data.step = step;
+        counter_data.count = CounterMath(counter_data.count).checked_add(step)?;
+        if counter_data.count > counter_data.max_value {
+            return Err(CounterError::MaxValueExceeded.into());
+        }
+        msg!(
+            "Counter step set to {} and incremented to: {}",
+            step,
+            counter_data.display_amount()
+        );
+        Ok(())
+    })
+}
+ 
+// Choose whether reaching max_value freezes the counter
+fn process_set_auto_freeze(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    auto_freeze_at_max: bool,
+) -> ProgramResult {
+    process_authority_update(program_id, accounts, |counter_data| {
+        counter_data.auto_freeze_at_max = auto_freeze_at_max;
+        msg!("Counter auto_freeze_at_max set to: {}", auto_freeze_at_max);
+        Ok(())
+    })
+}
+ 
+// Choose whether increments clamp at max_value rather than failing
+fn process_set_saturating(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    saturating: bool,
+) -> ProgramResult {
+    process_authority_update(program_id, accounts, |counter_data| {
+        counter_data.saturating = saturating;
+        msg!("Counter saturating set to: {}", saturating);
+        Ok(())
+    })
+}
+ 
+// Choose whether a counter may rest at zero or closes when decremented to it
+fn process_set_allow_zero(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    allow_zero: bool,
+) -> ProgramResult {
+    process_authority_update(program_id, accounts, |counter_data| {
+        counter_data.allow_zero = allow_zero;
+        msg!("Counter allow_zero set to: {}", allow_zero);
+        Ok(())
+    })
+}
+ 
+// Name the key that may take over as authority; the current authority keeps
+// control until that key accepts. Proposing the default key withdraws it.
+fn process_propose_authority(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    new_authority: Pubkey,
+) -> ProgramResult {
+    process_authority_update(program_id, accounts, |counter_data| {
+        counter_data.pending_authority = new_authority;
+        msg!("Counter authority proposed: {}", new_authority);
+        Ok(())
+    })
+}
+ 
+// Complete a proposed transfer, signed by the pending authority, which becomes
+// the authority while the pending field is cleared
+fn process_accept_authority(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let pending_account = next_account_info(accounts_iter)?;
+    require_not_executable(counter_account)?;
+    require_writable(counter_account)?;
+    require_program_owned(program_id, counter_account)?;
+ 
+    let mut data = counter_account.data.borrow_mut();
+    let mut counter_data = CounterAccount::load(&data)?;
+    if !pending_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let pending_authority = counter_data.pending_authority;
+    if pending_authority == Pubkey::default() || pending_authority != *pending_account.key {
+        return Err(CounterError::Unauthorized.into());
+    }
+    counter_data.check_not_frozen()?;
+ 
+    counter_data.authority = pending_authority;
+    counter_data.pending_authority = Pubkey::default();
+    counter_data.bump_nonce();
+    counter_data.store(&mut data)?;
+    counter_data.return_state_hash(&[])?;
+ 
+    msg!("Counter authority accepted by: {}", pending_authority);
+    Ok(())
+}
+ 
+// Gate IncrementIfHolder on holding `min_amount` of `gate_mint`; the default
+// mint leaves the gate closed
+fn process_set_token_gate(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    gate_mint: Pubkey,
+    min_amount: u64,
+) -> ProgramResult {
+    process_authority_update(program_id, accounts, |counter_data| {
+        counter_data.gate_mint = gate_mint;
+        counter_data.gate_min_amount = min_amount;
+        msg!("Counter token gate set to {} of {}", min_amount, gate_mint);
+        Ok(())
+    })
+}
+ 
+// Restrict IncrementIfInTree to the members of the allowlist with this root
+fn process_set_merkle_root(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    merkle_root: [u8; 32],
+) -> ProgramResult {
+    process_authority_update(program_id, accounts, |counter_data| {
+        counter_data.merkle_root = merkle_root;
+        msg!("Counter merkle_root set to: {:?}", merkle_root);
+        Ok(())
+    })
+}
+ 
+// Set how much DecrementDaily may take off the counter per 24h window
+fn process_set_daily_limit(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    daily_limit: u64,
+) -> ProgramResult {
+    process_authority_update(program_id, accounts, |counter_data| {
+        counter_data.daily_limit = daily_limit;
+        msg!("Counter daily_limit set to: {}", daily_limit);
+        Ok(())
+    })
+}
+ 
+// Set the slot a countdown on this counter runs out at
+fn process_set_expiry(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    expires_at_slot: u64,
+) -> ProgramResult {
+    process_authority_update(program_id, accounts, |counter_data| {
+        counter_data.expires_at_slot = expires_at_slot;
+        msg!("Counter expires at slot: {}", expires_at_slot);
+        Ok(())
+    })
+}
+ 
+// Hold the count where it is until the clock reaches `unlock_ts`
+fn process_lock_until(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    unlock_ts: i64,
+) -> ProgramResult {
+    process_authority_update(program_id, accounts, |counter_data| {
+        counter_data.locked_until = unlock_ts;
+        msg!("Counter locked until: {}", unlock_ts);
+        Ok(())
+    })
+}
+ 
+// Switch the counter's structured `sol_log_data` events on or off
+fn process_set_emit_events(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    emit_events: bool,
+) -> ProgramResult {
+    process_authority_update(program_id, accounts, |counter_data| {
+        counter_data.emit_events = emit_events;
+        msg!("Counter emit_events set to: {}", emit_events);
+        Ok(())
+    })
+}
+ 
+// Rewrite the stored bump of a counter PDA with the canonical one, repairing
+// counters a faulty client initialized. Accounts: the counter, its authority
+// and, optionally, the key the PDA was derived from: the creating authority
+// of a counter whose authority has since moved, or the mint of a mint
+// counter. Without it the current authority is taken as the seed.
+fn process_refresh_bump(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let counter_account = accounts.first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+    let seed_key = accounts.get(2).map(|seed_account| *seed_account.key);
+    process_authority_update(program_id, accounts, |counter_data| {
+        let seed_key = seed_key.unwrap_or(counter_data.authority);
+        let mut derived = CounterAccount::find_pda(program_id, &seed_key);
+        if derived.0 != *counter_account.key {
+            derived = CounterAccount::find_mint_pda(program_id, &seed_key);
+        }
+        let (counter_address, bump) = derived;
+        if *counter_account.key != counter_address {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        if counter_data.bump != bump {
+            msg!(
+                "Counter bump refreshed from {} to {}",
+                counter_data.bump,
+                bump
+            );
+            counter_data.bump = bump;
+        }
+        Ok(())
+    })
+}
+ 
+// Apply a template counter's configuration to the target. Accounts: the
+// target, its authority, then the template, which is only read.
+fn process_copy_config_from(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let template_account = accounts.get(2).ok_or(ProgramError::NotEnoughAccountKeys)?;
+    require_program_owned(program_id, template_account)?;
+    let template = CounterAccount::load(&template_account.data.borrow())?;
+ 
+    process_authority_update(program_id, accounts, |counter_data| {
+        counter_data.copy_config_from(&template)?;
+        msg!(
+            "Counter configuration copied from: {}",
+            template_account.key
+        );
+        Ok(())
+    })
+}
+ 
+// Overwrite the count within its bounds, reporting the count it replaced as
+// 8 LE bytes ahead of the state hash, compare-and-set style
+fn process_set_counter(program_id: &Pubkey, accounts: &[AccountInfo], value: u64) -> ProgramResult {
+    let mut previous_count = 0;
+    let counter_data = apply_authority_update(program_id, accounts, |counter_data| {
+        if value > counter_data.max_value {
+            return Err(CounterError::MaxValueExceeded.into());
+        }
+        if value < counter_data.min_value {
+            return Err(CounterError::BelowMinValue.into());
+        }
+        previous_count = counter_data.count;
+        counter_data.count = value;
+        Ok(())
+    })?;
+    counter_data.return_state_hash(&previous_count.to_le_bytes())?;
+ 
+    msg!("Counter set from {} to {}", previous_count, value);
+    Ok(())
+}
+ 
+// Create a multi-counter with every slot at zero. Accounts: the new account,
+// signing, the payer, who signs and becomes its authority, and the system
+// program.
+fn process_initialize_multi_counter(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+ 
+    let multi_account = next_account_info(accounts_iter)?;
+    let payer_account = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    require_not_executable(multi_account)?;
+    require_writable(multi_account)?;
+ 
+    create_counter_account(
+        program_id,
+        accounts,
+        multi_account,
+        payer_account,
+        system_program,
+        MultiCounter::LEN,
+        &[],
+    )?;
+    MultiCounter::new(*payer_account.key).store(&mut multi_account.data.borrow_mut())?;
+ 
+    msg!(
+        "Multi-counter initialized with {} slots",
+        MultiCounter::SLOTS
+    );
+    Ok(())
+}
+ 
+// Increment one slot of a multi-counter. Accounts: the multi-counter.
+fn process_increment_slot(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    slot: u8,
+) -> ProgramResult {
+    let multi_account = accounts.first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+    let mut multi_data = load_multi_counter(program_id, multi_account)?;
+    let count = multi_data.slot_mut(slot)?;
+    *count = CounterMath(*count).checked_add(1)?;
+    let count = *count;
+    multi_data.store(&mut multi_account.data.borrow_mut())?;
+ 
+    msg!("Slot {} incremented to: {}", slot, count);
+    Ok(())
+}
+ 
+// Overwrite one slot of a multi-counter. Accounts: the multi-counter and its
+// authority.
+fn process_set_slot(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    slot: u8,
+    value: u64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let multi_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+    let mut multi_data = load_multi_counter(program_id, multi_account)?;
+    if !authority_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if multi_data.authority != *authority_account.key {
+        return Err(CounterError::Unauthorized.into());
+    }
+    *multi_data.slot_mut(slot)? = value;
+    multi_data.store(&mut multi_account.data.borrow_mut())?;
+ 
+    msg!("Slot {} set to: {}", slot, value);
+    Ok(())
+}
+ 
+// Load a writable multi-counter owned by this program
+fn load_multi_counter(
+    program_id: &Pubkey,
+    multi_account: &AccountInfo,
+) -> Result<MultiCounter, ProgramError> {
+    require_not_executable(multi_account)?;
+    require_writable(multi_account)?;
+    require_program_owned(program_id, multi_account)?;
+    MultiCounter::unpack(&multi_account.data.borrow())
+}
+ 
+// Set the count to the counter account's lamports above its rent-exempt
+// minimum, so the counter mirrors the SOL deposited into it
+fn process_sync_from_lamports(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let counter_account = accounts.first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+    let surplus = counter_account
+        .lamports()
+        .saturating_sub(Rent::get()?.minimum_balance(counter_account.data_len()));
+    process_authority_update(program_id, accounts, |counter_data| {
+        if surplus > counter_data.max_value {
+            return Err(CounterError::MaxValueExceeded.into());
+        }
+        if surplus < counter_data.min_value {
+            return Err(CounterError::BelowMinValue.into());
+        }
+        counter_data.count = surplus;
+        msg!("Counter synced to lamport surplus: {}", surplus);
+        Ok(())
+    })
+}
+ 
+// Reset the count to zero, returning the count it held as 8 LE bytes so
+// consumers read and clear in one step. A counter with a min_value above zero
+// cannot be drained.
+fn process_drain_counter(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let mut drained = 0;
+    let counter_data = apply_authority_update(program_id, accounts, |counter_data| {
+        if counter_data.min_value > 0 {
+            return Err(CounterError::BelowMinValue.into());
+        }
+        drained = counter_data.count;
+        counter_data.count = 0;
+        Ok(())
+    })?;
+    counter_data.return_state_hash(&drained.to_le_bytes())?;
+ 
+    msg!("Counter drained of: {}", drained);
+    close_if_drained(&counter_data, accounts)
+}
+ 
+// Reset the count to its floor, zero unless min_value is set, keeping the
+// lifetime total
+fn process_reset_counter(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    process_authority_update(program_id, accounts, |counter_data| {
+        counter_data.count = counter_data.min_value;
+        msg!(
+            "Counter reset, lifetime total: {}",
+            counter_data.lifetime_total
+        );
+        Ok(())
+    })
+}
+ 
+// Tag the counter with a category for off-chain grouping
+fn process_set_category(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    category: u16,
+) -> ProgramResult {
+    process_authority_update(program_id, accounts, |counter_data| {
+        counter_data.category = category;
+        msg!("Counter category set to: {}", category);
+        Ok(())
+    })
+}
+ 
+// Update step, max_value and cooldown together, leaving `None` fields untouched
+fn process_configure(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    step: Option<u64>,
+    max_value: Option<u64>,
+    cooldown_secs: Option<u32>,
+) -> ProgramResult {
+    process_authority_update(program_id, accounts, |counter_data| {
+        if let Some(step) = step {
+            if step == 0 {
+                return Err(CounterError::InvalidStep.into());
+            }
+            counter_data.step = step;
+            msg!("Counter step set to: {}", step);
+        }
+        if let Some(max_value) = max_value {
+            if max_value < counter_data.count {
+                return Err(CounterError::MaxValueBelowCount.into());
+            }
+            counter_data.max_value = max_value;
+            msg!("Counter max_value set to: {}", max_value);
+        }
+        if let Some(cooldown_secs) = cooldown_secs {
+            counter_data.cooldown_secs = cooldown_secs;
+            msg!("Counter cooldown set to: {}s", cooldown_secs);
+        }
+        Ok(())
+    })
+}
+ 
+// Report layout version, ownership, size and rent status without mutating
+fn process_describe_counter(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+ 
+    let data_len = counter_account.data_len();
+    let counter_data = CounterAccount::unpack(&counter_account.data.borrow()).ok();
+    let description = CounterDescription {
+        version: CounterAccount::layout_version(&counter_account.data.borrow()),
+        owner_matches: counter_account.owner == program_id,
+        data_len: data_len as u64,
+        rent_exempt: Rent::get()?.is_exempt(counter_account.lamports(), data_len),
+        nonce: counter_data.as_ref().map(|counter_data| counter_data.nonce),
+        category: counter_data
+            .as_ref()
+            .map(|counter_data| counter_data.category),
+    };
+    set_return_data_bounded(&borsh::to_vec(&description)?)?;
+ 
+    msg!(
+        "Counter version: {}, owner matches: {}, data length: {}, rent exempt: {}, nonce: {:?}, category: {:?}",
+        description.version,
+        description.owner_matches,
+        description.data_len,
+        description.rent_exempt,
+        description.nonce,
+        description.category
+    );
+    Ok(())
+}
+ 
+// Return a counter's count and lamport balance as 16 LE bytes, in that order
+fn process_query_account(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    require_program_owned(program_id, counter_account)?;
+ 
+    let counter_data = CounterAccount::load(&counter_account.data.borrow())?;
+    let lamports = counter_account.lamports();
+    set_return_data_bounded(&[counter_data.count.to_le_bytes(), lamports.to_le_bytes()].concat())?;
+ 
+    msg!(
+        "Counter count: {}, lamports: {}",
+        counter_data.count,
+        lamports
+    );
+    Ok(())
+}
+ 
+// Return the counts of every passed counter as consecutive 8-byte LE values, in
+// account order. Return data caps this at 128 counters.
+fn process_bulk_query(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let mut counts = Vec::with_capacity(accounts.len() * RETURN_U64_LEN);
+    for counter_account in accounts {
+        require_program_owned(program_id, counter_account)?;
+        let counter_data = CounterAccount::load(&counter_account.data.borrow())?;
+        counts.extend_from_slice(&counter_data.count.to_le_bytes());
+    }
+    set_return_data_bounded(&counts)?;
+ 
+    msg!("Queried {} counters", accounts.len());
+    Ok(())
+}
+ 
+// Increment every counter in `accounts` by its step. Each is updated on its
+// own, so counters that require a signer cannot take part.
+fn process_batch_increment(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    require_valid_batch(accounts)?;
+    for counter_account in accounts {
+        let counter_data = process_count_update(
+            program_id,
+            std::slice::from_ref(counter_account),
+            |counter_data| {
+                counter_data.count =
+                    CounterMath(counter_data.count).checked_add(counter_data.step)?;
+                if counter_data.count > counter_data.max_value {
+                    return Err(CounterError::MaxValueExceeded.into());
+                }
+                Ok(())
+            },
+        )?;
+        msg!(
+            "Counter {} incremented to: {}",
+            counter_account.key,
+            counter_data.display_amount()
+        );
+    }
+    Ok(())
+}
+ 
+// Reject a batch of more than MAX_BATCH counters, or one that lists the same
+// account more than once, which would apply the update to it twice
+fn require_valid_batch(accounts: &[AccountInfo]) -> ProgramResult {
+    if accounts.len() > MAX_BATCH {
+        return Err(CounterError::BatchTooLarge.into());
+    }
+    let mut seen = Vec::with_capacity(accounts.len());
+    for account in accounts {
+        if seen.contains(&account.key) {
+            return Err(CounterError::DuplicateAccountInBatch.into());
+        }
+        seen.push(account.key);
+    }
+    Ok(())
+}
+ 
+// Report, as a single 0/1 byte, whether incrementing by `amount` would pass
+// max_value or u64::MAX, without touching the counter
+fn process_check_increment_by(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+) -> ProgramResult {
+    let counter_account = accounts.first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+    require_program_owned(program_id, counter_account)?;
+    let counter_data = CounterAccount::load(&counter_account.data.borrow())?;
+    // Option::is_none_or needs Rust 1.82, newer than the SBF toolchain
+    #[allow(clippy::unnecessary_map_or)]
+    let would_overflow = counter_data
+        .count
+        .checked_add(amount)
+        .map_or(true, |count| count > counter_data.max_value);
+    set_return_data_bounded(&[u8::from(would_overflow)])?;
+ 
+    msg!("Increment by {} would overflow: {}", amount, would_overflow);
+    Ok(())
+}
+ 
+// Return the slots left before expires_at_slot as 8 LE bytes, zero once it
+// has passed
+fn process_slots_remaining(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let counter_account = accounts.first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+    require_program_owned(program_id, counter_account)?;
+    let counter_data = CounterAccount::load(&counter_account.data.borrow())?;
+    let remaining = counter_data
+        .expires_at_slot
+        .saturating_sub(Clock::get()?.slot);
+    set_return_data_bounded(&remaining.to_le_bytes())?;
+ 
+    msg!("Counter slots remaining: {}", remaining);
+    Ok(())
+}
+ 
+// Return the seconds since the counter last changed as 8 LE bytes of i64,
+// clamped to zero when the clock reads earlier than last_updated
+fn process_query_staleness(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let counter_account = accounts.first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+    require_program_owned(program_id, counter_account)?;
+    let counter_data = CounterAccount::load(&counter_account.data.borrow())?;
+    let now = Clock::get()?.unix_timestamp;
+    let staleness = now.saturating_sub(counter_data.last_updated).max(0);
+    set_return_data_bounded(&staleness.to_le_bytes())?;
+ 
+    msg!("Counter staleness: {} seconds", staleness);
+    Ok(())
+}
+ 
+// Return the rent-exempt minimum for an account of `space` bytes as 8 LE bytes
+fn process_query_rent(space: u16) -> ProgramResult {
+    let minimum_balance = Rent::get()?.minimum_balance(usize::from(space));
+    set_return_data_bounded(&minimum_balance.to_le_bytes())?;
+ 
+    msg!(
+        "Rent-exempt minimum for {} bytes: {}",
+        space,
+        minimum_balance
+    );
+    Ok(())
+}
+ 
+// Return a CounterMetrics snapshot of the counter for monitoring
+fn process_metrics(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    require_program_owned(program_id, counter_account)?;
+ 
+    let counter_data = CounterAccount::load(&counter_account.data.borrow())?;
+    let metrics = CounterMetrics {
+        count: counter_data.count,
+        nonce: counter_data.nonce,
+        increment_count: counter_data.increment_count,
+        last_updated: counter_data.last_updated,
+        is_frozen: counter_data.is_frozen,
+    };
+    set_return_data_bounded(&borsh::to_vec(&metrics)?)?;
+ 
+    msg!("Counter metrics: {:?}", metrics);
+    Ok(())
+}
+ 
+// Do nothing beyond a single log, for padding or compute budgeting
+fn process_noop() -> ProgramResult {
+    msg!("counter:noop");
+    Ok(())
+}
+ 
+// Engage or lift the global kill-switch, creating the config PDA on first use
+fn process_set_global_pause(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    paused: bool,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+ 
+    let config_account = next_account_info(accounts_iter)?;
+    let upgrade_authority = next_account_info(accounts_iter)?;
+    let program_data = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    let bump = check_config_update(program_id, config_account, upgrade_authority, program_data)?;
+ 
+    // Create the config account the first time the switch is used, with the
+    // upgrade authority as its admin; afterwards the stored settings are kept
+    let mut config = if config_account.data_is_empty() {
+        create_config_account(
+            program_id,
+            config_account,
+            upgrade_authority,
+            system_program,
+            bump,
+        )?;
+        GlobalConfig::new(bump, *upgrade_authority.key)
+    } else {
+        GlobalConfig::try_from_slice(&config_account.data.borrow())?
+    };
+    config.paused = paused;
+    config.serialize(&mut &mut config_account.data.borrow_mut()[..])?;
+ 
+    msg!("Global pause set to: {}", paused);
+    Ok(())
+}
+ 
+// Create the config PDA naming `admin`, unpaused. Accounts match
+// SetGlobalPause: the config PDA, the upgrade authority, who signs and pays,
+// the program's ProgramData account and the system program.
+fn process_initialize_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    admin: Pubkey,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+ 
+    let config_account = next_account_info(accounts_iter)?;
+    let upgrade_authority = next_account_info(accounts_iter)?;
+    let program_data = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    let bump = check_config_update(program_id, config_account, upgrade_authority, program_data)?;
+    if !config_account.data_is_empty() {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+    create_config_account(
+        program_id,
+        config_account,
+        upgrade_authority,
+        system_program,
+        bump,
+    )?;
+ 
+    GlobalConfig::new(bump, admin).serialize(&mut &mut config_account.data.borrow_mut()[..])?;
+ 
+    msg!("Config initialized with admin: {}", admin);
+    Ok(())
+}
+ 
+// Set the fee every initializer charges on top of rent and the treasury that
+// receives it. Accounts: the config PDA, its admin and, while the fee is above
+// zero, the treasury, which must be able to receive the transfer.
+fn process_set_creation_fee(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    creation_fee: u64,
+    treasury: Pubkey,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+ 
+    let config_account = next_account_info(accounts_iter)?;
+    let admin_account = next_account_info(accounts_iter)?;
+    require_writable(config_account)?;
+    require_program_owned(program_id, config_account)?;
+    if *config_account.key != GlobalConfig::find_address(program_id).0 {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    let mut config = GlobalConfig::try_from_slice(&config_account.data.borrow())?;
+ 
+    // Only the config's admin may price counter creation
+    if !admin_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if config.admin != *admin_account.key {
+        return Err(CounterError::Unauthorized.into());
+    }
+ 
+    // A treasury that can't be credited would fail every initializer's transfer
+    if creation_fee > 0 {
+        if treasury == Pubkey::default() {
+            return Err(CounterError::InvalidTreasury.into());
+        }
+        let treasury_account = next_account_info(accounts_iter)?;
+        if *treasury_account.key != treasury {
+            return Err(ProgramError::InvalidArgument);
+        }
+        if treasury_account.executable {
+            return Err(CounterError::InvalidTreasury.into());
+        }
+    }
+ 
+    config.creation_fee = creation_fee;
+    config.treasury = treasury;
+    config.serialize(&mut &mut config_account.data.borrow_mut()[..])?;
+ 
+    msg!("Creation fee set to {}, paid to {}", creation_fee, treasury);
+    Ok(())
+}
+ 
+// Require the program's upgrade authority to sign a change to the canonical
+// config PDA, returning the PDA's bump
+fn check_config_update(
+    program_id: &Pubkey,
+    config_account: &AccountInfo,
+    upgrade_authority: &AccountInfo,
+    program_data: &AccountInfo,
+) -> Result<u8, ProgramError> {
+    require_writable(config_account)?;
+ 
+    // Only the program's upgrade authority may change the config
+    if !upgrade_authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if load_upgrade_authority(program_id, program_data)? != Some(*upgrade_authority.key) {
+        return Err(CounterError::Unauthorized.into());
+    }
+ 
+    // Verify the config account is the canonical config PDA
+    let (config_address, bump) = GlobalConfig::find_address(program_id);
+    if *config_account.key != config_address {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(bump)
+}
+ 
+// Create the config PDA funded by `payer_account`
+fn create_config_account<'a>(
+    program_id: &Pubkey,
+    config_account: &AccountInfo<'a>,
+    payer_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    bump: u8,
+) -> ProgramResult {
+    let bump_seed = [bump];
+    create_program_account(
+        program_id,
+        config_account,
+        payer_account,
+        system_program,
+        GlobalConfig::LEN,
+        &[&seeds::with_bump(&seeds::config_seeds(), &bump_seed)],
+    )
+}
+ 
+// Fail cleanly when an account the handler writes to was passed read-only
+fn require_writable(account: &AccountInfo) -> ProgramResult {
+    if !account.is_writable {
+        msg!("Account {} must be writable", account.key);
+        return Err(CounterError::AccountNotWritable.into());
+    }
+    Ok(())
+}
+ 
+// Require a counter owned by this program, calling out system-owned accounts
+// that were never initialized
+fn require_program_owned(program_id: &Pubkey, account: &AccountInfo) -> ProgramResult {
+    if system_program::check_id(account.owner) {
+        msg!(
+            "Account {} is not a counter yet, initialize it first",
+            account.key
+        );
+        return Err(CounterError::NotInitialized.into());
+    }
+    assert_owned_by(account, program_id)
+}
+ 
+// Fail with IncorrectProgramId, logging both owners, unless `owner` owns `account`
+fn assert_owned_by(account: &AccountInfo, owner: &Pubkey) -> Result<(), ProgramError> {
+    if account.owner != owner {
+        msg!(
+            "Account {} is owned by {}, expected {}",
+            account.key,
+            account.owner,
+            owner
+        );
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
+ 
+// Refuse program accounts as counters; their data is never ours to write
+fn require_not_executable(account: &AccountInfo) -> ProgramResult {
+    if account.executable {
+        msg!("Account {} is executable", account.key);
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(())
+}
+ 
+// Take the instructions sysvar that introspecting instructions pass as their
+// last account, refusing any other account in that slot
+pub(crate) fn load_instructions_sysvar<'a, 'b>(
+    accounts: &'a [AccountInfo<'b>],
+) -> Result<&'a AccountInfo<'b>, ProgramError> {
+    let sysvar_account = accounts.last().ok_or(ProgramError::NotEnoughAccountKeys)?;
+    if !instructions::check_id(sysvar_account.key) {
+        return Err(CounterError::InvalidInstructionsSysvar.into());
+    }
+    Ok(sysvar_account)
+}
+ 
+// Require the instruction before the current one to be an ed25519 precompile
+// verification of `message` signed by `signer`. The runtime fails the whole
+// transaction on a bad precompile signature, so only what was signed, and by
+// whom, is left to check.
+fn check_ed25519_approval(
+    instructions_sysvar: &AccountInfo,
+    signer: &Pubkey,
+    message: &[u8],
+) -> ProgramResult {
+    let verify = get_instruction_relative(-1, instructions_sysvar)
+        .map_err(|_| CounterError::ApprovalMissing)?;
+    if verify.program_id != ed25519_program::id() {
+        return Err(CounterError::ApprovalMissing.into());
+    }
+ 
+    // A count byte and a padding byte, then one set of u16 offsets whose
+    // instruction indexes must all point into this same instruction
+    let data = verify.data.as_slice();
+    let field = |index: usize| {
+        let at = 2 + index * 2;
+        data.get(at..at + 2)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(|bytes: [u8; 2]| usize::from(u16::from_le_bytes(bytes)))
+    };
+    let mut offsets = [0; 7];
+    for (index, offset) in offsets.iter_mut().enumerate() {
+        *offset = field(index).ok_or(CounterError::ApprovalMissing)?;
+    }
+    let [_, signature_ix, pubkey_at, pubkey_ix, message_at, message_len, message_ix] = offsets;
+    let this_instruction = usize::from(u16::MAX);
+    let signed_here = [signature_ix, pubkey_ix, message_ix]
+        .iter()
+        .all(|index| *index == this_instruction);
+    if data.first() != Some(&1) || !signed_here {
+        return Err(CounterError::ApprovalMissing.into());
+    }
+ 
+    let signed_by = data.get(pubkey_at..pubkey_at + 32);
+    let signed_message = data.get(message_at..message_at + message_len);
+    if signed_by != Some(signer.as_ref()) || signed_message != Some(message) {
+        return Err(CounterError::ApprovalMissing.into());
+    }
+    Ok(())
+}
+ 
+// Read the upgrade authority recorded in the program's ProgramData account
+fn load_upgrade_authority(
+    program_id: &Pubkey,
+    program_data: &AccountInfo,
+) -> Result<Option<Pubkey>, ProgramError> {
+    let (program_data_address, _) =
+        Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+    if *program_data.key != program_data_address
+        || *program_data.owner != bpf_loader_upgradeable::id()
+    {
+        return Err(ProgramError::InvalidAccountData);
+    }
+ 
+    // ProgramData metadata is bincode: u32 variant (3), u64 slot, Option<Pubkey>
+    let data = program_data.data.borrow();
+    let Some([3, 0, 0, 0, metadata @ ..]) =
+        data.get(..UpgradeableLoaderState::size_of_programdata_metadata())
+    else {
+        return Err(ProgramError::InvalidAccountData);
+    };
+ 
+    // Skip the deployment slot to reach the optional authority
+    match metadata.get(8..) {
+        Some([0, ..]) => Ok(None),
+        Some([1, key @ ..]) => Ok(Some(
+            Pubkey::try_from(key).map_err(|_| ProgramError::InvalidAccountData)?,
+        )),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+ 
+// Apply an authority-only change to an existing counter
+fn process_authority_update(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    update: impl FnOnce(&mut CounterAccount) -> ProgramResult,
+) -> ProgramResult {
+    apply_authority_update(program_id, accounts, update)?.return_state_hash(&[])
+}
+ 
+// Apply an authority-only change, returning the stored state so the caller
+// can choose its return data
+fn apply_authority_update(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    update: impl FnOnce(&mut CounterAccount) -> ProgramResult,
+) -> Result<CounterAccount, ProgramError> {
+    let accounts_iter = &mut accounts.iter();
+    let counter_account = next_account_info(accounts_iter)?;
+    let authority_account = next_account_info(accounts_iter)?;
+    require_not_executable(counter_account)?;
+    require_writable(counter_account)?;
+ 
+    // Verify account ownership
+    require_program_owned(program_id, counter_account)?;
+ 
+    // Mutable borrow the account data
+    let mut data = counter_account.data.borrow_mut();
+ 
+    // Deserialize the account data into our CounterAccount struct
+    let mut counter_data = CounterAccount::load(&data)?;
+ 
+    // Only the stored authority may change the counter's configuration
+    counter_data.check_authority(authority_account)?;
+    counter_data.check_not_frozen()?;
+ 
+    let previous_count = counter_data.count;
+    update(&mut counter_data)?;
+    // Configuration may change under a time lock, the count may not. A count
+    // change is stamped and announced as any other count update is.
+    let count_changed = counter_data.count != previous_count;
+    if count_changed {
+        let now = Clock::get()?.unix_timestamp;
+        counter_data.check_unlocked(now)?;
+        counter_data.last_updated = now;
+    }
+    counter_data.record_growth(previous_count)?;
+    counter_data.apply_auto_freeze();
+    counter_data.bump_nonce();
+ 
+    // Serialize the updated counter data back into the account
+    counter_data.store(&mut data)?;
+    if count_changed {
+        counter_data.emit_event(CounterEvent::CountChanged {
+            counter: *counter_account.key,
+            count: counter_data.count,
+        })?;
+    }
+    Ok(counter_data)
+}
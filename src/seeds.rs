@@ -0,0 +1,76 @@
+//! PDA seeds, shared by client-side address derivation and the program's
+//! signing and validation so the two cannot drift apart.
+use crate::state::{CounterAccount, CounterMetadata, GlobalConfig, HistoryEntry};
+use solana_program::pubkey::Pubkey;
+ 
+// Seeds of the counter PDA owned by `authority`
+pub fn counter_seeds(authority: &Pubkey) -> [&[u8]; 2] {
+    [CounterAccount::PDA_SEED, authority.as_ref()]
+}
+ 
+// Seeds of the counter PDA associated with `mint`
+pub fn mint_counter_seeds(mint: &Pubkey) -> [&[u8]; 2] {
+    [CounterAccount::MINT_SEED, mint.as_ref()]
+}
+ 
+// Seeds of the history PDA recording `counter`'s checkpoints
+pub fn history_seeds(counter: &Pubkey) -> [&[u8]; 2] {
+    [HistoryEntry::SEED, counter.as_ref()]
+}
+ 
+// Seeds of the metadata PDA attached to `counter`
+pub fn meta_seeds(counter: &Pubkey) -> [&[u8]; 2] {
+    [CounterMetadata::SEED, counter.as_ref()]
+}
+ 
+// Seeds of the program-wide config PDA
+pub fn config_seeds() -> [&'static [u8]; 1] {
+    [GlobalConfig::SEED]
+}
+ 
+// `seeds` followed by the bump, as `invoke_signed` and
+// `create_program_address` take them
+pub fn with_bump<'a>(seeds: &[&'a [u8]], bump: &'a [u8; 1]) -> Vec<&'a [u8]> {
+    let mut signer_seeds = seeds.to_vec();
+    signer_seeds.push(bump);
+    signer_seeds
+}
+ 
+#[cfg(test)]
+mod test {
+    use super::*;
+ 
+    #[test]
+    fn test_derived_addresses_validate_with_their_bumps() {
+        let program_id = Pubkey::new_from_array([6; 32]);
+        let key = Pubkey::new_from_array([7; 32]);
+        let derived = [
+            (
+                CounterAccount::find_pda(&program_id, &key),
+                counter_seeds(&key).to_vec(),
+            ),
+            (
+                CounterAccount::find_mint_pda(&program_id, &key),
+                mint_counter_seeds(&key).to_vec(),
+            ),
+            (
+                HistoryEntry::find_address(&program_id, &key),
+                history_seeds(&key).to_vec(),
+            ),
+            (
+                CounterMetadata::find_address(&program_id, &key),
+                meta_seeds(&key).to_vec(),
+            ),
+            (
+                GlobalConfig::find_address(&program_id),
+                config_seeds().to_vec(),
+            ),
+        ];
+        for ((address, bump), seeds) in derived {
+            assert_eq!(
+                Pubkey::create_program_address(&with_bump(&seeds, &[bump]), &program_id),
+                Ok(address)
+            );
+        }
+    }
+}
@@ -0,0 +1,822 @@
+//! Account layouts, return-data types and events.
+use crate::{error::CounterError, seeds};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    hash::{hash, hashv, HASH_BYTES},
+    log::sol_log_data,
+    msg,
+    program::{set_return_data, MAX_RETURN_DATA},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_instruction::MAX_PERMITTED_DATA_LENGTH,
+    sysvar::{rent::Rent, Sysvar},
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+ 
+// Return data layout shared by the handlers that set it and the client
+// decoders: values are 8-byte LE u64s, and mutating handlers end with the
+// hash of the counter's post-state
+pub const RETURN_U64_LEN: usize = 8;
+pub const STATE_HASH_LEN: usize = HASH_BYTES;
+ 
+// Leaf of `member` in a Merkle allowlist
+pub fn merkle_leaf(member: &Pubkey) -> [u8; 32] {
+    hash(member.as_ref()).to_bytes()
+}
+ 
+// Parent of two allowlist nodes. The children are hashed in sorted order, so
+// proofs need not say which side each sibling is on.
+pub fn merkle_parent(first: &[u8; 32], second: &[u8; 32]) -> [u8; 32] {
+    let (left, right) = if first <= second {
+        (first, second)
+    } else {
+        (second, first)
+    };
+    hashv(&[left, right]).to_bytes()
+}
+ 
+// Set `bytes` as the instruction's return data, failing with
+// ReturnDataTooLarge past the runtime's limit instead of letting it truncate
+pub(crate) fn set_return_data_bounded(bytes: &[u8]) -> ProgramResult {
+    if bytes.len() > MAX_RETURN_DATA {
+        return Err(CounterError::ReturnDataTooLarge.into());
+    }
+    set_return_data(bytes);
+    Ok(())
+}
+ 
+// Struct representing our counter account's data
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+pub struct CounterAccount {
+    pub discriminator: [u8; 8],
+    pub count: u64,
+    pub authority: Pubkey,
+    pub require_signer: bool,
+    pub delegate_pda: Pubkey,
+    pub max_value: u64,
+    pub step: u64,
+    pub cooldown_secs: u32,
+    pub last_updated: i64,
+    pub last_reason: u8,
+    pub decimals: u8,
+    pub max_step_per_call: u64,
+    pub nonce: u64,
+    pub min_reserve: u64,
+    pub auto_freeze_at_max: bool,
+    pub is_frozen: bool,
+    pub bump: u8,
+    pub sum: u128,
+    pub samples: u64,
+    pub lifetime_total: u64,
+    pub min_value: u64,
+    pub increment_count: u64,
+    pub category: u16,
+    pub saturating: bool,
+    pub allow_zero: bool,
+    pub pending_authority: Pubkey,
+    pub gate_mint: Pubkey,
+    pub gate_min_amount: u64,
+    pub emit_events: bool,
+    pub daily_limit: u64,
+    pub daily_spent: u64,
+    pub day_start: i64,
+    pub locked_until: i64,
+    pub expires_at_slot: u64,
+    pub total_tips: u64,
+    pub last_increment_slot: u64,
+    pub merkle_root: [u8; 32],
+    pub delegates: [Pubkey; CounterAccount::MAX_DELEGATES],
+}
+ 
+// The baseline configuration: zero count, no authority, flags off apart from
+// allow_zero, step 1 and no upper bounds
+impl Default for CounterAccount {
+    fn default() -> Self {
+        Self {
+            discriminator: Self::DISCRIMINATOR,
+            count: 0,
+            authority: Pubkey::default(),
+            require_signer: false,
+            delegate_pda: Pubkey::default(),
+            max_value: u64::MAX,
+            step: 1,
+            cooldown_secs: 0,
+            last_updated: 0,
+            last_reason: 0,
+            decimals: 0,
+            max_step_per_call: u64::MAX,
+            nonce: 0,
+            min_reserve: 0,
+            auto_freeze_at_max: false,
+            is_frozen: false,
+            bump: 0,
+            sum: 0,
+            samples: 0,
+            lifetime_total: 0,
+            min_value: 0,
+            increment_count: 0,
+            category: 0,
+            saturating: false,
+            allow_zero: true,
+            pending_authority: Pubkey::default(),
+            gate_mint: Pubkey::default(),
+            gate_min_amount: 0,
+            emit_events: true,
+            daily_limit: u64::MAX,
+            daily_spent: 0,
+            day_start: 0,
+            locked_until: 0,
+            expires_at_slot: 0,
+            total_tips: 0,
+            last_increment_slot: 0,
+            merkle_root: [0; 32],
+            delegates: [Pubkey::default(); Self::MAX_DELEGATES],
+        }
+    }
+}
+ 
+impl CounterAccount {
+    // Length of the window DecrementDaily spends against
+    pub const DAY_SECS: i64 = 24 * 60 * 60;
+ 
+    // How far IncrementAt's client_ts may run ahead of the cluster clock
+    pub const MAX_CLIENT_TS_SKEW_SECS: i64 = 60;
+ 
+    // Serialized size in bytes
+    pub const LEN: usize = 8
+        + 8
+        + 32
+        + 1
+        + 32
+        + 8
+        + 8
+        + 4
+        + 8
+        + 1
+        + 1
+        + 8
+        + 8
+        + 8
+        + 1
+        + 1
+        + 1
+        + 16
+        + 8
+        + 8
+        + 8
+        + 8
+        + 2
+        + 1
+        + 1
+        + 32
+        + 32
+        + 8
+        + 1
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8
+        + 32
+        + 32 * Self::MAX_DELEGATES;
+ 
+    // Leading bytes identifying a counter account
+    pub const DISCRIMINATOR: [u8; 8] = *b"counter\0";
+ 
+    // Byte offset of the little-endian `count`, stable across layout changes so
+    // off-chain readers can fetch just those 8 bytes. New fields go after it.
+    pub const COUNT_OFFSET: usize = 8;
+ 
+    // Size of accounts created before the layout held more than the count
+    pub const LEGACY_LEN: usize = 8;
+ 
+    // Layout versions reported by DescribeCounter; 0 means unrecognized data
+    pub const LEGACY_VERSION: u8 = 1;
+    pub const VERSION: u8 = 2;
+ 
+    // Largest `decimals` whose scale, 10^decimals, fits in a u64
+    pub const MAX_DECIMALS: u8 = 19;
+ 
+    // Largest growth IncrementPercent accepts, in basis points (500%)
+    pub const MAX_INCREMENT_BPS: u16 = 50_000;
+ 
+    // Most delegates that may sign count updates alongside the authority
+    pub const MAX_DELEGATES: usize = 4;
+ 
+    // Seed prefix of per-authority counter PDAs
+    pub const PDA_SEED: &'static [u8] = b"counter";
+ 
+    // Seed prefix of per-mint counter PDAs
+    pub const MINT_SEED: &'static [u8] = b"mint_counter";
+ 
+    // A counter holding `count` with the default configuration
+    pub(crate) fn new(count: u64, authority: Pubkey, now: i64) -> Self {
+        Self {
+            count,
+            authority,
+            last_updated: now,
+            ..Self::default()
+        }
+    }
+ 
+    // Address and bump of the counter PDA tallying for `mint`
+    pub fn find_mint_pda(program_id: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&seeds::mint_counter_seeds(mint), program_id)
+    }
+ 
+    // Address and bump of the counter PDA owned by `authority`
+    pub fn find_pda(program_id: &Pubkey, authority: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&seeds::counter_seeds(authority), program_id)
+    }
+ 
+    // Truncated average of the samples added so far, if any
+    pub fn mean(&self) -> Option<u64> {
+        let samples = u128::from(self.samples);
+        u64::try_from(self.sum.checked_div(samples)?).ok()
+    }
+ 
+    // Account size for a counter followed by `extra` reserved bytes, which may be
+    // derived from client input, so the cast is checked and the total bounded
+    pub fn space_for(extra: u64) -> Result<usize, ProgramError> {
+        usize::try_from(extra)
+            .ok()
+            .and_then(|extra| Self::LEN.checked_add(extra))
+            .filter(|space| *space as u64 <= MAX_PERMITTED_DATA_LENGTH)
+            .ok_or_else(|| CounterError::ValueTooLarge.into())
+    }
+ 
+    // Deserialize account data, warning when it still uses the legacy layout
+    pub fn load(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() == Self::LEGACY_LEN {
+            msg!("Warning: legacy counter account, run MigrateCounter to upgrade it");
+        }
+        Self::unpack(data)
+    }
+ 
+    // Deserialize account data, rejecting anything without the counter discriminator.
+    // Legacy count-only accounts unpack with default configuration until migrated.
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() == Self::LEGACY_LEN {
+            let count = u64::try_from_slice(data).map_err(|_| ProgramError::InvalidAccountData)?;
+            return Ok(Self::new(count, Pubkey::default(), 0));
+        }
+        let counter_data =
+            Self::try_from_slice(data).map_err(|_| ProgramError::InvalidAccountData)?;
+        if counter_data.discriminator != Self::DISCRIMINATOR {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(counter_data)
+    }
+ 
+    // Identify which counter layout, if any, the account data uses
+    pub fn layout_version(data: &[u8]) -> u8 {
+        if data.len() == Self::LEGACY_LEN {
+            Self::LEGACY_VERSION
+        } else if data.len() == Self::LEN && data.starts_with(&Self::DISCRIMINATOR) {
+            Self::VERSION
+        } else {
+            0
+        }
+    }
+ 
+    // The count interpreted as a fixed-point quantity, `count / 10^decimals`
+    pub fn ui_amount(&self) -> f64 {
+        self.count as f64 / 10f64.powi(i32::from(self.decimals))
+    }
+ 
+    // The scaled count formatted with integer math, for logs
+    pub(crate) fn display_amount(&self) -> String {
+        // Decimals are validated at initialization, but stay safe for any stored value
+        let Some(scale) = 10u64.checked_pow(u32::from(self.decimals)) else {
+            return self.count.to_string();
+        };
+        if scale == 1 {
+            return self.count.to_string();
+        }
+        format!(
+            "{}.{:0width$}",
+            self.count / scale,
+            self.count % scale,
+            width = usize::from(self.decimals)
+        )
+    }
+ 
+    // Add any increase over `previous_count` to the lifetime total, which
+    // resets never touch, and count the increment
+    pub(crate) fn record_growth(&mut self, previous_count: u64) -> ProgramResult {
+        let growth = self.count.saturating_sub(previous_count);
+        self.lifetime_total = self
+            .lifetime_total
+            .checked_add(growth)
+            .ok_or(CounterError::Overflow)?;
+        if growth > 0 {
+            self.increment_count = self.increment_count.saturating_add(1);
+        }
+        Ok(())
+    }
+ 
+    // A frozen counter refuses every further change
+    pub(crate) fn check_not_frozen(&self) -> ProgramResult {
+        if self.is_frozen {
+            return Err(CounterError::Frozen.into());
+        }
+        Ok(())
+    }
+ 
+    // Lock the counter once it reaches max_value, if configured to
+    pub(crate) fn apply_auto_freeze(&mut self) {
+        if self.auto_freeze_at_max && self.count == self.max_value {
+            self.is_frozen = true;
+            msg!("Counter reached max_value and is now frozen");
+        }
+    }
+ 
+    // Record a state change; clients compare nonces to detect concurrent writes
+    pub(crate) fn bump_nonce(&mut self) {
+        self.nonce = self.nonce.wrapping_add(1);
+    }
+ 
+    // Write the account state, serializing into a scratch buffer first so an
+    // undersized account is rejected before any of its bytes are overwritten
+    pub(crate) fn store(&self, data: &mut [u8]) -> ProgramResult {
+        let bytes = borsh::to_vec(self)?;
+        data.get_mut(..bytes.len())
+            .ok_or(ProgramError::AccountDataTooSmall)?
+            .copy_from_slice(&bytes);
+        Ok(())
+    }
+ 
+    // Set the return data of a mutating handler: its own `prefix`, if any,
+    // followed by the 32-byte hash of the serialized post-state so light
+    // clients can check it against the state they expect
+    pub(crate) fn return_state_hash(&self, prefix: &[u8]) -> ProgramResult {
+        let digest = hash(&borsh::to_vec(self)?);
+        set_return_data_bounded(&[prefix, digest.as_ref()].concat())
+    }
+ 
+    // Only the stored authority, signing, may reconfigure or drain the counter
+    pub(crate) fn check_authority(&self, authority_account: &AccountInfo) -> ProgramResult {
+        if !authority_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if self.authority != *authority_account.key {
+            return Err(CounterError::Unauthorized.into());
+        }
+        Ok(())
+    }
+ 
+    // Fail with NotInAllowlist unless `proof` leads from `leaf` to the stored
+    // merkle_root. The all-zero root of a counter without an allowlist admits
+    // no one.
+    pub(crate) fn check_in_tree(&self, leaf: &[u8; 32], proof: &[[u8; 32]]) -> ProgramResult {
+        let root = proof
+            .iter()
+            .fold(*leaf, |node, sibling| merkle_parent(&node, sibling));
+        if self.merkle_root == [0; 32] || root != self.merkle_root {
+            return Err(CounterError::NotInAllowlist.into());
+        }
+        Ok(())
+    }
+ 
+    // An account of `data_len` bytes keeping `lamports` must still cover rent
+    // plus the configured reserve
+    pub(crate) fn check_reserve(&self, lamports: u64, data_len: usize) -> ProgramResult {
+        let required = Rent::get()?
+            .minimum_balance(data_len)
+            .saturating_add(self.min_reserve);
+        if lamports < required {
+            return Err(CounterError::ReserveBreached.into());
+        }
+        Ok(())
+    }
+ 
+    // When `require_signer` is set, the next account must be a signer that is
+    // the authority, the delegate PDA (signing through invoke_signed) or one of
+    // the listed delegates
+    pub(crate) fn check_signer<'a, 'b: 'a>(
+        &self,
+        accounts_iter: &mut impl Iterator<Item = &'a AccountInfo<'b>>,
+    ) -> ProgramResult {
+        if !self.require_signer {
+            return Ok(());
+        }
+        let signer_account = next_account_info(accounts_iter)?;
+        if !signer_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let is_delegate = (self.delegate_pda != Pubkey::default()
+            && *signer_account.key == self.delegate_pda)
+            || self.delegates.contains(signer_account.key);
+        if *signer_account.key != self.authority && !is_delegate {
+            return Err(CounterError::Unauthorized.into());
+        }
+        Ok(())
+    }
+ 
+    // Append `delegate` to the delegate list, rejecting the zero pubkey and
+    // delegates already listed
+    pub(crate) fn add_delegate(&mut self, delegate: Pubkey) -> ProgramResult {
+        if delegate == Pubkey::default() {
+            return Err(CounterError::InvalidDelegate.into());
+        }
+        if self.delegates.contains(&delegate) {
+            return Err(CounterError::DelegateAlreadyExists.into());
+        }
+        let free = self
+            .delegates
+            .iter_mut()
+            .find(|listed| **listed == Pubkey::default())
+            .ok_or(CounterError::TooManyDelegates)?;
+        *free = delegate;
+        Ok(())
+    }
+ 
+    // Remove `delegate`, shifting the later delegates down so the listed ones
+    // stay contiguous at the front
+    pub(crate) fn remove_delegate(&mut self, delegate: Pubkey) -> ProgramResult {
+        let index = self
+            .delegates
+            .iter()
+            .position(|listed| *listed == delegate && delegate != Pubkey::default())
+            .ok_or(CounterError::InvalidDelegate)?;
+        self.delegates.copy_within(index + 1.., index);
+        if let Some(last) = self.delegates.last_mut() {
+            *last = Pubkey::default();
+        }
+        Ok(())
+    }
+ 
+    // Reject a client-supplied configuration no counter may start from: a zero
+    // step, a count outside its bounds or more than MAX_DECIMALS decimals
+    pub(crate) fn validate_config(&self) -> ProgramResult {
+        if self.step == 0 {
+            return Err(CounterError::InvalidStep.into());
+        }
+        if self.count > self.max_value {
+            return Err(CounterError::InitialExceedsMax.into());
+        }
+        if self.count < self.min_value {
+            return Err(CounterError::InitialBelowMin.into());
+        }
+        if self.decimals > Self::MAX_DECIMALS {
+            return Err(CounterError::InvalidDecimals.into());
+        }
+        Ok(())
+    }
+ 
+    // Reject count changes before the locked_until timestamp
+    pub(crate) fn check_unlocked(&self, now: i64) -> ProgramResult {
+        if now < self.locked_until {
+            return Err(CounterError::TimeLocked.into());
+        }
+        Ok(())
+    }
+ 
+    // Reject updates arriving sooner than `cooldown_secs` after the last one
+    pub(crate) fn check_cooldown(&self, now: i64) -> ProgramResult {
+        let elapsed = now.saturating_sub(self.last_updated);
+        if elapsed < i64::from(self.cooldown_secs) {
+            return Err(CounterError::CooldownActive.into());
+        }
+        Ok(())
+    }
+ 
+    // Take over `template`'s step, bounds, cooldown and behaviour flags, leaving
+    // the count, authority, bookkeeping and frozen state alone
+    pub(crate) fn copy_config_from(&mut self, template: &CounterAccount) -> ProgramResult {
+        if template.max_value < self.count {
+            return Err(CounterError::MaxValueBelowCount.into());
+        }
+        if template.min_value > self.count {
+            return Err(CounterError::BelowMinValue.into());
+        }
+        self.step = template.step;
+        self.max_value = template.max_value;
+        self.min_value = template.min_value;
+        self.cooldown_secs = template.cooldown_secs;
+        self.max_step_per_call = template.max_step_per_call;
+        self.decimals = template.decimals;
+        self.require_signer = template.require_signer;
+        self.auto_freeze_at_max = template.auto_freeze_at_max;
+        self.saturating = template.saturating;
+        self.allow_zero = template.allow_zero;
+        self.emit_events = template.emit_events;
+        Ok(())
+    }
+}
+ 
+// Account metadata returned by DescribeCounter
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+pub struct CounterDescription {
+    pub version: u8,
+    pub owner_matches: bool,
+    pub data_len: u64,
+    pub rent_exempt: bool,
+    // The counter's mutation nonce, if the data decodes as a counter
+    pub nonce: Option<u64>,
+    // The counter's grouping category, if the data decodes as a counter
+    pub category: Option<u16>,
+}
+ 
+// Monitoring snapshot returned by Metrics, stable across account layout changes
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+pub struct CounterMetrics {
+    pub count: u64,
+    pub nonce: u64,
+    pub increment_count: u64,
+    pub last_updated: i64,
+    pub is_frozen: bool,
+}
+ 
+// Structured events emitted through `sol_log_data` as borsh bytes, decoded by
+// `client::parse_events`
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub enum CounterEvent {
+    Initialized {
+        counter: Pubkey,
+        authority: Pubkey,
+        count: u64,
+    },
+    CountChanged {
+        counter: Pubkey,
+        count: u64,
+    },
+}
+ 
+impl CounterAccount {
+    // Emit `event` unless the counter has structured events switched off
+    pub(crate) fn emit_event(&self, event: CounterEvent) -> ProgramResult {
+        if self.emit_events {
+            event.emit()?;
+        }
+        Ok(())
+    }
+}
+ 
+impl CounterEvent {
+    // Log the event as a single "Program data:" entry
+    pub(crate) fn emit(&self) -> ProgramResult {
+        sol_log_data(&[&borsh::to_vec(self)?]);
+        Ok(())
+    }
+}
+ 
+// Program-wide settings stored in the PDA seeded by `[b"config"]`
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct GlobalConfig {
+    pub discriminator: [u8; 8],
+    pub paused: bool,
+    pub bump: u8,
+    pub admin: Pubkey,
+    pub creation_fee: u64,
+    pub treasury: Pubkey,
+}
+ 
+impl GlobalConfig {
+    // Serialized size in bytes
+    pub const LEN: usize = 8 + 1 + 1 + 32 + 8 + 32;
+ 
+    // Leading bytes identifying the config account
+    pub const DISCRIMINATOR: [u8; 8] = *b"config\0\0";
+ 
+    pub const SEED: &'static [u8] = b"config";
+ 
+    pub fn find_address(program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&seeds::config_seeds(), program_id)
+    }
+ 
+    // An unpaused config naming `admin`, charging no creation fee
+    pub fn new(bump: u8, admin: Pubkey) -> Self {
+        Self {
+            discriminator: Self::DISCRIMINATOR,
+            paused: false,
+            bump,
+            admin,
+            creation_fee: 0,
+            treasury: Pubkey::default(),
+        }
+    }
+ 
+    // The config account among `accounts`, if it was passed and created. The
+    // config is optional, so it is located by its PDA address; an account that
+    // merely looks like a config is never taken for it.
+    pub(crate) fn find(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> Result<Option<Self>, ProgramError> {
+        let (config_address, _) = Self::find_address(program_id);
+        let config_account = accounts
+            .iter()
+            .find(|account| *account.key == config_address);
+        let Some(config_account) = config_account else {
+            return Ok(None);
+        };
+        // Only this program can create an account at the PDA, so one it does
+        // not own is a config that does not exist yet
+        if config_account.owner != program_id {
+            return Ok(None);
+        }
+        Self::try_from_slice(&config_account.data.borrow())
+            .ok()
+            .filter(|config| config.discriminator == Self::DISCRIMINATOR)
+            .map(Some)
+            .ok_or(ProgramError::InvalidAccountData)
+    }
+ 
+    // Fail with GloballyPaused if the config account was passed and is paused
+    pub(crate) fn check_not_paused(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        match Self::find(program_id, accounts)? {
+            Some(config) if config.paused => Err(CounterError::GloballyPaused.into()),
+            _ => Ok(()),
+        }
+    }
+}
+ 
+// The fields of an SPL Token account that IncrementIfHolder inspects, read
+// straight from the token program's 165-byte account layout
+#[derive(Debug, PartialEq)]
+pub struct TokenHolding {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+}
+ 
+impl TokenHolding {
+    pub const PROGRAM_ID: Pubkey =
+        solana_program::pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+ 
+    // Serialized size of a token account
+    pub const LEN: usize = 165;
+ 
+    // Serialized size of a mint
+    pub const MINT_LEN: usize = 82;
+ 
+    // Offset of the account state byte: 1 is initialized, 2 frozen
+    const STATE_OFFSET: usize = 108;
+ 
+    // Read the holding from an initialized or frozen token account's data
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        let pubkey_at = |at: usize| {
+            data.get(at..at + 32)
+                .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+                .map(Pubkey::new_from_array)
+        };
+        let amount = data
+            .get(64..72)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(u64::from_le_bytes);
+        let state = data.get(Self::STATE_OFFSET);
+        match (pubkey_at(0), pubkey_at(32), amount, state) {
+            (Some(mint), Some(owner), Some(amount), Some(1 | 2)) if data.len() == Self::LEN => {
+                Ok(Self {
+                    mint,
+                    owner,
+                    amount,
+                })
+            }
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+}
+ 
+// One Checkpoint record. A counter's history PDA, seeded by
+// `[b"history", counter]`, holds these back to back, oldest first.
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+pub struct HistoryEntry {
+    pub count: u64,
+    pub slot: u64,
+}
+ 
+impl HistoryEntry {
+    // Serialized size in bytes
+    pub const LEN: usize = 8 + 8;
+ 
+    // Most entries a history account may hold
+    pub const MAX_ENTRIES: usize = 64;
+ 
+    pub const SEED: &'static [u8] = b"history";
+ 
+    pub fn find_address(program_id: &Pubkey, counter: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&seeds::history_seeds(counter), program_id)
+    }
+}
+ 
+// Sixteen counts sharing one rent-exempt account, addressed by slot index
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+pub struct MultiCounter {
+    pub discriminator: [u8; 8],
+    pub authority: Pubkey,
+    pub counts: [u64; MultiCounter::SLOTS],
+}
+ 
+impl MultiCounter {
+    // Number of counts held
+    pub const SLOTS: usize = 16;
+ 
+    // Serialized size in bytes
+    pub const LEN: usize = 8 + 32 + Self::SLOTS * 8;
+ 
+    // Leading bytes identifying a multi-counter account
+    pub const DISCRIMINATOR: [u8; 8] = *b"multictr";
+ 
+    // Every slot at zero, reconfigurable by `authority`
+    pub fn new(authority: Pubkey) -> Self {
+        Self {
+            discriminator: Self::DISCRIMINATOR,
+            authority,
+            counts: [0; Self::SLOTS],
+        }
+    }
+ 
+    // Deserialize account data, rejecting anything without the multi-counter
+    // discriminator
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        Self::try_from_slice(data)
+            .ok()
+            .filter(|multi| multi.discriminator == Self::DISCRIMINATOR)
+            .ok_or(ProgramError::InvalidAccountData)
+    }
+ 
+    // Serialize into account data
+    pub(crate) fn store(&self, data: &mut [u8]) -> ProgramResult {
+        let bytes = borsh::to_vec(self)?;
+        data.get_mut(..bytes.len())
+            .ok_or(ProgramError::AccountDataTooSmall)?
+            .copy_from_slice(&bytes);
+        Ok(())
+    }
+ 
+    // The count at `slot`, failing with SlotOutOfRange past the last one
+    pub(crate) fn slot_mut(&mut self, slot: u8) -> Result<&mut u64, CounterError> {
+        self.counts
+            .get_mut(usize::from(slot))
+            .ok_or(CounterError::SlotOutOfRange)
+    }
+}
+ 
+// Key-value metadata attached to a counter, stored in the PDA seeded by
+// `[b"meta", counter]` and sized to fit its entries
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, PartialEq)]
+pub struct CounterMetadata {
+    pub entries: Vec<(String, String)>,
+}
+ 
+impl CounterMetadata {
+    // Largest serialized size the metadata may grow to
+    pub const MAX_LEN: usize = 1024;
+ 
+    pub const SEED: &'static [u8] = b"meta";
+ 
+    pub fn find_address(program_id: &Pubkey, counter: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&seeds::meta_seeds(counter), program_id)
+    }
+ 
+    // Set `key` to `value`, replacing the value of an existing key in place
+    pub(crate) fn set(&mut self, key: String, value: String) {
+        match self
+            .entries
+            .iter_mut()
+            .find(|(existing, _)| *existing == key)
+        {
+            Some((_, existing_value)) => *existing_value = value,
+            None => self.entries.push((key, value)),
+        }
+    }
+ 
+    // Drop `key` if present
+    pub(crate) fn remove(&mut self, key: &str) {
+        self.entries.retain(|(existing, _)| existing != key);
+    }
+}
+ 
+#[cfg(test)]
+mod test {
+    // Only `state` is imported, the way an off-chain reader of counters would
+    use crate::state::{CounterAccount, GlobalConfig};
+    use solana_program::pubkey::Pubkey;
+ 
+    #[test]
+    fn test_state_types_round_trip_on_their_own() {
+        let authority = Pubkey::new_from_array([4; 32]);
+        let counter = CounterAccount {
+            count: 12,
+            authority,
+            step: 3,
+            ..CounterAccount::default()
+        };
+        let data = borsh::to_vec(&counter).unwrap();
+        assert_eq!(data.len(), CounterAccount::LEN);
+        assert_eq!(
+            CounterAccount::layout_version(&data),
+            CounterAccount::VERSION
+        );
+        assert_eq!(CounterAccount::load(&data).unwrap(), counter);
+ 
+        let program_id = Pubkey::new_from_array([5; 32]);
+        let (pda, _) = CounterAccount::find_pda(&program_id, &authority);
+        assert_ne!(pda, GlobalConfig::find_address(&program_id).0);
+    }
+}
@@ -0,0 +1,118 @@
+// Builtins under program-test print sol_log_data to stdout rather than the
+// transaction logs, so events are checked here by calling the processor
+// directly under syscall stubs that record them. The stubs are process-wide,
+// which is why this lives in its own test binary.
+ 
+use borsh::BorshDeserialize;
+use counter::{process_instruction, CounterAccount, CounterEvent, CounterInstruction};
+use solana_program::{
+    account_info::AccountInfo, clock::Clock, entrypoint::SUCCESS, program_stubs::SyscallStubs,
+    pubkey::Pubkey,
+};
+use std::sync::Mutex;
+ 
+const NOW: i64 = 1_700_000_000;
+ 
+static EVENTS: Mutex<Vec<Vec<u8>>> = Mutex::new(Vec::new());
+ 
+struct RecordingStubs;
+ 
+impl SyscallStubs for RecordingStubs {
+    fn sol_log(&self, _message: &str) {}
+ 
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        let clock = Clock {
+            unix_timestamp: NOW,
+            ..Clock::default()
+        };
+        unsafe { std::ptr::write(var_addr.cast::<Clock>(), clock) };
+        SUCCESS
+    }
+ 
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        let mut events = EVENTS.lock().unwrap();
+        events.extend(fields.iter().map(|field| field.to_vec()));
+    }
+}
+ 
+// Run `instruction` from its authority against a counter holding `count`
+// with events on, returning the stored state and the events emitted
+fn run_authority_update(
+    count: u64,
+    instruction: CounterInstruction,
+) -> (CounterAccount, Vec<CounterEvent>) {
+    let program_id = Pubkey::new_unique();
+    let counter = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let counter_data = CounterAccount {
+        count,
+        authority,
+        emit_events: true,
+        ..CounterAccount::default()
+    };
+    let mut data = vec![0; CounterAccount::LEN];
+    let bytes = borsh::to_vec(&counter_data).unwrap();
+    data[..bytes.len()].copy_from_slice(&bytes);
+    let mut counter_lamports = 1_000_000;
+    let mut authority_lamports = 1_000_000;
+    let mut authority_data = [];
+    let system_program = Pubkey::default();
+    let accounts = [
+        AccountInfo::new(
+            &counter,
+            false,
+            true,
+            &mut counter_lamports,
+            &mut data,
+            &program_id,
+            false,
+            0,
+        ),
+        AccountInfo::new(
+            &authority,
+            true,
+            false,
+            &mut authority_lamports,
+            &mut authority_data,
+            &system_program,
+            false,
+            0,
+        ),
+    ];
+ 
+    EVENTS.lock().unwrap().clear();
+    process_instruction(&program_id, &accounts, &instruction.pack()).unwrap();
+    let events = EVENTS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|event| CounterEvent::try_from_slice(event).unwrap())
+        .collect();
+    let stored = CounterAccount::unpack(&accounts[0].data.borrow()).unwrap();
+    (stored, events)
+}
+ 
+#[test]
+fn test_authority_count_changes_are_stamped_and_announced() {
+    solana_program::program_stubs::set_syscall_stubs(Box::new(RecordingStubs));
+ 
+    for (instruction, expected) in [
+        (CounterInstruction::SetCounter { value: 9 }, 9),
+        (CounterInstruction::ResetCounter, 0),
+        (CounterInstruction::DrainCounter, 0),
+    ] {
+        let (stored, events) = run_authority_update(5, instruction);
+        assert_eq!(stored.count, expected);
+        assert_eq!(stored.last_updated, NOW);
+        assert!(matches!(
+            events.as_slice(),
+            [CounterEvent::CountChanged { count, .. }] if *count == expected
+        ));
+    }
+ 
+    // A configuration change leaves the count, its stamp and the event log alone
+    let (stored, events) = run_authority_update(5, CounterInstruction::SetCategory { category: 3 });
+    assert_eq!(stored.category, 3);
+    assert_eq!(stored.last_updated, 0);
+    assert!(events.is_empty());
+}